@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A file sent along with a user message.
+ */
+export type Attachment = { id: string, 
+/**
+ * File name shown in the chat and given to the model.
+ */
+name: string, mime_type: string, 
+/**
+ * Size in bytes.
+ */
+size: number, 
+/**
+ * Hex SHA-256 of the contents; identical files are stored once.
+ */
+sha256: string, 
+/**
+ * Small JPEG preview of an image as a `data:` URL; `None` for text.
+ */
+thumbnail: string | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A file to attach: one the user picked on disk, text pasted as a file, or
+ * a pasted image or document as base64.
+ */
+export type AttachmentInput = { "type": "path", path: string, } | { "type": "pasted", name: string, content: string, } | { "type": "image", name: string, data: string, } | { "type": "document", name: string, data: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A built-in tool and whether it is enabled in a conversation.
+ */
+export type BuiltinToolInfo = { name: string, description: string, enabled: boolean, };
@@ -1,3 +1,19 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type Conversation = { id: string, title: string, model: string | null, created_at: string, updated_at: string, };
+export type Conversation = { id: string, title: string, model: string | null, created_at: string, updated_at: string, 
+/**
+ * Conversation this one was forked from, if any.
+ */
+forked_from_conversation_id: string | null, 
+/**
+ * Message of the source conversation the fork was taken at.
+ */
+forked_from_message_id: string | null, 
+/**
+ * Folder the conversation's sessions work in; the sandbox when `None`.
+ */
+workspace_folder: string | null, 
+/**
+ * Persona the conversation was started with, if any.
+ */
+persona_id: string | null, };
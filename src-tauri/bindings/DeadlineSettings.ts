@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ResponseDeadlines } from "./ResponseDeadlines";
+
+/**
+ * Global response limits plus overrides keyed by model id, stored as the
+ * `response_deadlines` setting.
+ */
+export type DeadlineSettings = { default: ResponseDeadlines, models: { [key in string]?: ResponseDeadlines }, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Conversation } from "./Conversation";
+import type { Message } from "./Message";
+import type { SessionStart } from "./SessionStart";
+
+/**
+ * Returned by `fork_conversation`.
+ */
+export type ForkedConversation = { conversation: Conversation, 
+/**
+ * Copies of the messages up to the fork point, oldest first.
+ */
+messages: Array<Message>, 
+/**
+ * Session seeded with `messages`; `None` when the client is not running,
+ * in which case the first prompt opens one as usual.
+ */
+session: SessionStart | null, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { McpServer } from "./McpServer";
+
+/**
+ * Result of importing the CLI's MCP config.
+ */
+export type McpImport = { imported: Array<McpServer>, 
+/**
+ * Names left out because a server with that name exists or the entry
+ * could not be read.
+ */
+skipped: Array<string>, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { McpTransport } from "./McpTransport";
+
+/**
+ * An MCP server the user configured.
+ */
+export type McpServer = { id: string, 
+/**
+ * Unique name, also the key the CLI knows the server by.
+ */
+name: string, 
+/**
+ * Disabled servers are kept but not given to new sessions.
+ */
+enabled: boolean, transport: McpTransport, created_at: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { McpTransport } from "./McpTransport";
+
+/**
+ * Fields of an MCP server the user can edit.
+ */
+export type McpServerInput = { name: string, enabled: boolean, transport: McpTransport, };
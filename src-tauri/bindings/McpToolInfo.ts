@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A tool reported by an MCP server.
+ */
+export type McpToolInfo = { name: string, description: string | null, };
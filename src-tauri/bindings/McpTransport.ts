@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How the CLI reaches an MCP server.
+ */
+export type McpTransport = { "type": "local", command: string, args: Array<string>, env: { [key in string]?: string }, cwd: string | null, } | { "type": "remote", url: string, headers: { [key in string]?: string }, };
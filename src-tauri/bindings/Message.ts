@@ -1,3 +1,29 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Attachment } from "./Attachment";
+import type { ToolCall } from "./ToolCall";
 
-export type Message = { id: string, conversation_id: string, role: string, content: string, created_at: string, };
+export type Message = { id: string, conversation_id: string, role: string, content: string, created_at: string, 
+/**
+ * True when the reply was cut short by `abort_message`.
+ */
+interrupted: boolean, 
+/**
+ * Message this one answers or follows; `None` for the first message.
+ */
+parent_id: string | null, 
+/**
+ * Position among messages sharing the same parent (regenerations and edits).
+ */
+sibling_index: number, 
+/**
+ * Number of messages sharing the same parent, including this one.
+ */
+sibling_count: number, 
+/**
+ * Tools the agent ran while producing this reply, in the order they started.
+ */
+tool_calls: Array<ToolCall>, 
+/**
+ * Files sent along with this prompt.
+ */
+attachments: Array<Attachment>, };
@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type ModelInfo = { id: string, name: string, provider: string | null, };
+export type ModelInfo = { id: string, name: string, provider: string | null, 
+/**
+ * Whether the model accepts image attachments.
+ */
+supports_vision: boolean, 
+/**
+ * Tokens the model can take in at once, when known.
+ */
+context_window_tokens: number | null, };
@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PermissionDecision } from "./PermissionDecision";
+
+/**
+ * A remembered answer to permission requests.
+ */
+export type PermissionRule = { id: string, 
+/**
+ * Request kind (`shell`, `write`, `read`, `url`) or the MCP tool name.
+ */
+tool: string, 
+/**
+ * Glob matched against the file path, or word by word against the
+ * command line for shell requests (see `command_match`). `None` matches
+ * every request for the tool.
+ */
+path_pattern: string | null, 
+/**
+ * `None` applies the rule in every conversation.
+ */
+conversation_id: string | null, decision: PermissionDecision, created_at: string, };
@@ -0,0 +1,32 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A reusable bundle of system prompt, model and tools a conversation can be
+ * started with.
+ */
+export type Persona = { id: string, 
+/**
+ * Unique name shown when starting a conversation.
+ */
+name: string, 
+/**
+ * Replaces the system prompt of sessions in the persona's conversations.
+ */
+system_prompt: string | null, 
+/**
+ * Model new conversations start with; the selected one when `None`.
+ */
+model: string | null, 
+/**
+ * One of `REASONING_EFFORTS`; the model's default when `None`.
+ */
+reasoning_effort: string | null, 
+/**
+ * Built-in tools turned on in new conversations; all of them when `None`.
+ */
+tools: Array<string> | null, 
+/**
+ * Ids of the MCP servers given to sessions, enabled or not; the enabled
+ * servers when `None`.
+ */
+mcp_servers: Array<string> | null, created_at: string, updated_at: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Fields of a persona the user can edit.
+ */
+export type PersonaInput = { name: string, system_prompt: string | null, model: string | null, reasoning_effort: string | null, tools: Array<string> | null, mcp_servers: Array<string> | null, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TemplateVariable } from "./TemplateVariable";
+
+/**
+ * A reusable prompt the user saved.
+ */
+export type PromptTemplate = { id: string, 
+/**
+ * Unique name, typed as `/name` in the message box.
+ */
+name: string, description: string | null, body: string, 
+/**
+ * Placeholders of `body` in the order they first appear.
+ */
+variables: Array<TemplateVariable>, created_at: string, updated_at: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Fields of a prompt template the user can edit.
+ */
+export type PromptTemplateInput = { name: string, description: string | null, body: string, };
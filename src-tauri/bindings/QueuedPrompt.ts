@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A prompt waiting for its session to go idle, as reported to the UI.
+ */
+export type QueuedPrompt = { id: string, content: string, created_at: string, 
+/**
+ * 1-based position in the queue; 1 is sent next.
+ */
+position: number, };
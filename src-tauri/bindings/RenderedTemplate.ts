@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SendReceipt } from "./SendReceipt";
+
+/**
+ * Returned by `render_template`.
+ */
+export type RenderedTemplate = { content: string, 
+/**
+ * Set when the result was also sent to a conversation.
+ */
+receipt: SendReceipt | null, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Response time limits in seconds. `None` inherits the default (or, for a
+ * model override, the global value); `0` disables the limit.
+ */
+export type ResponseDeadlines = { 
+/**
+ * Longest gap between two streamed events.
+ */
+idle_timeout_secs: number | null, 
+/**
+ * Longest wait for the first thinking or reply text.
+ */
+first_token_timeout_secs: number | null, 
+/**
+ * Longest a whole reply may take.
+ */
+total_timeout_secs: number | null, };
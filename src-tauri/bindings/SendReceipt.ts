@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Message } from "./Message";
+
+/**
+ * Returned by `send_message`.
+ */
+export type SendReceipt = { 
+/**
+ * The prompt; stored already unless it was queued.
+ */
+message: Message, 
+/**
+ * 1-based queue position when the prompt waits for the current reply,
+ * `None` when it was sent right away.
+ */
+queue_position: number | null, };
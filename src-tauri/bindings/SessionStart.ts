@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Returned by `create_session` so the UI knows what the model can see.
+ */
+export type SessionStart = { session_id: string, 
+/**
+ * True when the session already bound to the conversation was returned.
+ */
+reused: boolean, 
+/**
+ * True when the conversation's SDK session from an earlier run was
+ * resumed, so no stored history had to be replayed.
+ */
+resumed: boolean, 
+/**
+ * Ids of stored messages replayed into the new session, oldest first.
+ */
+carried_over: Array<string>, 
+/**
+ * Older stored messages left out to fit the context budget.
+ */
+omitted: number, };
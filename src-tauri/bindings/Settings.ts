@@ -1,3 +1,33 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeadlineSettings } from "./DeadlineSettings";
 
-export type Settings = { theme: string, default_model: string | null, system_prompt: string | null, };
+export type Settings = { theme: string, default_model: string | null, system_prompt: string | null, 
+/**
+ * Characters of stored history replayed into a new session for an existing
+ * conversation. `0` disables rehydration.
+ */
+context_budget_chars: number | null, 
+/**
+ * Seconds a session may sit idle before it is destroyed. `0` keeps idle
+ * sessions until the session limit is reached.
+ */
+session_idle_ttl_secs: number | null, 
+/**
+ * Most sessions kept alive at once; the least recently used idle session
+ * is destroyed to make room.
+ */
+max_sessions: number | null, 
+/**
+ * Times a send that failed for a temporary reason is retried. `0`
+ * reports every failure right away.
+ */
+send_retry_limit: number | null, 
+/**
+ * Idle, first-token and total response limits, with per-model overrides.
+ */
+response_deadlines: DeadlineSettings | null, 
+/**
+ * Hours the sandbox of a deleted conversation is archived before it is
+ * deleted too. `0` deletes it with the conversation.
+ */
+sandbox_retention_hours: number | null, };
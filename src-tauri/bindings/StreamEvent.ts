@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Message } from "./Message";
+import type { ToolCall } from "./ToolCall";
+
+/**
+ * One event of a streaming turn, delivered on the channel passed to the
+ * command that started the turn. `seq` numbers a turn's events from 0.
+ */
+export type StreamEvent = { "type": "dispatched", seq: number, message: Message, } | { "type": "thinking_delta", seq: number, delta: string, } | { "type": "thinking_complete", seq: number, content: string, } | { "type": "message_delta", seq: number, delta: string, } | { "type": "message_complete", seq: number, content: string, } | { "type": "usage", seq: number, input_tokens: number | null, output_tokens: number | null, } | { "type": "tool_started", seq: number, tool_call: ToolCall, } | { "type": "tool_progress", seq: number, tool_call_id: string, message: string, } | { "type": "tool_completed", seq: number, tool_call: ToolCall, } | { "type": "message_saved", seq: number, message_id: string, } | { "type": "aborted", seq: number, content: string, message_id: string | null, } | { "type": "error", seq: number, message: string, } | { "type": "idle", seq: number, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A `{{name}}` or `{{name|default}}` placeholder in a template.
+ */
+export type TemplateVariable = { name: string, 
+/**
+ * Used when no value is given; `None` makes the variable required.
+ */
+default: string | null, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * One logged call of a built-in tool.
+ */
+export type ToolAuditEntry = { id: string, conversation_id: string, session_id: string, tool_name: string, 
+/**
+ * Arguments as JSON text, cut off after 4 KiB.
+ */
+arguments: string, success: boolean, 
+/**
+ * Error message, or the size of the result for successful calls.
+ */
+outcome: string, duration_ms: number, created_at: string, };
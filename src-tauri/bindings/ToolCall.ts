@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * One tool the agent ran while producing a reply.
+ */
+export type ToolCall = { 
+/**
+ * The SDK's tool call id; unique within a reply.
+ */
+id: string, tool_name: string, 
+/**
+ * Arguments as JSON text.
+ */
+arguments: string | null, 
+/**
+ * Latest progress message while the tool runs.
+ */
+progress: string | null, result: string | null, error: string | null, 
+/**
+ * `None` while running, or when the reply ended before the tool did.
+ */
+success: boolean | null, started_at: string, duration_ms: number | null, };
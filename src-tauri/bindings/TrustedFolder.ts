@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A folder the user allows conversations to work in, along with its
+ * subfolders.
+ */
+export type TrustedFolder = { 
+/**
+ * Absolute path with symlinks resolved.
+ */
+path: string, created_at: string, };
@@ -0,0 +1,529 @@
+use crate::documents;
+use base64::Engine;
+use image::{DynamicImage, ImageFormat};
+use sha2::{Digest, Sha256};
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+
+/// Most files that can be attached to one message.
+pub const MAX_ATTACHMENTS: usize = 10;
+/// Largest text file that can be attached, in bytes.
+pub const MAX_ATTACHMENT_SIZE: u64 = 2 * 1024 * 1024;
+/// Largest image that can be attached, in bytes, before it is downscaled.
+pub const MAX_IMAGE_SIZE: u64 = 20 * 1024 * 1024;
+/// Images are downscaled to fit this many pixels on their longer side.
+const MAX_IMAGE_DIMENSION: u32 = 2048;
+/// Images larger than this on either side are refused without decoding.
+const MAX_DECODED_DIMENSION: u32 = 16_384;
+const THUMBNAIL_DIMENSION: u32 = 256;
+const JPEG_QUALITY: u8 = 85;
+const THUMBNAIL_QUALITY: u8 = 75;
+
+const MAX_NAME_LENGTH: usize = 120;
+/// Folder inside a conversation's sandbox the SDK reads attachments from.
+pub(crate) const ATTACHMENTS_DIR: &str = ".attachments";
+
+/// A file sent along with a user message.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct Attachment {
+    pub id: String,
+    /// File name shown in the chat and given to the model.
+    pub name: String,
+    pub mime_type: String,
+    /// Size in bytes.
+    pub size: u32,
+    /// Hex SHA-256 of the contents; identical files are stored once.
+    pub sha256: String,
+    /// Small JPEG preview of an image as a `data:` URL; `None` for text.
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+
+    /// Whether the model gets this attachment as extracted text.
+    pub fn is_document(&self) -> bool {
+        documents::is_document(&self.mime_type)
+    }
+}
+
+/// A file to attach: one the user picked on disk, text pasted as a file, or
+/// a pasted image or document as base64.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum AttachmentInput {
+    Path { path: String },
+    Pasted { name: String, content: String },
+    Image { name: String, data: String },
+    Document { name: String, data: String },
+}
+
+/// An attachment checked against the limits, with its contents.
+#[derive(Debug)]
+pub struct Prepared {
+    pub attachment: Attachment,
+    pub data: Vec<u8>,
+    /// JPEG preview of an image.
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// Reads and checks every input. Fails on the first one that is missing,
+/// too large, or not a text file, a PNG, JPEG or WebP image, or a PDF, DOCX,
+/// HTML or notebook document.
+pub fn prepare_all(inputs: &[AttachmentInput]) -> Result<Vec<Prepared>, String> {
+    if inputs.len() > MAX_ATTACHMENTS {
+        return Err(format!(
+            "At most {} files can be attached to a message.",
+            MAX_ATTACHMENTS
+        ));
+    }
+    inputs.iter().map(prepare).collect()
+}
+
+fn prepare(input: &AttachmentInput) -> Result<Prepared, String> {
+    let (name, data) = match input {
+        AttachmentInput::Image { name, data } => {
+            let name = sanitize_name(name);
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map_err(|_| format!("{} is not a valid image.", name))?;
+            if image_format(&data).is_none() {
+                return Err(unsupported_image(&name));
+            }
+            (name, data)
+        }
+        AttachmentInput::Document { name, data } => {
+            let name = sanitize_name(name);
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map_err(|_| format!("{} is not a valid document.", name))?;
+            if documents::detect(&name, &data).is_none() {
+                return Err(format!(
+                    "{} is not a PDF, DOCX, HTML or notebook document.",
+                    name
+                ));
+            }
+            (name, data)
+        }
+        AttachmentInput::Path { path } => {
+            let path = Path::new(path.trim());
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let meta = std::fs::metadata(path)
+                .map_err(|_| format!("File not found: {}", path.display()))?;
+            if !meta.is_file() {
+                return Err(format!("{} is not a file.", path.display()));
+            }
+            let limit = MAX_IMAGE_SIZE.max(documents::MAX_DOCUMENT_SIZE);
+            if meta.len() > limit {
+                return Err(too_large(&name, limit));
+            }
+            let data = std::fs::read(path).map_err(|e| {
+                tracing::warn!("Failed to read attachment {}: {}", path.display(), e);
+                format!("Could not read {}.", path.display())
+            })?;
+            (name, data)
+        }
+        AttachmentInput::Pasted { name, content } => (name.clone(), content.as_bytes().to_vec()),
+    };
+    let name = sanitize_name(&name);
+    if let Some(format) = image_format(&data) {
+        return prepare_image(name, &data, format);
+    }
+    if let Some(mime_type) = documents::detect(&name, &data) {
+        if data.len() as u64 > documents::MAX_DOCUMENT_SIZE {
+            return Err(too_large(&name, documents::MAX_DOCUMENT_SIZE));
+        }
+        return Ok(Prepared {
+            attachment: Attachment {
+                id: uuid::Uuid::new_v4().to_string(),
+                mime_type: mime_type.to_string(),
+                size: data.len() as u32,
+                sha256: sha256_hex(&data),
+                name,
+                thumbnail: None,
+            },
+            data,
+            thumbnail: None,
+        });
+    }
+    if data.len() as u64 > MAX_ATTACHMENT_SIZE {
+        return Err(too_large(&name, MAX_ATTACHMENT_SIZE));
+    }
+    if data.contains(&0) || std::str::from_utf8(&data).is_err() {
+        return Err(format!(
+            "{} is not a text file, an image or a supported document.",
+            name
+        ));
+    }
+    let attachment = Attachment {
+        id: uuid::Uuid::new_v4().to_string(),
+        mime_type: mime_type(&name).to_string(),
+        size: data.len() as u32,
+        sha256: sha256_hex(&data),
+        name,
+        thumbnail: None,
+    };
+    Ok(Prepared {
+        attachment,
+        data,
+        thumbnail: None,
+    })
+}
+
+fn too_large(name: &str, limit: u64) -> String {
+    format!(
+        "{} is larger than the {} MB attachment limit.",
+        name,
+        limit / (1024 * 1024)
+    )
+}
+
+fn unsupported_image(name: &str) -> String {
+    format!("{} is not a PNG, JPEG or WebP image.", name)
+}
+
+/// The format of `data` when it is an image that can be attached.
+fn image_format(data: &[u8]) -> Option<ImageFormat> {
+    match image::guess_format(data) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)) => Some(format),
+        _ => None,
+    }
+}
+
+/// Decodes an image, downscales it to `MAX_IMAGE_DIMENSION` and re-encodes
+/// it, which also drops metadata such as the location a photo was taken at.
+/// JPEGs stay JPEGs; PNGs and WebPs become PNGs so screenshots stay sharp.
+fn prepare_image(name: String, data: &[u8], format: ImageFormat) -> Result<Prepared, String> {
+    if data.len() as u64 > MAX_IMAGE_SIZE {
+        return Err(too_large(&name, MAX_IMAGE_SIZE));
+    }
+    let mut reader = image::ImageReader::with_format(Cursor::new(data), format);
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_DECODED_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODED_DIMENSION);
+    reader.limits(limits);
+    let decoded = reader.decode().map_err(|e| {
+        tracing::warn!("Failed to decode image {}: {}", name, e);
+        format!("{} could not be read as an image.", name)
+    })?;
+    let image = if decoded.width() > MAX_IMAGE_DIMENSION || decoded.height() > MAX_IMAGE_DIMENSION {
+        decoded.resize(
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION,
+            image::imageops::FilterType::Lanczos3,
+        )
+    } else {
+        decoded
+    };
+
+    let (output, mime_type, extension) = if format == ImageFormat::Jpeg {
+        (ImageFormat::Jpeg, "image/jpeg", "jpg")
+    } else {
+        (ImageFormat::Png, "image/png", "png")
+    };
+    let encode_error = |e: image::ImageError| {
+        tracing::error!("Failed to encode image {}: {}", name, e);
+        format!("{} could not be processed.", name)
+    };
+    let data = encode(&image, output, JPEG_QUALITY).map_err(encode_error)?;
+    let thumbnail = encode(
+        &image.thumbnail(THUMBNAIL_DIMENSION, THUMBNAIL_DIMENSION),
+        ImageFormat::Jpeg,
+        THUMBNAIL_QUALITY,
+    )
+    .map_err(encode_error)?;
+    let attachment = Attachment {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: with_extension(&name, extension),
+        mime_type: mime_type.to_string(),
+        size: data.len() as u32,
+        sha256: sha256_hex(&data),
+        thumbnail: Some(thumbnail_url(&thumbnail)),
+    };
+    Ok(Prepared {
+        attachment,
+        data,
+        thumbnail: Some(thumbnail),
+    })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> image::ImageResult<Vec<u8>> {
+    let mut out = Cursor::new(Vec::new());
+    if format == ImageFormat::Jpeg {
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality);
+        DynamicImage::ImageRgb8(flatten(image)).write_with_encoder(encoder)?;
+    } else {
+        image.write_to(&mut out, format)?;
+    }
+    Ok(out.into_inner())
+}
+
+/// Drops the alpha channel, blending transparent areas onto white.
+fn flatten(image: &DynamicImage) -> image::RgbImage {
+    if !image.color().has_alpha() {
+        return image.to_rgb8();
+    }
+    let rgba = image.to_rgba8();
+    image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let p = rgba.get_pixel(x, y);
+        let alpha = p[3] as u16;
+        image::Rgb([0, 1, 2].map(|i| ((p[i] as u16 * alpha + 255 * (255 - alpha)) / 255) as u8))
+    })
+}
+
+/// `data:` URL of a JPEG thumbnail, for the transcript.
+pub fn thumbnail_url(jpeg: &[u8]) -> String {
+    format!(
+        "data:image/jpeg;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(jpeg)
+    )
+}
+
+/// `name` with its extension replaced by `extension` unless it already has
+/// an equivalent one.
+fn with_extension(name: &str, extension: &str) -> String {
+    let (stem, current) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, ext.to_ascii_lowercase()),
+        _ => (name, String::new()),
+    };
+    let same = current == extension || (extension == "jpg" && current == "jpeg");
+    if same {
+        name.to_string()
+    } else {
+        format!("{}.{}", stem, extension)
+    }
+}
+
+/// Keeps the last path component of `name` and replaces characters that are
+/// not safe in a file name.
+fn sanitize_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    let cleaned: String = base
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    if cleaned.trim_matches('.').is_empty() {
+        "attachment.txt".to_string()
+    } else {
+        cleaned
+    }
+}
+
+fn mime_type(name: &str) -> &'static str {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "js" | "mjs" | "cjs" => "text/javascript",
+        "css" => "text/css",
+        _ => "text/plain",
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Writes `attachment` under `sandbox` unless an identical copy is already
+/// there, and returns the path the SDK reads it from.
+pub fn materialize(sandbox: &Path, attachment: &Attachment, data: &[u8]) -> io::Result<PathBuf> {
+    let dir = sandbox
+        .join(ATTACHMENTS_DIR)
+        .join(&attachment.sha256[..attachment.sha256.len().min(16)]);
+    let path = dir.join(sanitize_name(&attachment.name));
+    if !path.exists() {
+        crate::sandbox::create_private_dir(&dir)?;
+        std::fs::write(&path, data)?;
+    }
+    Ok(path)
+}
+
+/// Deletes files `materialize` wrote once the SDK is done with them, so
+/// their contents only stay in the encrypted database.
+pub fn discard(paths: &[PathBuf]) {
+    for path in paths {
+        if let Err(e) = std::fs::remove_file(path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                tracing::warn!("Failed to delete attachment {}: {}", path.display(), e);
+            }
+        }
+        // Only succeeds once the folder is empty
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_hashes_and_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("build.log");
+        std::fs::write(&file, "error: oops\n").unwrap();
+
+        let prepared = prepare_all(&[
+            AttachmentInput::Path {
+                path: file.to_string_lossy().into_owned(),
+            },
+            AttachmentInput::Pasted {
+                name: "../notes.md".to_string(),
+                content: "error: oops\n".to_string(),
+            },
+        ])
+        .unwrap();
+        assert_eq!(prepared[0].attachment.name, "build.log");
+        assert_eq!(prepared[0].attachment.mime_type, "text/plain");
+        assert_eq!(prepared[0].attachment.size, 12);
+        assert_eq!(prepared[1].attachment.name, "notes.md");
+        assert_eq!(prepared[1].attachment.mime_type, "text/markdown");
+        // Same contents, same hash
+        assert_eq!(prepared[0].attachment.sha256, prepared[1].attachment.sha256);
+        assert_eq!(prepared[0].attachment.sha256.len(), 64);
+
+        let binary = dir.path().join("a.bin");
+        std::fs::write(&binary, [0u8, 1, 2]).unwrap();
+        let path = |p: &Path| AttachmentInput::Path {
+            path: p.to_string_lossy().into_owned(),
+        };
+        assert!(prepare_all(&[path(&binary)]).is_err());
+        assert!(prepare_all(&[path(dir.path())]).is_err());
+        assert!(prepare_all(&[path(&dir.path().join("missing.txt"))]).is_err());
+        let big = AttachmentInput::Pasted {
+            name: "big.txt".to_string(),
+            content: "x".repeat(MAX_ATTACHMENT_SIZE as usize + 1),
+        };
+        assert!(prepare_all(&[big]).is_err());
+        assert!(prepare_all(&vec![path(&file); MAX_ATTACHMENTS + 1]).is_err());
+    }
+
+    fn encoded(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image.write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_images_are_downscaled_and_reencoded() {
+        let screenshot = encoded(
+            DynamicImage::ImageRgba8(image::RgbaImage::new(3000, 1000)),
+            ImageFormat::Png,
+        );
+        let prepared = prepare_all(&[AttachmentInput::Image {
+            name: "screenshot".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(&screenshot),
+        }])
+        .unwrap()
+        .remove(0);
+        assert!(prepared.attachment.is_image());
+        assert_eq!(prepared.attachment.name, "screenshot.png");
+        assert_eq!(prepared.attachment.mime_type, "image/png");
+        let stored = image::load_from_memory(&prepared.data).unwrap();
+        assert_eq!((stored.width(), stored.height()), (2048, 683));
+        let thumbnail = image::load_from_memory(prepared.thumbnail.as_ref().unwrap()).unwrap();
+        assert_eq!(thumbnail.width(), THUMBNAIL_DIMENSION);
+        assert!(prepared
+            .attachment
+            .thumbnail
+            .unwrap()
+            .starts_with("data:image/jpeg;base64,"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("photo.jpeg");
+        let jpeg = encoded(
+            DynamicImage::ImageRgb8(image::RgbImage::new(40, 30)),
+            ImageFormat::Jpeg,
+        );
+        std::fs::write(&photo, jpeg).unwrap();
+        let prepared = prepare_all(&[AttachmentInput::Path {
+            path: photo.to_string_lossy().into_owned(),
+        }])
+        .unwrap()
+        .remove(0);
+        assert_eq!(prepared.attachment.name, "photo.jpeg");
+        assert_eq!(prepared.attachment.mime_type, "image/jpeg");
+
+        let gif = b"GIF89a\x04\x00\x04\x00\x00\x00\x00;";
+        let image = |data: &[u8]| AttachmentInput::Image {
+            name: "x.gif".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+        };
+        assert!(prepare_all(&[image(gif)]).is_err());
+        assert!(prepare_all(&[image(b"not an image")]).is_err());
+        // A truncated PNG is refused rather than stored
+        assert!(prepare_all(&[image(&screenshot[..64])]).is_err());
+    }
+
+    #[test]
+    fn test_documents_are_detected() {
+        let pdf = AttachmentInput::Document {
+            name: "report.pdf".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(b"%PDF-1.7\n\x00\xff"),
+        };
+        let page = AttachmentInput::Pasted {
+            name: "page.html".to_string(),
+            content: "<p>Hi</p>".to_string(),
+        };
+        let prepared = prepare_all(&[pdf, page]).unwrap();
+        assert!(prepared[0].attachment.is_document());
+        assert_eq!(prepared[0].attachment.mime_type, documents::PDF_MIME);
+        assert_eq!(prepared[0].attachment.size, 11);
+        assert!(prepared[1].attachment.is_document());
+        assert!(!prepared[1].attachment.is_image());
+
+        let fake = AttachmentInput::Document {
+            name: "report.docx".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(b"plain text"),
+        };
+        assert!(prepare_all(&[fake]).is_err());
+    }
+
+    #[test]
+    fn test_materialize_writes_once() {
+        let sandbox = tempfile::tempdir().unwrap();
+        let prepared = prepare_all(&[AttachmentInput::Pasted {
+            name: "a:b.txt".to_string(),
+            content: "hello".to_string(),
+        }])
+        .unwrap()
+        .remove(0);
+        let path = materialize(sandbox.path(), &prepared.attachment, &prepared.data).unwrap();
+        assert!(path.starts_with(sandbox.path()));
+        assert!(path.ends_with("a_b.txt"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+        let again = materialize(sandbox.path(), &prepared.attachment, &prepared.data).unwrap();
+        assert_eq!(path, again);
+
+        discard(&[path.clone(), again]);
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+    }
+}
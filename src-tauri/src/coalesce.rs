@@ -0,0 +1,135 @@
+use std::time::{Duration, Instant};
+
+const DEFAULT_FLUSH_INTERVAL_MS: u64 = 50;
+const DEFAULT_FLUSH_BYTES: usize = 2048;
+
+/// How long streamed deltas may wait, and how much may pile up, before they
+/// are sent to the UI as one event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoalesceConfig {
+    pub flush_interval: Duration,
+    pub flush_bytes: usize,
+}
+
+impl Default for CoalesceConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval: Duration::from_millis(DEFAULT_FLUSH_INTERVAL_MS),
+            flush_bytes: DEFAULT_FLUSH_BYTES,
+        }
+    }
+}
+
+impl CoalesceConfig {
+    /// Reads COPILOT_DELTA_FLUSH_MS and COPILOT_DELTA_FLUSH_BYTES, falling back
+    /// to the defaults. A flush interval of 0 sends every delta on its own.
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+        let default = Self::default();
+        Self {
+            flush_interval: var("COPILOT_DELTA_FLUSH_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.flush_interval),
+            flush_bytes: var("COPILOT_DELTA_FLUSH_BYTES").unwrap_or(default.flush_bytes),
+        }
+    }
+}
+
+/// Collects deltas of one kind until the flush interval has passed since the
+/// oldest pending delta or the pending text reaches the byte threshold.
+pub struct DeltaBuffer {
+    config: CoalesceConfig,
+    pending: String,
+    pending_since: Option<Instant>,
+    received: usize,
+    flushed: usize,
+}
+
+impl DeltaBuffer {
+    pub fn new(config: CoalesceConfig) -> Self {
+        Self {
+            config,
+            pending: String::new(),
+            pending_since: None,
+            received: 0,
+            flushed: 0,
+        }
+    }
+
+    /// Adds `delta` and returns whether the buffer should be flushed now.
+    pub fn push(&mut self, delta: &str, now: Instant) -> bool {
+        self.received += 1;
+        self.pending.push_str(delta);
+        let since = *self.pending_since.get_or_insert(now);
+        self.pending.len() >= self.config.flush_bytes
+            || now.duration_since(since) >= self.config.flush_interval
+    }
+
+    /// Takes the pending text, if any.
+    pub fn take(&mut self) -> Option<String> {
+        self.pending_since = None;
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.flushed += 1;
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    /// When the pending text is due, or `None` when nothing is pending.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending_since
+            .map(|since| since + self.config.flush_interval)
+    }
+
+    /// Deltas received and events sent for them so far.
+    pub fn counts(&self) -> (usize, usize) {
+        (self.received, self.flushed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(ms: u64, bytes: usize) -> CoalesceConfig {
+        CoalesceConfig {
+            flush_interval: Duration::from_millis(ms),
+            flush_bytes: bytes,
+        }
+    }
+
+    #[test]
+    fn test_buffer_batches_until_interval() {
+        let start = Instant::now();
+        let mut buffer = DeltaBuffer::new(config(50, 1024));
+        assert!(!buffer.push("Hel", start));
+        assert!(!buffer.push("lo ", start + Duration::from_millis(20)));
+        assert_eq!(buffer.deadline(), Some(start + Duration::from_millis(50)));
+        assert!(buffer.push("world", start + Duration::from_millis(50)));
+        assert_eq!(buffer.take().as_deref(), Some("Hello world"));
+        assert_eq!(buffer.deadline(), None);
+        assert_eq!(buffer.take(), None);
+        assert_eq!(buffer.counts(), (3, 1));
+    }
+
+    #[test]
+    fn test_buffer_flushes_at_byte_threshold() {
+        let start = Instant::now();
+        let mut buffer = DeltaBuffer::new(config(1000, 8));
+        assert!(!buffer.push("1234", start));
+        assert!(buffer.push("5678", start));
+        assert_eq!(buffer.take().as_deref(), Some("12345678"));
+    }
+
+    #[test]
+    fn test_zero_interval_flushes_every_delta() {
+        let start = Instant::now();
+        let mut buffer = DeltaBuffer::new(config(0, 1024));
+        assert!(buffer.push("a", start));
+        assert_eq!(buffer.take().as_deref(), Some("a"));
+        assert!(buffer.push("b", start));
+        assert_eq!(buffer.counts(), (2, 1));
+    }
+}
@@ -1,14 +1,29 @@
+use crate::attachments::{Attachment, AttachmentInput};
+use crate::coalesce::{CoalesceConfig, DeltaBuffer};
 use crate::db;
+use crate::deadlines::{DeadlineSettings, Deadlines};
 use crate::error::AppError;
+use crate::mcp::{McpImport, McpServer, McpServerInput, McpToolInfo, McpTransport};
+use crate::permissions::{PermissionDecision, PermissionRule, RuleScope};
+use crate::personas::{Persona, PersonaInput};
+use crate::queue::QueuedPrompt;
+use crate::retry::ErrorClass;
 use crate::state::AppState;
+use crate::supervisor::ClientStatus;
+use crate::templates::{PromptTemplate, PromptTemplateInput};
+use crate::tool_log::{ToolCall, ToolLog};
+use crate::tools::{BuiltinToolInfo, ToolAuditEntry};
+use crate::workspace::TrustedFolder;
 use std::time::Duration;
-use tauri::{Emitter, State};
+use tauri::ipc::Channel;
+use tauri::{Emitter, Manager, State};
 use tokio_util::sync::CancellationToken;
 use ts_rs::TS;
 
 const MAX_MESSAGE_SIZE: usize = 1_000_000; // 1MB
 const MAX_TITLE_LENGTH: usize = 200;
 const MAX_SETTING_VALUE_SIZE: usize = 100_000; // 100KB
+const MAX_SEND_RETRY_LIMIT: u32 = 10;
 
 /// Acquires the database lock, checks that the connection is initialized,
 /// and passes a reference to the closure `f`.
@@ -22,18 +37,11 @@ where
         .ok_or_else(|| "Database not initialized.".to_string())?;
     f(conn)
 }
-const DEFAULT_EVENT_TIMEOUT_SECS: u64 = 120;
 
-/// Per-event timeout for the streaming event loop.
-/// If no event arrives within this window the loop emits whatever partial
-/// content has been collected and reports a timeout error to the frontend.
-/// Override with the COPILOT_EVENT_TIMEOUT_SECS environment variable.
-fn event_timeout() -> Duration {
-    let secs: u64 = std::env::var("COPILOT_EVENT_TIMEOUT_SECS")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(DEFAULT_EVENT_TIMEOUT_SECS);
-    Duration::from_secs(secs)
+/// Current time in the same ISO-8601 shape the webview produces with
+/// `Date.toISOString()`, so stored timestamps sort consistently.
+fn now_timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
@@ -49,6 +57,12 @@ pub struct ModelInfo {
     pub id: String,
     pub name: String,
     pub provider: Option<String>,
+    /// Whether the model accepts image attachments.
+    #[serde(default)]
+    pub supports_vision: bool,
+    /// Tokens the model can take in at once, when known.
+    #[serde(default)]
+    pub context_window_tokens: Option<u32>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
@@ -59,6 +73,18 @@ pub struct Conversation {
     pub model: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// Conversation this one was forked from, if any.
+    #[serde(default)]
+    pub forked_from_conversation_id: Option<String>,
+    /// Message of the source conversation the fork was taken at.
+    #[serde(default)]
+    pub forked_from_message_id: Option<String>,
+    /// Folder the conversation's sessions work in; the sandbox when `None`.
+    #[serde(default)]
+    pub workspace_folder: Option<String>,
+    /// Persona the conversation was started with, if any.
+    #[serde(default)]
+    pub persona_id: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
@@ -69,6 +95,24 @@ pub struct Message {
     pub role: String,
     pub content: String,
     pub created_at: String,
+    /// True when the reply was cut short by `abort_message`.
+    #[serde(default)]
+    pub interrupted: bool,
+    /// Message this one answers or follows; `None` for the first message.
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Position among messages sharing the same parent (regenerations and edits).
+    #[serde(default)]
+    pub sibling_index: u32,
+    /// Number of messages sharing the same parent, including this one.
+    #[serde(default)]
+    pub sibling_count: u32,
+    /// Tools the agent ran while producing this reply, in the order they started.
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Files sent along with this prompt.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
@@ -77,6 +121,72 @@ pub struct Settings {
     pub theme: String,
     pub default_model: Option<String>,
     pub system_prompt: Option<String>,
+    /// Characters of stored history replayed into a new session for an existing
+    /// conversation. `0` disables rehydration.
+    pub context_budget_chars: Option<u32>,
+    /// Seconds a session may sit idle before it is destroyed. `0` keeps idle
+    /// sessions until the session limit is reached.
+    pub session_idle_ttl_secs: Option<u32>,
+    /// Most sessions kept alive at once; the least recently used idle session
+    /// is destroyed to make room.
+    pub max_sessions: Option<u32>,
+    /// Times a send that failed for a temporary reason is retried. `0`
+    /// reports every failure right away.
+    pub send_retry_limit: Option<u32>,
+    /// Idle, first-token and total response limits, with per-model overrides.
+    pub response_deadlines: Option<DeadlineSettings>,
+    /// Hours the sandbox of a deleted conversation is archived before it is
+    /// deleted too. `0` deletes it with the conversation.
+    pub sandbox_retention_hours: Option<u32>,
+}
+
+/// Returned by `create_session` so the UI knows what the model can see.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct SessionStart {
+    pub session_id: String,
+    /// True when the session already bound to the conversation was returned.
+    pub reused: bool,
+    /// True when the conversation's SDK session from an earlier run was
+    /// resumed, so no stored history had to be replayed.
+    #[serde(default)]
+    pub resumed: bool,
+    /// Ids of stored messages replayed into the new session, oldest first.
+    pub carried_over: Vec<String>,
+    /// Older stored messages left out to fit the context budget.
+    pub omitted: u32,
+}
+
+/// Returned by `send_message`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct SendReceipt {
+    /// The prompt; stored already unless it was queued.
+    pub message: Message,
+    /// 1-based queue position when the prompt waits for the current reply,
+    /// `None` when it was sent right away.
+    pub queue_position: Option<u32>,
+}
+
+/// Returned by `render_template`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct RenderedTemplate {
+    pub content: String,
+    /// Set when the result was also sent to a conversation.
+    pub receipt: Option<SendReceipt>,
+}
+
+/// Returned by `fork_conversation`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct ForkedConversation {
+    pub conversation: Conversation,
+    /// Copies of the messages up to the fork point, oldest first.
+    pub messages: Vec<Message>,
+    /// Session seeded with `messages`; `None` when the client is not running,
+    /// in which case the first prompt opens one as usual.
+    pub session: Option<SessionStart>,
 }
 
 /// Find the copilot CLI executable, searching common macOS/Linux/Windows paths
@@ -158,7 +268,176 @@ fn find_copilot_cli_path() -> Option<std::path::PathBuf> {
 }
 
 #[tauri::command]
-pub async fn start_client(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn start_client(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    crate::supervisor::emit_status(&app, ClientStatus::Starting, None, None);
+    let client = match launch_client().await {
+        Ok(client) => client,
+        Err(e) => {
+            crate::supervisor::emit_status(&app, ClientStatus::Failed, None, Some(e.clone()));
+            return Err(e);
+        }
+    };
+
+    *state.client.write().await = Some(client);
+    tracing::info!("Copilot client started successfully");
+    crate::supervisor::emit_status(&app, ClientStatus::Ready, None, None);
+    crate::supervisor::watch(&app).await;
+    Ok(())
+}
+
+/// Folder the sessions of `conversation_id` work in: its workspace folder
+/// while that is still trusted, otherwise its own sandbox, created here.
+pub(crate) async fn working_directory(
+    state: &AppState,
+    conversation_id: &str,
+) -> std::path::PathBuf {
+    let loaded = with_db(state, |conn| {
+        let chosen = db::get_conversation(conn, conversation_id)
+            .map_err(|e| e.to_string())?
+            .and_then(|c| c.workspace_folder);
+        let trusted = db::list_trusted_folders(conn).map_err(|e| e.to_string())?;
+        Ok((chosen, trusted))
+    })
+    .await;
+    let workspace = match loaded {
+        Ok((chosen, trusted)) => crate::workspace::session_directory(chosen.as_deref(), &trusted),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to load the workspace of conversation {}: {}",
+                conversation_id,
+                e
+            );
+            None
+        }
+    };
+    if let Some(folder) = workspace {
+        return folder;
+    }
+    let root = crate::sandbox::root();
+    crate::sandbox::create(&root, conversation_id).unwrap_or_else(|e| {
+        tracing::error!(
+            "Failed to create the sandbox of conversation {}, using the shared one: {}",
+            conversation_id,
+            e
+        );
+        root
+    })
+}
+
+/// How long the sandbox of an ended session is archived.
+pub(crate) fn sandbox_retention(conn: &rusqlite::Connection) -> Duration {
+    let hours = db::get_setting(conn, "sandbox_retention_hours")
+        .map_err(|e| tracing::error!("Failed to get setting 'sandbox_retention_hours': {}", e))
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(crate::sandbox::DEFAULT_SANDBOX_RETENTION_HOURS);
+    Duration::from_secs(hours * 3600)
+}
+
+async fn current_sandbox_retention(state: &AppState) -> Duration {
+    with_db(state, |conn| Ok(sandbox_retention(conn)))
+        .await
+        .unwrap_or(Duration::from_secs(
+            crate::sandbox::DEFAULT_SANDBOX_RETENTION_HOURS * 3600,
+        ))
+}
+
+/// Archives or deletes the sandbox of a deleted conversation. Sandboxes of
+/// live conversations are kept while their sessions come and go.
+async fn release_sandbox(state: &AppState, conversation_id: &str) {
+    let retention = current_sandbox_retention(state).await;
+    let released = crate::sandbox::release(
+        &crate::sandbox::root(),
+        conversation_id,
+        retention,
+        std::time::SystemTime::now(),
+    );
+    if let Err(e) = released {
+        tracing::warn!(
+            "Failed to release the sandbox of conversation {}: {}",
+            conversation_id,
+            e
+        );
+    }
+}
+
+/// Deletes archived sandboxes older than the retention setting.
+pub(crate) async fn expire_sandboxes(state: &AppState) {
+    let retention = current_sandbox_retention(state).await;
+    let expired = tauri::async_runtime::spawn_blocking(move || {
+        crate::sandbox::expire(
+            &crate::sandbox::root(),
+            retention,
+            std::time::SystemTime::now(),
+        )
+    })
+    .await;
+    if let Ok(n) = expired.map_err(|e| tracing::warn!("Sandbox expiry failed: {}", e)) {
+        if n > 0 {
+            tracing::info!("Removed {} expired sandbox(es)", n);
+        }
+    }
+}
+
+/// Built-in tools enabled in `conversation_id`, for a new or resumed session.
+async fn builtin_tools(state: &AppState, conversation_id: &str) -> Vec<copilot_sdk::Tool> {
+    let disabled = with_db(state, |conn| {
+        db::get_disabled_tools(conn, conversation_id).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!(
+            "Failed to load tool settings for conversation {}: {}",
+            conversation_id,
+            e
+        );
+        Vec::new()
+    });
+    crate::tools::definitions(&disabled)
+}
+
+/// MCP servers for a new or resumed session: the ones `persona` picks, or
+/// the enabled ones.
+async fn mcp_servers(
+    state: &AppState,
+    persona: Option<&Persona>,
+) -> Option<std::collections::HashMap<String, copilot_sdk::McpServerConfig>> {
+    let servers = with_db(state, |conn| {
+        db::list_mcp_servers(conn).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!("Failed to load MCP servers: {}", e);
+        Vec::new()
+    });
+    let servers = match persona {
+        Some(persona) => persona.select_servers(servers),
+        None => servers,
+    };
+    let servers = crate::mcp::session_servers(&servers);
+    (!servers.is_empty()).then_some(servers)
+}
+
+/// The persona `conversation_id` was started with, if any.
+async fn conversation_persona(state: &AppState, conversation_id: &str) -> Option<Persona> {
+    with_db(state, |conn| {
+        db::get_conversation_persona(conn, conversation_id).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!(
+            "Failed to load persona of conversation {}: {}",
+            conversation_id,
+            e
+        );
+        None
+    })
+}
+
+/// Finds the Copilot CLI and starts a client on it. Used by `start_client`
+/// and by the supervisor when it restarts a crashed CLI.
+pub(crate) async fn launch_client() -> Result<copilot_sdk::Client, String> {
     let cli_path = find_copilot_cli_path()
         .ok_or_else(|| -> String { AppError::NotFound("Could not find Copilot CLI. Install via: brew install copilot-cli, or set COPILOT_CLI_PATH env var.".into()).into() })?;
 
@@ -169,8 +448,8 @@ pub async fn start_client(state: State<'_, AppState>) -> Result<(), String> {
 
     // Isolate the SDK process into a dedicated directory so it does NOT inherit
     // the repo working directory and cannot access the source tree.
-    let isolated_dir = std::env::temp_dir().join("copilot-desktop-sandbox");
-    std::fs::create_dir_all(&isolated_dir).map_err(|e| -> String {
+    let isolated_dir = crate::sandbox::root();
+    crate::sandbox::create_private_dir(&isolated_dir).map_err(|e| -> String {
         tracing::error!(
             "Failed to create sandbox dir ({}): {}",
             isolated_dir.display(),
@@ -207,16 +486,12 @@ pub async fn start_client(state: State<'_, AppState>) -> Result<(), String> {
         )
         .into()
     })?;
-
-    let mut client_guard = state.client.write().await;
-    *client_guard = Some(client);
-
-    tracing::info!("Copilot client started successfully");
-    Ok(())
+    Ok(client)
 }
 
 #[tauri::command]
 pub async fn stop_client(state: State<'_, AppState>) -> Result<(), String> {
+    crate::supervisor::unwatch(&state).await;
     let mut client_guard = state.client.write().await;
     if let Some(client) = client_guard.take() {
         client.stop().await;
@@ -243,6 +518,11 @@ pub async fn get_auth_status(state: State<'_, AppState>) -> Result<AuthStatus, S
 
 #[tauri::command]
 pub async fn list_models(state: State<'_, AppState>) -> Result<Vec<ModelInfo>, String> {
+    load_models(&state).await
+}
+
+/// The cached model list, fetched from the CLI when there is none yet.
+async fn load_models(state: &AppState) -> Result<Vec<ModelInfo>, String> {
     // Check cache first
     {
         let cache = state.cached_models.read().await;
@@ -265,6 +545,9 @@ pub async fn list_models(state: State<'_, AppState>) -> Result<Vec<ModelInfo>, S
             id: m.id.clone(),
             name: m.name.clone(),
             provider: None,
+            supports_vision: m.capabilities.supports.vision,
+            context_window_tokens: u32::try_from(m.capabilities.limits.max_context_window_tokens)
+                .ok(),
         })
         .collect();
 
@@ -295,24 +578,223 @@ pub async fn refresh_model_list(state: State<'_, AppState>) -> Result<(), String
 #[tauri::command]
 pub async fn create_session(
     state: State<'_, AppState>,
+    conversation_id: String,
     model: Option<String>,
     system_prompt: Option<String>,
-) -> Result<String, String> {
+) -> Result<SessionStart, String> {
+    let model = match model {
+        Some(model) => Some(model),
+        None => conversation_persona(&state, &conversation_id)
+            .await
+            .and_then(|p| p.model),
+    };
+    // Returning to a conversation reuses its session as long as the model is unchanged.
+    {
+        let mut sessions = state.sessions.write().await;
+        let bindings = state.conversation_sessions.read().await;
+        if let Some(info) = bindings
+            .get(&conversation_id)
+            .and_then(|session_id| sessions.get_mut(session_id))
+        {
+            if info.model == model {
+                info.last_used = std::time::Instant::now();
+                let session_id = info.session.session_id().to_string();
+                tracing::info!(
+                    "Reusing session {} for conversation {}",
+                    session_id,
+                    conversation_id
+                );
+                return Ok(SessionStart {
+                    session_id,
+                    reused: true,
+                    resumed: false,
+                    carried_over: Vec::new(),
+                    omitted: 0,
+                });
+            }
+            // Replacing the session would cancel the reply and drop the queue
+            if info.turn_token.is_some() || !info.queue.is_empty() {
+                return Err(AppError::Validation(
+                    "Wait for the current reply to finish before changing the model.".into(),
+                )
+                .into());
+            }
+        }
+    }
+
+    let (history, budget, stored_session) = with_db(&state, |conn| {
+        require_conversation(conn, &conversation_id)?;
+        let history = db::get_conversation_history(conn, &conversation_id).map_err(|e| {
+            tracing::error!("Failed to get conversation history: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
+        let stored_session = db::get_sdk_session(conn, &conversation_id).map_err(|e| {
+            tracing::error!("Failed to get stored session: {}", e);
+            "Failed to load conversation.".to_string()
+        })?;
+        Ok((history, context_budget(conn)?, stored_session))
+    })
+    .await?;
+
+    // After a restart or an eviction the CLI may still have the conversation's
+    // session; resuming it keeps the model's full context.
+    if let Some((sdk_session_id, stored_model)) = stored_session {
+        if stored_model == model {
+            if let Some(start) = resume_session(
+                &state,
+                &conversation_id,
+                &sdk_session_id,
+                model.clone(),
+                system_prompt.clone(),
+            )
+            .await
+            {
+                return Ok(start);
+            }
+        }
+    }
+
+    open_session(
+        &state,
+        &conversation_id,
+        model,
+        system_prompt,
+        &history,
+        budget,
+    )
+    .await
+}
+
+fn require_conversation(conn: &rusqlite::Connection, id: &str) -> Result<Conversation, String> {
+    db::get_conversation(conn, id)
+        .map_err(|e| {
+            tracing::error!("Failed to get conversation: {}", e);
+            "Failed to load conversation.".to_string()
+        })?
+        .ok_or_else(|| -> String { AppError::NotFound("Conversation not found".into()).into() })
+}
+
+/// Characters of stored history replayed into a new session.
+fn context_budget(conn: &rusqlite::Connection) -> Result<usize, String> {
+    Ok(db::get_setting(conn, "context_budget_chars")
+        .map_err(|e| {
+            tracing::error!("Failed to get setting 'context_budget_chars': {}", e);
+            "Failed to load settings.".to_string()
+        })?
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(crate::history::DEFAULT_CONTEXT_BUDGET_CHARS))
+}
+
+/// Times a transient send failure is retried before the user sees it.
+fn send_retry_limit(conn: &rusqlite::Connection) -> Result<u32, String> {
+    Ok(db::get_setting(conn, "send_retry_limit")
+        .map_err(|e| {
+            tracing::error!("Failed to get setting 'send_retry_limit': {}", e);
+            "Failed to load settings.".to_string()
+        })?
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(crate::retry::DEFAULT_SEND_RETRY_LIMIT))
+}
+
+/// Response time limits, global and per model.
+fn response_deadlines(conn: &rusqlite::Connection) -> Result<DeadlineSettings, String> {
+    Ok(db::get_setting(conn, "response_deadlines")
+        .map_err(|e| {
+            tracing::error!("Failed to get setting 'response_deadlines': {}", e);
+            "Failed to load settings.".to_string()
+        })?
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_default())
+}
+
+/// Model and system prompt of the session bound to `conversation_id`, if any.
+async fn bound_session_config(
+    state: &AppState,
+    conversation_id: &str,
+) -> (Option<String>, Option<String>) {
+    let sessions = state.sessions.read().await;
+    let bindings = state.conversation_sessions.read().await;
+    bindings
+        .get(conversation_id)
+        .and_then(|session_id| sessions.get(session_id))
+        .map(|info| (info.model.clone(), info.system_prompt.clone()))
+        .unwrap_or_default()
+}
+
+/// Fails with `message` while the session bound to `conversation_id` is
+/// streaming a reply or has prompts queued behind one.
+async fn require_idle(
+    state: &AppState,
+    conversation_id: &str,
+    message: &str,
+) -> Result<(), String> {
+    let sessions = state.sessions.read().await;
+    let bindings = state.conversation_sessions.read().await;
+    let busy = bindings
+        .get(conversation_id)
+        .and_then(|session_id| sessions.get(session_id))
+        .is_some_and(|info| info.turn_token.is_some() || !info.queue.is_empty());
+    if busy {
+        return Err(AppError::Validation(message.into()).into());
+    }
+    Ok(())
+}
+
+/// Creates a new SDK session for `conversation_id`, seeded with `history`, and
+/// binds it in place of whatever session the conversation had before.
+async fn open_session(
+    state: &AppState,
+    conversation_id: &str,
+    model: Option<String>,
+    system_prompt: Option<String>,
+    history: &[Message],
+    budget: usize,
+) -> Result<SessionStart, String> {
+    release_conversation_session(state, conversation_id).await;
+
+    // A persona's model is the fallback, its system prompt replaces the given one
+    let persona = conversation_persona(state, conversation_id).await;
+    let model = model.or_else(|| persona.as_ref().and_then(|p| p.model.clone()));
+    let system_prompt = persona
+        .as_ref()
+        .and_then(|p| p.system_prompt.clone())
+        .or(system_prompt);
+
     let client_guard = state.client.read().await;
     let client = client_guard.as_ref().ok_or("Client not started")?;
 
     let mut config = copilot_sdk::SessionConfig::default();
-    // Pin the session working directory to the sandbox so the model does not
-    // see the application source tree.
-    let isolated_dir = std::env::temp_dir().join("copilot-desktop-sandbox");
-    config.working_directory = Some(isolated_dir.to_string_lossy().into_owned());
+    // Sessions work in the conversation's trusted workspace folder, or in the
+    // sandbox so the model does not see the application source tree.
+    let workdir = working_directory(state, conversation_id).await;
+    config.working_directory = Some(workdir.to_string_lossy().into_owned());
+    config.tools = builtin_tools(state, conversation_id).await;
+    config.mcp_servers = mcp_servers(state, persona.as_ref()).await;
+    config.reasoning_effort = persona.as_ref().and_then(|p| p.reasoning_effort.clone());
 
     if let Some(ref model_id) = model {
         config.model = Some(model_id.clone());
     }
-    if let Some(ref prompt) = system_prompt {
+    // Reopening a conversation with stored messages replays them into the new
+    // session so the model picks up where the conversation left off.
+    let seed = crate::history::build_seed(history, budget);
+    if let Some(ref seed) = seed {
+        tracing::info!(
+            "Seeding session for conversation {} with {} stored message(s) ({} omitted)",
+            conversation_id,
+            seed.carried_over.len(),
+            seed.omitted
+        );
+    }
+    let system_content = match (&system_prompt, &seed) {
+        (Some(prompt), Some(seed)) => Some(format!("{}\n\n{}", prompt, seed.transcript)),
+        (Some(prompt), None) => Some(prompt.clone()),
+        (None, Some(seed)) => Some(seed.transcript.clone()),
+        (None, None) => None,
+    };
+    if let Some(content) = system_content {
         config.system_message = Some(copilot_sdk::SystemMessageConfig {
-            content: Some(prompt.clone()),
+            content: Some(content),
             ..Default::default()
         });
     }
@@ -322,75 +804,2353 @@ pub async fn create_session(
         "Failed to create session. Please try again.".to_string()
     })?;
 
+    let session_id = bind_session(state, conversation_id, session, model, system_prompt).await;
+
+    let (carried_over, omitted) = seed
+        .map(|seed| (seed.carried_over, seed.omitted as u32))
+        .unwrap_or_default();
+    Ok(SessionStart {
+        session_id,
+        reused: false,
+        resumed: false,
+        carried_over,
+        omitted,
+    })
+}
+
+/// Resumes SDK session `sdk_session_id` for `conversation_id`. Returns `None`
+/// when the CLI no longer has it, so the caller can fall back to replaying
+/// stored history.
+///
+/// The session keeps the system prompt, model, reasoning effort and working
+/// directory it was created with; changing the conversation's persona or
+/// workspace forgets the stored session so it is never resumed with stale
+/// ones (see `reset_conversation_session`).
+async fn resume_session(
+    state: &AppState,
+    conversation_id: &str,
+    sdk_session_id: &str,
+    model: Option<String>,
+    system_prompt: Option<String>,
+) -> Option<SessionStart> {
+    let persona = conversation_persona(state, conversation_id).await;
+    let system_prompt = persona
+        .as_ref()
+        .and_then(|p| p.system_prompt.clone())
+        .or(system_prompt);
+    let config = copilot_sdk::ResumeSessionConfig {
+        tools: builtin_tools(state, conversation_id).await,
+        mcp_servers: mcp_servers(state, persona.as_ref()).await,
+        ..Default::default()
+    };
+    let resumed = {
+        let client_guard = state.client.read().await;
+        let client = client_guard.as_ref()?;
+        client.resume_session(sdk_session_id, config).await
+    };
+    let session = match resumed {
+        Ok(session) => session,
+        Err(e) => {
+            tracing::info!(
+                "Could not resume session {} for conversation {}, replaying history instead: {}",
+                sdk_session_id,
+                conversation_id,
+                e
+            );
+            return None;
+        }
+    };
+    tracing::info!(
+        "Resumed session {} for conversation {}",
+        sdk_session_id,
+        conversation_id
+    );
+    release_conversation_session(state, conversation_id).await;
+    let session_id = bind_session(state, conversation_id, session, model, system_prompt).await;
+    Some(SessionStart {
+        session_id,
+        reused: false,
+        resumed: true,
+        carried_over: Vec::new(),
+        omitted: 0,
+    })
+}
+
+/// Registers `session` as the one bound to `conversation_id` and remembers it
+/// in the database so it can be resumed after a restart. Returns its id.
+async fn bind_session(
+    state: &AppState,
+    conversation_id: &str,
+    session: std::sync::Arc<copilot_sdk::Session>,
+    model: Option<String>,
+    system_prompt: Option<String>,
+) -> String {
     let session_id = session.session_id().to_string();
+    crate::permissions::register(state, &session, conversation_id).await;
+    let workdir = working_directory(state, conversation_id).await;
+    crate::tools::register(&state.db, &session, conversation_id, &workdir).await;
+    let remembered = with_db(state, |conn| {
+        db::set_sdk_session(
+            conn,
+            conversation_id,
+            Some((session_id.as_str(), model.as_deref())),
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await;
+    if let Err(e) = remembered {
+        tracing::warn!(
+            "Failed to store session id for conversation {}: {}",
+            conversation_id,
+            e
+        );
+    }
+
+    let replaced = {
+        let mut sessions = state.sessions.write().await;
+        let mut bindings = state.conversation_sessions.write().await;
+        sessions.insert(
+            session_id.clone(),
+            crate::state::SessionInfo {
+                session,
+                conversation_id: conversation_id.to_string(),
+                model,
+                system_prompt,
+                cancel_token: CancellationToken::new(),
+                turn_token: None,
+                queue: Default::default(),
+                last_used: std::time::Instant::now(),
+            },
+        );
+        // A concurrent create_session for the same conversation may have bound a
+        // session in the meantime; the newest one wins and the other is cancelled.
+        let previous = bindings
+            .insert(conversation_id.to_string(), session_id.clone())
+            .filter(|previous| *previous != session_id);
+        if let Some(info) = previous.as_ref().and_then(|id| sessions.remove(id)) {
+            info.cancel_token.cancel();
+        }
+        previous
+    };
+    if let Some(previous) = replaced {
+        state.permissions.deny_pending(&previous).await;
+    }
+
+    state.sessions_changed.notify_one();
+    session_id
+}
+
+/// Re-creates the sessions that were live when the CLI was restarted. Each is
+/// resumed when the new process still has it and is otherwise reopened from
+/// stored history; sessions that end up with a new id are reported evicted.
+pub(crate) async fn restore_sessions(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let previous: Vec<(String, crate::state::SessionInfo)> = {
+        let mut sessions = state.sessions.write().await;
+        let mut bindings = state.conversation_sessions.write().await;
+        bindings.clear();
+        sessions.drain().collect()
+    };
+
+    for (session_id, info) in previous {
+        state.permissions.deny_pending(&session_id).await;
+        // A streaming turn ends as if aborted so its partial reply is kept.
+        match info.turn_token {
+            Some(turn) => turn.cancel(),
+            None => info.cancel_token.cancel(),
+        }
+        let mut queue = info.queue;
+        if !queue.is_empty() {
+            while let Some((_, channel)) = queue.pop() {
+                TurnStream::new(channel).send(|seq| StreamEvent::Error {
+                    seq,
+                    message: "Copilot restarted before this message was sent.".to_string(),
+                });
+            }
+            emit_queue_updated(app, &session_id, &info.conversation_id, Vec::new());
+        }
+
+        let restored = restore_session(
+            &state,
+            &info.conversation_id,
+            &session_id,
+            info.model,
+            info.system_prompt,
+        )
+        .await;
+        match restored {
+            Ok(new_id) if new_id == session_id => {}
+            Ok(_) => crate::reaper::emit_session_evicted(
+                app,
+                &session_id,
+                &info.conversation_id,
+                crate::reaper::EvictReason::Restarted,
+            ),
+            Err(e) => {
+                tracing::warn!(
+                    "Could not restore session for conversation {}: {}",
+                    info.conversation_id,
+                    e
+                );
+                crate::reaper::emit_session_evicted(
+                    app,
+                    &session_id,
+                    &info.conversation_id,
+                    crate::reaper::EvictReason::Restarted,
+                );
+            }
+        }
+    }
+}
+
+/// Resumes `session_id` for `conversation_id` or, failing that, opens a new
+/// session seeded from stored history. Returns the id of the session bound.
+async fn restore_session(
+    state: &AppState,
+    conversation_id: &str,
+    session_id: &str,
+    model: Option<String>,
+    system_prompt: Option<String>,
+) -> Result<String, String> {
+    if let Some(start) = resume_session(
+        state,
+        conversation_id,
+        session_id,
+        model.clone(),
+        system_prompt.clone(),
+    )
+    .await
+    {
+        return Ok(start.session_id);
+    }
+    let (history, budget) = with_db(state, |conn| {
+        let history = db::get_conversation_history(conn, conversation_id).map_err(|e| {
+            tracing::error!("Failed to get conversation history: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
+        Ok((history, context_budget(conn)?))
+    })
+    .await?;
+    let start = open_session(
+        state,
+        conversation_id,
+        model,
+        system_prompt,
+        &history,
+        budget,
+    )
+    .await?;
+    Ok(start.session_id)
+}
+
+/// Removes the session bound to `conversation_id`, if any, cancels its event
+/// loop and denies its open permission requests.
+async fn release_conversation_session(state: &AppState, conversation_id: &str) {
+    let released = {
+        let mut sessions = state.sessions.write().await;
+        let mut bindings = state.conversation_sessions.write().await;
+        let Some(session_id) = bindings.remove(conversation_id) else {
+            return;
+        };
+        if let Some(info) = sessions.remove(&session_id) {
+            info.cancel_token.cancel();
+            tracing::info!(
+                "Session {} released from conversation {}",
+                session_id,
+                conversation_id
+            );
+        }
+        session_id
+    };
+    state.permissions.deny_pending(&released).await;
+}
 
+#[tauri::command]
+pub async fn destroy_session(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
+    state.permissions.deny_pending(&session_id).await;
     let mut sessions = state.sessions.write().await;
-    sessions.insert(
-        session_id.clone(),
-        crate::state::SessionInfo {
-            session,
-            model,
-            system_prompt,
-            cancel_token: CancellationToken::new(),
-        },
-    );
+    let Some(info) = sessions.remove(&session_id) else {
+        return Ok(());
+    };
+    // Cancel the event-processing task so it stops emitting events immediately
+    info.cancel_token.cancel();
+    let mut bindings = state.conversation_sessions.write().await;
+    tracing::info!("Session {} destroyed and event loop cancelled", session_id);
+    if bindings.get(&info.conversation_id) == Some(&session_id) {
+        bindings.remove(&info.conversation_id);
+    }
+    Ok(())
+}
+
+/// One event of a streaming turn, delivered on the channel passed to the
+/// command that started the turn. `seq` numbers a turn's events from 0.
+#[derive(serde::Serialize, Clone, Debug, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum StreamEvent {
+    /// A queued prompt was sent; its reply follows on the same channel.
+    Dispatched {
+        seq: u32,
+        message: Message,
+    },
+    ThinkingDelta {
+        seq: u32,
+        delta: String,
+    },
+    ThinkingComplete {
+        seq: u32,
+        content: String,
+    },
+    MessageDelta {
+        seq: u32,
+        delta: String,
+    },
+    MessageComplete {
+        seq: u32,
+        content: String,
+    },
+    Usage {
+        seq: u32,
+        input_tokens: Option<f64>,
+        output_tokens: Option<f64>,
+    },
+    /// The agent started running a tool.
+    ToolStarted {
+        seq: u32,
+        tool_call: ToolCall,
+    },
+    ToolProgress {
+        seq: u32,
+        tool_call_id: String,
+        message: String,
+    },
+    /// A tool finished; `tool_call` carries its result and duration.
+    ToolCompleted {
+        seq: u32,
+        tool_call: ToolCall,
+    },
+    /// The reply was stored under `message_id`.
+    MessageSaved {
+        seq: u32,
+        message_id: String,
+    },
+    /// `abort_message` stopped the reply. `message_id` is set when the partial
+    /// reply was stored.
+    Aborted {
+        seq: u32,
+        content: String,
+        message_id: Option<String>,
+    },
+    Error {
+        seq: u32,
+        message: String,
+    },
+    /// The reply is complete.
+    Idle {
+        seq: u32,
+    },
+}
+
+/// Delivers one turn's events to the caller's channel, numbering them.
+/// A closed channel (e.g. a reloaded webview) does not stop the turn; the
+/// reply is still stored.
+struct TurnStream {
+    channel: Channel<StreamEvent>,
+    seq: u32,
+}
+
+impl TurnStream {
+    fn new(channel: Channel<StreamEvent>) -> Self {
+        Self { channel, seq: 0 }
+    }
+
+    fn send(&mut self, event: impl FnOnce(u32) -> StreamEvent) {
+        let seq = self.seq;
+        self.seq += 1;
+        if let Err(e) = self.channel.send(event(seq)) {
+            tracing::debug!("Dropped stream event {}: {}", seq, e);
+        }
+    }
+}
+
+/// Reads and checks the files attached to a prompt and stores their contents.
+async fn store_attachments(
+    state: &AppState,
+    inputs: Vec<AttachmentInput>,
+) -> Result<Vec<Attachment>, String> {
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let prepared = tokio::task::spawn_blocking(move || crate::attachments::prepare_all(&inputs))
+        .await
+        .map_err(|e| {
+            tracing::error!("Attachment task failed: {}", e);
+            "Failed to read attachments.".to_string()
+        })?
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    // Extract documents up front so unreadable ones are refused before the
+    // prompt is sent, and so the text is cached for every later send
+    for p in prepared.iter().filter(|p| p.attachment.is_document()) {
+        document_text(state, &p.attachment, &p.data).await?;
+    }
+    with_db(state, |conn| {
+        for p in &prepared {
+            let thumbnail = p.thumbnail.as_deref();
+            db::save_attachment_data(conn, &p.attachment.sha256, &p.data, thumbnail).map_err(
+                |e| {
+                    tracing::error!("Failed to store attachment: {}", e);
+                    "Failed to store attachments.".to_string()
+                },
+            )?;
+        }
+        Ok(())
+    })
+    .await?;
+    Ok(prepared.into_iter().map(|p| p.attachment).collect())
+}
+
+/// The text of a document attachment, extracted locally on first use and
+/// cached by content hash.
+async fn document_text(
+    state: &AppState,
+    attachment: &Attachment,
+    data: &[u8],
+) -> Result<String, String> {
+    let sha256 = attachment.sha256.clone();
+    let cached = with_db(state, |conn| {
+        db::get_document_text(conn, &sha256).map_err(|e| {
+            tracing::error!("Failed to load document text: {}", e);
+            "Failed to load attachments.".to_string()
+        })
+    })
+    .await?;
+    if let Some(text) = cached {
+        return Ok(text);
+    }
+    let mime_type = attachment.mime_type.clone();
+    let data = data.to_vec();
+    let text = tokio::task::spawn_blocking(move || crate::documents::extract(&mime_type, &data))
+        .await
+        .map_err(|e| {
+            tracing::error!("Document extraction task failed: {}", e);
+            "Failed to read attachments.".to_string()
+        })?
+        .map_err(|e| -> String {
+            AppError::Validation(format!("{}: {}", attachment.name, e)).into()
+        })?;
+    with_db(state, |conn| {
+        db::save_document_text(conn, &sha256, &text).map_err(|e| {
+            tracing::error!("Failed to cache document text: {}", e);
+            "Failed to store attachments.".to_string()
+        })
+    })
+    .await?;
+    Ok(text)
+}
 
-    Ok(session_id)
+/// Bytes of document text that fit in one message to `model`: the message
+/// size limit, or half the model's context window when that is smaller, so
+/// there is room left for the conversation and the reply.
+async fn document_budget(state: &AppState, model: Option<&str>) -> usize {
+    let cache = state.cached_models.read().await;
+    let tokens = model.and_then(|model| {
+        cache
+            .as_ref()
+            .and_then(|models| models.iter().find(|m| m.id == model))
+            .and_then(|m| m.context_window_tokens)
+    });
+    match tokens {
+        Some(tokens) => {
+            MAX_MESSAGE_SIZE.min(tokens as usize * crate::documents::CHARS_PER_TOKEN / 2)
+        }
+        None => MAX_MESSAGE_SIZE,
+    }
+}
+
+/// Refuses images unless `model` is known to accept them.
+async fn require_vision(state: &AppState, model: Option<&str>) -> Result<(), String> {
+    let models = load_models(state).await?;
+    match model.and_then(|model| models.iter().find(|m| m.id == model)) {
+        Some(info) if info.supports_vision => Ok(()),
+        Some(info) => Err(AppError::Validation(format!(
+            "{} cannot read images. Choose a model with vision support or remove the images.",
+            info.name
+        ))
+        .into()),
+        None => Err(AppError::Validation(
+            "Choose a model with vision support to send images.".into(),
+        )
+        .into()),
+    }
+}
+
+/// What the SDK is sent for `prompt` on a session using `model`: its text
+/// followed by the text of attached documents, plus its other attachments
+/// written to the conversation's sandbox so the CLI can read them. Those
+/// files are deleted with `attachments::discard` when the turn ends.
+async fn message_options(
+    state: &AppState,
+    prompt: &Message,
+    model: Option<&str>,
+) -> Result<copilot_sdk::MessageOptions, String> {
+    if prompt.attachments.iter().any(Attachment::is_image) {
+        require_vision(state, model).await?;
+    }
+    let mut options = copilot_sdk::MessageOptions {
+        prompt: prompt.content.clone(),
+        attachments: None,
+        mode: None,
+    };
+    if prompt.attachments.is_empty() {
+        return Ok(options);
+    }
+    let contents = with_db(state, |conn| {
+        prompt
+            .attachments
+            .iter()
+            .map(|a| {
+                db::get_attachment_data(conn, &a.sha256)
+                    .map_err(|e| {
+                        tracing::error!("Failed to load attachment {}: {}", a.id, e);
+                        "Failed to load attachments.".to_string()
+                    })?
+                    .ok_or_else(|| -> String {
+                        AppError::NotFound(format!("{} is no longer available.", a.name)).into()
+                    })
+            })
+            .collect::<Result<Vec<_>, String>>()
+    })
+    .await?;
+    let mut documents = Vec::new();
+    for (attachment, data) in prompt.attachments.iter().zip(&contents) {
+        if attachment.is_document() {
+            documents.push((
+                attachment.name.as_str(),
+                document_text(state, attachment, data).await?,
+            ));
+        }
+    }
+    if !documents.is_empty() {
+        let documents: Vec<(&str, &str)> = documents
+            .iter()
+            .map(|(name, text)| (*name, text.as_str()))
+            .collect();
+        let budget = document_budget(state, model).await;
+        options.prompt = crate::documents::render(&prompt.content, &documents, budget);
+    }
+    let files: Vec<_> = prompt
+        .attachments
+        .iter()
+        .zip(&contents)
+        .filter(|(a, _)| !a.is_document())
+        .collect();
+    if files.is_empty() {
+        return Ok(options);
+    }
+    let sandbox = crate::sandbox::create(&crate::sandbox::root(), &prompt.conversation_id)
+        .map_err(|e| {
+            tracing::error!("Failed to create sandbox for attachments: {}", e);
+            "Failed to prepare attachments.".to_string()
+        })?;
+    let mut attachments = Vec::with_capacity(files.len());
+    let mut written = Vec::with_capacity(files.len());
+    for (attachment, data) in files {
+        let path = crate::attachments::materialize(&sandbox, attachment, data).map_err(|e| {
+            tracing::error!("Failed to write attachment {}: {}", attachment.id, e);
+            crate::attachments::discard(&written);
+            "Failed to prepare attachments.".to_string()
+        })?;
+        written.push(path.clone());
+        attachments.push(copilot_sdk::UserMessageAttachment {
+            attachment_type: copilot_sdk::AttachmentType::File,
+            path: path.to_string_lossy().into_owned(),
+            display_name: attachment.name.clone(),
+        });
+    }
+    options.attachments = Some(attachments);
+    Ok(options)
+}
+
+#[tauri::command]
+pub async fn send_message(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    conversation_id: String,
+    session_id: String,
+    content: String,
+    attachments: Option<Vec<AttachmentInput>>,
+    on_event: Channel<StreamEvent>,
+) -> Result<SendReceipt, String> {
+    if content.len() > MAX_MESSAGE_SIZE {
+        return Err(AppError::Validation(
+            "Message is too large. Please shorten your message.".into(),
+        )
+        .into());
+    }
+    let attachments = attachments.unwrap_or_default();
+    if content.trim().is_empty() && attachments.is_empty() {
+        return Err(AppError::Validation("Message cannot be empty.".into()).into());
+    }
+    let attachments = store_attachments(&state, attachments).await?;
+
+    let user_message = Message {
+        id: uuid::Uuid::new_v4().to_string(),
+        conversation_id: conversation_id.clone(),
+        role: "user".to_string(),
+        content,
+        created_at: now_timestamp(),
+        interrupted: false,
+        parent_id: None,
+        sibling_index: 0,
+        sibling_count: 0,
+        tool_calls: Vec::new(),
+        attachments,
+    };
+
+    // Claim the session, or wait behind the turn that is already streaming.
+    {
+        let mut sessions = state.sessions.write().await;
+        let info = sessions
+            .get_mut(&session_id)
+            .ok_or::<String>(AppError::NotFound("Session not found".into()).into())?;
+        if info.conversation_id != conversation_id {
+            return Err(AppError::Validation(
+                "Session does not belong to this conversation.".into(),
+            )
+            .into());
+        }
+        if info.turn_token.is_some() || !info.queue.is_empty() {
+            if info.queue.len() >= crate::queue::MAX_QUEUED_PROMPTS {
+                return Err(AppError::Validation(
+                    "Too many queued messages. Wait for the current reply to finish.".into(),
+                )
+                .into());
+            }
+            let position = info.queue.push(user_message.clone(), on_event);
+            let queue = info.queue.snapshot();
+            drop(sessions);
+            tracing::info!(
+                "Queued message for session {} at position {}",
+                session_id,
+                position
+            );
+            emit_queue_updated(&app, &session_id, &conversation_id, queue);
+            return Ok(SendReceipt {
+                message: user_message,
+                queue_position: Some(position),
+            });
+        }
+        info.turn_token = Some(info.cancel_token.child_token());
+    }
+
+    match start_turn(
+        &app,
+        &state,
+        &session_id,
+        &user_message,
+        StorePrompt::AtActiveLeaf,
+        on_event,
+    )
+    .await
+    {
+        Ok(stored) => Ok(SendReceipt {
+            message: stored.unwrap_or(user_message),
+            queue_position: None,
+        }),
+        Err(e) => {
+            // Hand the session to whatever queued up behind this prompt.
+            tokio::spawn(finish_turn(app.clone(), session_id));
+            Err(e)
+        }
+    }
+}
+
+/// How `start_turn` stores the prompt once the SDK has accepted it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum StorePrompt {
+    /// The prompt is already stored (regenerating its reply).
+    Existing,
+    /// Under exactly `prompt.parent_id` (editing a prompt).
+    AtParent,
+    /// After the conversation's active message at the time it is sent.
+    AtActiveLeaf,
+    /// Like `AtActiveLeaf`, for a prompt taken off the session's queue. The
+    /// stream starts with `StreamEvent::Dispatched`.
+    Dequeued,
+}
+
+/// Sends `prompt` on the session and spawns the event loop that streams the
+/// reply to `channel` and stores it as a child of `prompt`.
+///
+/// Unless the prompt already exists it is stored once the SDK has accepted it,
+/// so neither side of the turn depends on the webview staying alive, and the
+/// turn is aborted if it cannot be stored. The stored prompt is returned with
+/// its sibling position filled in. A turn token claimed by the caller is
+/// reused, so an abort in between still applies.
+async fn start_turn(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    session_id: &str,
+    prompt: &Message,
+    store: StorePrompt,
+    channel: Channel<StreamEvent>,
+) -> Result<Option<Message>, String> {
+    let session = {
+        let mut sessions = state.sessions.write().await;
+        let session_info = sessions
+            .get_mut(session_id)
+            .ok_or::<String>(AppError::NotFound("Session not found".into()).into())?;
+        if session_info.conversation_id != prompt.conversation_id {
+            return Err(AppError::Validation(
+                "Session does not belong to this conversation.".into(),
+            )
+            .into());
+        }
+        session_info.last_used = std::time::Instant::now();
+        let cancel = session_info.cancel_token.clone();
+        let turn = session_info
+            .turn_token
+            .get_or_insert_with(|| cancel.child_token())
+            .clone();
+        (
+            session_info.session.clone(),
+            cancel,
+            turn,
+            session_info.model.clone(),
+        )
+    };
+
+    let mut events = session.0.subscribe();
+
+    let (retry_limit, deadlines) = with_db(state, |conn| {
+        let deadlines = response_deadlines(conn)?;
+        Ok((
+            send_retry_limit(conn)?,
+            Deadlines::resolve(&deadlines, session.3.as_deref()),
+        ))
+    })
+    .await
+    .unwrap_or_else(|_| {
+        (
+            crate::retry::DEFAULT_SEND_RETRY_LIMIT,
+            Deadlines::resolve(&DeadlineSettings::default(), session.3.as_deref()),
+        )
+    });
+    let options = match message_options(state, prompt, session.3.as_deref()).await {
+        Ok(options) => options,
+        Err(e) => {
+            if let Some(info) = state.sessions.write().await.get_mut(session_id) {
+                info.turn_token = None;
+            }
+            return Err(e);
+        }
+    };
+    let materialized: Vec<std::path::PathBuf> = options
+        .attachments
+        .iter()
+        .flatten()
+        .map(|a| std::path::PathBuf::from(&a.path))
+        .collect();
+    let mut attempt = 0;
+    while let Err(e) = session.0.send(options.clone()).await {
+        let reason = e.to_string();
+        let class = crate::retry::classify(&reason);
+        if class == ErrorClass::Retryable && attempt < retry_limit && !session.2.is_cancelled() {
+            attempt += 1;
+            let delay = crate::retry::backoff_delay(attempt, crate::retry::jitter());
+            tracing::warn!(
+                "Failed to send message (session {}), retrying in {:?} ({}/{}): {}",
+                session_id,
+                delay,
+                attempt,
+                retry_limit,
+                reason
+            );
+            emit_retrying(
+                app,
+                session_id,
+                &prompt.conversation_id,
+                attempt,
+                retry_limit,
+                delay,
+                &reason,
+            );
+            tokio::select! {
+                _ = session.2.cancelled() => {}
+                _ = tokio::time::sleep(delay) => continue,
+            }
+        }
+        tracing::error!(
+            "Failed to send message (session {}): {}",
+            session_id,
+            reason
+        );
+        if let Some(info) = state.sessions.write().await.get_mut(session_id) {
+            info.turn_token = None;
+        }
+        let message = if session.2.is_cancelled() {
+            "Sending was cancelled."
+        } else if class == ErrorClass::Retryable {
+            "Copilot is not responding right now. Please try again in a moment."
+        } else {
+            "Failed to send message. Please try again."
+        };
+        crate::attachments::discard(&materialized);
+        return Err(AppError::Network(message.into()).into());
+    }
+
+    let mut stored = None;
+    if store != StorePrompt::Existing {
+        let saved = with_db(state, |conn| {
+            let saved = if store == StorePrompt::AtParent {
+                db::insert_message(conn, prompt)
+            } else {
+                db::save_message(
+                    conn,
+                    &Message {
+                        parent_id: None,
+                        ..prompt.clone()
+                    },
+                )
+            };
+            let saved = saved.map_err(|e| e.to_string())?;
+            db::save_attachments(conn, &saved.conversation_id, &saved.id, &saved.attachments)
+                .map_err(|e| e.to_string())?;
+            Ok(saved)
+        })
+        .await;
+        match saved {
+            Ok(message) => stored = Some(message),
+            Err(e) => {
+                // A reply stored under a missing prompt would be an orphan
+                tracing::error!(
+                    "Failed to persist user message (session {}): {}",
+                    session_id,
+                    e
+                );
+                if let Err(e) = session.0.abort().await {
+                    tracing::warn!("Failed to abort generation (session {}): {}", session_id, e);
+                }
+                if let Some(info) = state.sessions.write().await.get_mut(session_id) {
+                    info.turn_token = None;
+                }
+                crate::attachments::discard(&materialized);
+                return Err(AppError::Internal("Failed to save message.".into()).into());
+            }
+        }
+    }
+    let mut stream = TurnStream::new(channel);
+    if store == StorePrompt::Dequeued {
+        let message = stored.clone().unwrap_or_else(|| prompt.clone());
+        stream.send(|seq| StreamEvent::Dispatched { seq, message });
+    }
+
+    let sid = session_id.to_string();
+    let convo_id = prompt.conversation_id.clone();
+    let reply_parent_id = prompt.id.clone();
+    let app_handle = app.clone();
+    let verbose = crate::is_verbose();
+    let coalesce = CoalesceConfig::from_env();
+    let sdk_session = session.0;
+    let prompt_options = options;
+    let cancel = session.1;
+    let turn = session.2;
+    tokio::spawn(async move {
+        if verbose {
+            tracing::debug!(
+                "[VERBOSE] Event loop started for session {} (deadlines: {:?})",
+                sid,
+                deadlines
+            );
+        }
+        let mut started = std::time::Instant::now();
+        let mut last_event = started;
+        let mut accumulated_content = String::with_capacity(4096);
+        let mut final_content: Option<String> = None;
+        let mut got_any_delta = false;
+        let mut thinking_deltas = DeltaBuffer::new(coalesce);
+        let mut reply_deltas = DeltaBuffer::new(coalesce);
+        let mut tools = ToolLog::default();
+        loop {
+            if cancel.is_cancelled() {
+                if verbose {
+                    tracing::debug!("[VERBOSE] Event loop cancelled for session {}", sid);
+                }
+                break;
+            }
+            let got_first_token = got_any_delta
+                || final_content.is_some()
+                || thinking_deltas.counts().0 > 0
+                || !tools.calls().is_empty();
+            let expiry = deadlines.next_expiry(
+                std::time::Instant::now(),
+                started,
+                last_event,
+                got_first_token,
+            );
+            let flush_at = match (thinking_deltas.deadline(), reply_deltas.deadline()) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            tokio::select! {
+                _ = sleep_until(flush_at), if flush_at.is_some() => {
+                    flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                }
+                _ = turn.cancelled() => {
+                    if cancel.is_cancelled() {
+                        if verbose {
+                            tracing::debug!("[VERBOSE] Event loop cancelled (select) for session {}", sid);
+                        }
+                        break;
+                    }
+                    // Only the turn was cancelled: abort_message stopped the generation.
+                    tracing::info!("Generation aborted for session {}", sid);
+                    flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                    let content = final_content.take().unwrap_or(accumulated_content);
+                    let saved = persist_assistant_reply(&app_handle, &sid, &convo_id, &content, &reply_parent_id, true, tools.calls()).await;
+                    let message_id = saved.map(|m| m.id);
+                    stream.send(|seq| StreamEvent::Aborted { seq, content, message_id });
+                    break;
+                }
+                result = with_deadline(expiry.map(|(wait, _)| wait), events.recv()) => {
+                    match result {
+                        Ok(Ok(event)) => {
+                            if cancel.is_cancelled() { break; }
+                            last_event = std::time::Instant::now();
+                            match &event.data {
+                                copilot_sdk::SessionEventData::AssistantReasoningDelta(delta) => {
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Thinking delta (session {}): {} bytes", sid, delta.delta_content.len());
+                                    }
+                                    if thinking_deltas.push(&delta.delta_content, std::time::Instant::now()) {
+                                        flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                                    }
+                                }
+                                copilot_sdk::SessionEventData::AssistantReasoning(reasoning) => {
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Thinking complete (session {}): {} chars", sid, reasoning.content.len());
+                                    }
+                                    flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                                    let content = reasoning.content.clone();
+                                    stream.send(|seq| StreamEvent::ThinkingComplete { seq, content });
+                                }
+                                copilot_sdk::SessionEventData::AssistantMessageDelta(delta) => {
+                                    got_any_delta = true;
+                                    accumulated_content.push_str(&delta.delta_content);
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Delta (session {}): {} bytes", sid, delta.delta_content.len());
+                                    }
+                                    if reply_deltas.push(&delta.delta_content, std::time::Instant::now()) {
+                                        flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                                    }
+                                }
+                                copilot_sdk::SessionEventData::AssistantMessage(msg) => {
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Complete message (session {}): {} chars", sid, msg.content.len());
+                                    }
+                                    flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                                    let content = msg.content.clone();
+                                    stream.send(|seq| StreamEvent::MessageComplete { seq, content });
+                                    final_content = Some(msg.content.clone());
+                                }
+                                copilot_sdk::SessionEventData::SessionIdle(_) => {
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Session idle: {}", sid);
+                                    }
+                                    flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                                    let reply = final_content.as_deref().unwrap_or(&accumulated_content);
+                                    save_reply(&app_handle, &mut stream, &sid, &convo_id, reply, &reply_parent_id, tools.calls()).await;
+                                    stream.send(|seq| StreamEvent::Idle { seq });
+                                    break;
+                                }
+                                copilot_sdk::SessionEventData::SessionError(err) => {
+                                    // Nothing reached the UI and no tool ran yet, so a transient failure can
+                                    // be retried without duplicating the reply or its side effects.
+                                    let nothing_streamed = !got_any_delta && final_content.is_none() && thinking_deltas.counts().0 == 0 && tools.calls().is_empty();
+                                    if nothing_streamed && attempt < retry_limit && crate::retry::classify(&err.message) == ErrorClass::Retryable {
+                                        attempt += 1;
+                                        let delay = crate::retry::backoff_delay(attempt, crate::retry::jitter());
+                                        tracing::warn!("Session error ({}), retrying in {:?} ({}/{}): {}", sid, delay, attempt, retry_limit, err.message);
+                                        emit_retrying(&app_handle, &sid, &convo_id, attempt, retry_limit, delay, &err.message);
+                                        let resent = tokio::select! {
+                                            // The abort is handled at the top of the loop
+                                            _ = turn.cancelled() => true,
+                                            _ = tokio::time::sleep(delay) => match sdk_session.send(prompt_options.clone()).await {
+                                                Ok(_) => true,
+                                                Err(e) => {
+                                                    tracing::error!("Retry failed (session {}): {}", sid, e);
+                                                    false
+                                                }
+                                            },
+                                        };
+                                        if resent {
+                                            continue;
+                                        }
+                                    }
+                                    tracing::error!("Session error ({}): {}", sid, err.message);
+                                    flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                                    let reply = final_content.as_deref().unwrap_or(&accumulated_content);
+                                    save_reply(&app_handle, &mut stream, &sid, &convo_id, reply, &reply_parent_id, tools.calls()).await;
+                                    let message = err.message.clone();
+                                    stream.send(|seq| StreamEvent::Error { seq, message });
+                                    break;
+                                }
+                                copilot_sdk::SessionEventData::ToolExecutionStart(tool) => {
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Tool started (session {}): {} ({})", sid, tool.tool_name, tool.tool_call_id);
+                                    }
+                                    flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                                    let arguments = tool.arguments.as_ref().map(|a| a.to_string());
+                                    let tool_call = tools
+                                        .start(&tool.tool_call_id, &tool.tool_name, arguments, now_timestamp(), std::time::Instant::now())
+                                        .clone();
+                                    stream.send(|seq| StreamEvent::ToolStarted { seq, tool_call });
+                                }
+                                copilot_sdk::SessionEventData::ToolExecutionProgress(progress) => {
+                                    if tools.progress(&progress.tool_call_id, &progress.progress_message).is_some() {
+                                        let (tool_call_id, message) = (progress.tool_call_id.clone(), progress.progress_message.clone());
+                                        stream.send(|seq| StreamEvent::ToolProgress { seq, tool_call_id, message });
+                                    }
+                                }
+                                copilot_sdk::SessionEventData::ToolExecutionComplete(done) => {
+                                    let result = done.result.as_ref().map(|r| r.content.as_str());
+                                    let error = done.error.as_ref().map(|e| e.message.as_str());
+                                    let completed = tools.complete(&done.tool_call_id, done.success, result, error, std::time::Instant::now());
+                                    if let Some(tool_call) = completed.cloned() {
+                                        if verbose {
+                                            tracing::debug!("[VERBOSE] Tool finished (session {}): {} in {:?}ms (success: {})", sid, tool_call.tool_name, tool_call.duration_ms, done.success);
+                                        }
+                                        stream.send(|seq| StreamEvent::ToolCompleted { seq, tool_call });
+                                    }
+                                }
+                                copilot_sdk::SessionEventData::AssistantUsage(usage) => {
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Usage (session {}): in={:?} out={:?}", sid, usage.input_tokens, usage.output_tokens);
+                                    }
+                                    let (input_tokens, output_tokens) = (usage.input_tokens, usage.output_tokens);
+                                    stream.send(|seq| StreamEvent::Usage { seq, input_tokens, output_tokens });
+                                }
+                                _ => {
+                                    if verbose {
+                                        tracing::debug!("[VERBOSE] Unhandled event for session {}", sid);
+                                    }
+                                }
+                            }
+                        }
+                        Ok(Err(e)) => {
+                            if verbose {
+                                tracing::debug!("[VERBOSE] Event channel closed for session {}: {}", sid, e);
+                            }
+                            flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                            if got_any_delta {
+                                let content = accumulated_content.clone();
+                                stream.send(|seq| StreamEvent::MessageComplete { seq, content });
+                            }
+                            let reply = final_content.as_deref().unwrap_or(&accumulated_content);
+                            save_reply(&app_handle, &mut stream, &sid, &convo_id, reply, &reply_parent_id, tools.calls()).await;
+                            stream.send(|seq| StreamEvent::Idle { seq });
+                            break;
+                        }
+                        Err(()) => {
+                            let Some((_, kind)) = expiry else { break };
+                            if app_handle.state::<AppState>().permissions.is_waiting(&sid).await {
+                                // Time spent waiting for the user's answer does not count against the model
+                                let now = std::time::Instant::now();
+                                started += now - last_event;
+                                last_event = now;
+                                continue;
+                            }
+                            let length = deadlines.length(kind);
+                            tracing::warn!("{:?} deadline ({:?}) hit for session {} — flushing partial response", kind, length, sid);
+                            // The model may still be working, e.g. on a long tool call; stop it so
+                            // the next queued prompt finds the session free
+                            if let Err(e) = sdk_session.abort().await {
+                                tracing::warn!("Failed to abort generation (session {}): {}", sid, e);
+                            }
+                            flush_deltas(&mut stream, &mut thinking_deltas, &mut reply_deltas);
+                            if got_any_delta {
+                                let content = accumulated_content.clone();
+                                stream.send(|seq| StreamEvent::MessageComplete { seq, content });
+                            }
+                            // Cut off by the deadline, so kept as an interrupted reply like an abort
+                            let reply = final_content.as_deref().unwrap_or(&accumulated_content);
+                            if let Some(message) = persist_assistant_reply(&app_handle, &sid, &convo_id, reply, &reply_parent_id, true, tools.calls()).await {
+                                stream.send(|seq| StreamEvent::MessageSaved { seq, message_id: message.id });
+                            }
+                            let message = crate::deadlines::error_message(kind, length, got_any_delta);
+                            stream.send(|seq| StreamEvent::Error { seq, message });
+                            stream.send(|seq| StreamEvent::Idle { seq });
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        if verbose {
+            let (thinking_received, thinking_sent) = thinking_deltas.counts();
+            let (reply_received, reply_sent) = reply_deltas.counts();
+            let received = thinking_received + reply_received;
+            let sent = thinking_sent + reply_sent;
+            tracing::debug!(
+                "[VERBOSE] Event loop ended for session {}: {} deltas sent as {} events ({} emits saved)",
+                sid,
+                received,
+                sent,
+                received - sent
+            );
+        }
+        crate::attachments::discard(&materialized);
+        if !cancel.is_cancelled() {
+            finish_turn(app_handle, sid).await;
+        }
+    });
+
+    Ok(stored)
+}
+
+/// Ends the session's current turn and sends the next queued prompt, if any.
+/// Prompts the SDK refuses get a `StreamEvent::Error` and are skipped.
+///
+/// Boxed because it starts a turn whose event loop calls back into it.
+fn finish_turn(
+    app: tauri::AppHandle,
+    session_id: String,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        let state = app.state::<AppState>();
+        loop {
+            let (next, queue) = {
+                let mut sessions = state.sessions.write().await;
+                let Some(info) = sessions.get_mut(&session_id) else {
+                    return;
+                };
+                let next = info.queue.pop();
+                // Keep the session claimed while the next prompt goes out so a
+                // new send_message queues behind it instead of overtaking it.
+                info.turn_token = next.as_ref().map(|_| info.cancel_token.child_token());
+                info.last_used = std::time::Instant::now();
+                (next, info.queue.snapshot())
+            };
+            let Some((prompt, channel)) = next else {
+                return;
+            };
+            emit_queue_updated(&app, &session_id, &prompt.conversation_id, queue);
+
+            let started = start_turn(
+                &app,
+                &state,
+                &session_id,
+                &prompt,
+                StorePrompt::Dequeued,
+                channel.clone(),
+            )
+            .await;
+            match started {
+                Ok(_) => return,
+                Err(e) => {
+                    let message = format!("Queued message could not be sent: {}", e);
+                    TurnStream::new(channel).send(|seq| StreamEvent::Error { seq, message });
+                }
+            }
+        }
+    })
+}
+
+fn emit_queue_updated(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    conversation_id: &str,
+    queue: Vec<QueuedPrompt>,
+) {
+    let _ = app.emit(
+        "copilot:queue-updated",
+        serde_json::json!({
+            "session_id": session_id,
+            "conversation_id": conversation_id,
+            "queue": queue,
+        }),
+    );
+}
+
+/// Applies `edit` to the session's queue and reports the result to the UI.
+async fn update_queue(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    session_id: &str,
+    edit: impl FnOnce(&mut crate::queue::PromptQueue<Channel<StreamEvent>>) -> bool,
+) -> Result<Vec<QueuedPrompt>, String> {
+    let (conversation_id, queue) = {
+        let mut sessions = state.sessions.write().await;
+        let info = sessions
+            .get_mut(session_id)
+            .ok_or::<String>(AppError::NotFound("Session not found".into()).into())?;
+        if !edit(&mut info.queue) {
+            return Err(AppError::NotFound("Queued message not found".into()).into());
+        }
+        (info.conversation_id.clone(), info.queue.snapshot())
+    };
+    emit_queue_updated(app, session_id, &conversation_id, queue.clone());
+    Ok(queue)
+}
+
+#[tauri::command]
+pub async fn list_queued_prompts(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<QueuedPrompt>, String> {
+    let sessions = state.sessions.read().await;
+    let info = sessions
+        .get(&session_id)
+        .ok_or::<String>(AppError::NotFound("Session not found".into()).into())?;
+    Ok(info.queue.snapshot())
+}
+
+/// Moves a queued prompt to the 1-based `position`, clamped to the queue.
+#[tauri::command]
+pub async fn reorder_queued_prompt(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+    prompt_id: String,
+    position: u32,
+) -> Result<Vec<QueuedPrompt>, String> {
+    update_queue(&app, &state, &session_id, |queue| {
+        queue.move_to(&prompt_id, position)
+    })
+    .await
+}
+
+/// Drops a prompt that has not been sent yet.
+#[tauri::command]
+pub async fn cancel_queued_prompt(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+    prompt_id: String,
+) -> Result<Vec<QueuedPrompt>, String> {
+    update_queue(&app, &state, &session_id, |queue| queue.remove(&prompt_id)).await
+}
+
+/// Awaits `fut`, giving up after `wait` when there is one.
+async fn with_deadline<F: std::future::Future>(
+    wait: Option<Duration>,
+    fut: F,
+) -> Result<F::Output, ()> {
+    match wait {
+        Some(wait) => tokio::time::timeout(wait, fut).await.map_err(|_| ()),
+        None => Ok(fut.await),
+    }
+}
+
+/// Tells the UI that a failed send is retried after `delay`.
+fn emit_retrying(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    conversation_id: &str,
+    attempt: u32,
+    max_attempts: u32,
+    delay: Duration,
+    reason: &str,
+) {
+    let _ = app.emit(
+        "copilot:retrying",
+        serde_json::json!({
+            "session_id": session_id,
+            "conversation_id": conversation_id,
+            "attempt": attempt,
+            "max_attempts": max_attempts,
+            "delay_ms": delay.as_millis() as u64,
+            "reason": reason,
+        }),
+    );
+}
+
+/// Sends whatever thinking and reply text is still buffered, thinking first.
+fn flush_deltas(stream: &mut TurnStream, thinking: &mut DeltaBuffer, reply: &mut DeltaBuffer) {
+    if let Some(delta) = thinking.take() {
+        stream.send(|seq| StreamEvent::ThinkingDelta { seq, delta });
+    }
+    if let Some(delta) = reply.take() {
+        stream.send(|seq| StreamEvent::MessageDelta { seq, delta });
+    }
+}
+
+/// Resolves at `deadline`; never resolves when there is none.
+async fn sleep_until(deadline: Option<std::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Stores the assistant reply of a finished or interrupted turn.
+async fn persist_assistant_reply(
+    app_handle: &tauri::AppHandle,
+    session_id: &str,
+    conversation_id: &str,
+    content: &str,
+    parent_id: &str,
+    interrupted: bool,
+    tool_calls: &[ToolCall],
+) -> Option<Message> {
+    // A reply that only ran tools is kept so the tool calls show in the transcript
+    if content.trim().is_empty() && tool_calls.is_empty() {
+        return None;
+    }
+    let message = Message {
+        id: uuid::Uuid::new_v4().to_string(),
+        conversation_id: conversation_id.to_string(),
+        role: "assistant".to_string(),
+        content: content.to_string(),
+        created_at: now_timestamp(),
+        interrupted,
+        parent_id: Some(parent_id.to_string()),
+        sibling_index: 0,
+        sibling_count: 0,
+        tool_calls: tool_calls.to_vec(),
+        attachments: Vec::new(),
+    };
+    let state = app_handle.state::<AppState>();
+    let saved = with_db(&state, |conn| {
+        let saved = db::save_message(conn, &message).map_err(|e| e.to_string())?;
+        db::save_tool_calls(conn, conversation_id, &saved.id, tool_calls)
+            .map_err(|e| e.to_string())?;
+        Ok(saved)
+    })
+    .await;
+    match saved {
+        Ok(message) => Some(message),
+        Err(e) => {
+            tracing::error!(
+                "Failed to persist assistant reply (session {}): {}",
+                session_id,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Stores a completed reply and tells the caller the id it was saved under so
+/// the UI can finalize its placeholder.
+async fn save_reply(
+    app_handle: &tauri::AppHandle,
+    stream: &mut TurnStream,
+    session_id: &str,
+    conversation_id: &str,
+    content: &str,
+    parent_id: &str,
+    tool_calls: &[ToolCall],
+) {
+    let saved = persist_assistant_reply(
+        app_handle,
+        session_id,
+        conversation_id,
+        content,
+        parent_id,
+        false,
+        tool_calls,
+    )
+    .await;
+    if let Some(message) = saved {
+        stream.send(|seq| StreamEvent::MessageSaved {
+            seq,
+            message_id: message.id,
+        });
+    }
+}
+
+#[tauri::command]
+pub async fn abort_message(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
+    let (session, turn) = {
+        let sessions = state.sessions.read().await;
+        let info = sessions
+            .get(&session_id)
+            .ok_or::<String>(AppError::NotFound("Session not found".into()).into())?;
+        (info.session.clone(), info.turn_token.clone())
+    };
+    let Some(turn) = turn else {
+        return Ok(());
+    };
+
+    // Ask the CLI to stop generating before closing our event loop, so the
+    // session is idle again by the time the next prompt is sent.
+    state.permissions.deny_pending(&session_id).await;
+    if let Err(e) = session.abort().await {
+        tracing::warn!("SDK abort failed (session {}): {}", session_id, e);
+    }
+    turn.cancel();
+    Ok(())
+}
+
+/// Answers a `copilot:permission-request`. With `remember`, the answer is
+/// saved as a rule for the tool and `path_pattern` (exactly the requested
+/// path or command when not given) so the same request is not asked again.
+#[tauri::command]
+pub async fn answer_permission(
+    state: State<'_, AppState>,
+    request_id: String,
+    decision: PermissionDecision,
+    remember: Option<RuleScope>,
+    path_pattern: Option<String>,
+) -> Result<(), String> {
+    let request = state.permissions.take(&request_id).await.ok_or::<String>(
+        AppError::NotFound("Permission request not found or already answered".into()).into(),
+    )?;
+    if let Some(scope) = remember {
+        let rule = PermissionRule {
+            id: uuid::Uuid::new_v4().to_string(),
+            tool: request.ask.tool.clone(),
+            path_pattern: path_pattern.filter(|p| !p.trim().is_empty()).or_else(|| {
+                request
+                    .ask
+                    .target
+                    .as_deref()
+                    .map(crate::permissions::escape_glob)
+            }),
+            conversation_id: match scope {
+                RuleScope::Conversation => Some(request.conversation_id.clone()),
+                RuleScope::Everywhere => None,
+            },
+            decision,
+            created_at: now_timestamp(),
+        };
+        let saved = with_db(&state, |conn| {
+            db::insert_permission_rule(conn, &rule).map_err(|e| {
+                tracing::error!("Failed to save permission rule: {}", e);
+                "Failed to save permission rule.".to_string()
+            })
+        })
+        .await;
+        // Still answer the waiting request; only the rule is lost
+        if let Err(e) = saved {
+            request.answer(decision);
+            return Err(e);
+        }
+    }
+    tracing::info!(
+        "Permission for {} {:?} answered: {:?}",
+        request.ask.tool,
+        request.ask.target,
+        decision
+    );
+    request.answer(decision);
+    Ok(())
+}
+
+/// Saved permission rules: global ones plus those of `conversation_id`, or
+/// every rule when no conversation is given.
+#[tauri::command]
+pub async fn list_permission_rules(
+    state: State<'_, AppState>,
+    conversation_id: Option<String>,
+) -> Result<Vec<PermissionRule>, String> {
+    with_db(&state, |conn| {
+        db::list_permission_rules(conn, conversation_id.as_deref()).map_err(|e| {
+            tracing::error!("Failed to list permission rules: {}", e);
+            "Failed to load permission rules.".to_string()
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn revoke_permission_rule(
+    state: State<'_, AppState>,
+    rule_id: String,
+) -> Result<(), String> {
+    let deleted = with_db(&state, |conn| {
+        db::delete_permission_rule(conn, &rule_id).map_err(|e| {
+            tracing::error!("Failed to delete permission rule: {}", e);
+            "Failed to revoke permission rule.".to_string()
+        })
+    })
+    .await?;
+    if !deleted {
+        return Err(AppError::NotFound("Permission rule not found".into()).into());
+    }
+    Ok(())
+}
+
+/// The app's own tools and whether each is on in `conversation_id`; all on
+/// when no conversation is given.
+#[tauri::command]
+pub async fn list_builtin_tools(
+    state: State<'_, AppState>,
+    conversation_id: Option<String>,
+) -> Result<Vec<BuiltinToolInfo>, String> {
+    let disabled = match conversation_id {
+        Some(conversation_id) => {
+            with_db(&state, |conn| {
+                require_conversation(conn, &conversation_id)?;
+                db::get_disabled_tools(conn, &conversation_id).map_err(|e| {
+                    tracing::error!("Failed to load tool settings: {}", e);
+                    "Failed to load tools.".to_string()
+                })
+            })
+            .await?
+        }
+        None => Vec::new(),
+    };
+    Ok(crate::tools::BUILTIN_TOOLS
+        .iter()
+        .map(|tool| BuiltinToolInfo {
+            name: tool.name.to_string(),
+            description: tool.description.to_string(),
+            enabled: !disabled.iter().any(|d| d == tool.name),
+        })
+        .collect())
+}
+
+/// Turns a built-in tool on or off in `conversation_id`. Sessions that are
+/// already open keep offering a tool that was turned off but refuse to run it.
+#[tauri::command]
+pub async fn set_conversation_tool_enabled(
+    state: State<'_, AppState>,
+    conversation_id: String,
+    tool_name: String,
+    enabled: bool,
+) -> Result<(), String> {
+    if !crate::tools::BUILTIN_TOOLS
+        .iter()
+        .any(|tool| tool.name == tool_name)
+    {
+        return Err(AppError::Validation(format!("Unknown tool: {}", tool_name)).into());
+    }
+    with_db(&state, |conn| {
+        require_conversation(conn, &conversation_id)?;
+        let mut disabled = db::get_disabled_tools(conn, &conversation_id).map_err(|e| {
+            tracing::error!("Failed to load tool settings: {}", e);
+            "Failed to update tools.".to_string()
+        })?;
+        disabled.retain(|d| d != &tool_name);
+        if !enabled {
+            disabled.push(tool_name.clone());
+        }
+        db::set_disabled_tools(conn, &conversation_id, &disabled).map_err(|e| {
+            tracing::error!("Failed to save tool settings: {}", e);
+            "Failed to update tools.".to_string()
+        })
+    })
+    .await
+}
+
+/// Logged calls of the built-in tools, newest first; every conversation's
+/// when `conversation_id` is not given.
+#[tauri::command]
+pub async fn list_tool_audit(
+    state: State<'_, AppState>,
+    conversation_id: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<ToolAuditEntry>, String> {
+    let limit = limit.unwrap_or(100).clamp(1, 1000);
+    with_db(&state, |conn| {
+        db::list_tool_audit(conn, conversation_id.as_deref(), limit).map_err(|e| {
+            tracing::error!("Failed to list tool calls: {}", e);
+            "Failed to load the tool call log.".to_string()
+        })
+    })
+    .await
+}
+
+fn require_unique_server_name(
+    conn: &rusqlite::Connection,
+    name: &str,
+    except_id: Option<&str>,
+) -> Result<(), String> {
+    let servers = db::list_mcp_servers(conn).map_err(|e| {
+        tracing::error!("Failed to list MCP servers: {}", e);
+        "Failed to load MCP servers.".to_string()
+    })?;
+    if servers
+        .iter()
+        .any(|s| s.name == name && Some(s.id.as_str()) != except_id)
+    {
+        return Err(
+            AppError::Validation(format!("An MCP server named {} already exists.", name)).into(),
+        );
+    }
+    Ok(())
+}
+
+/// Forgets the session of `conversation_id` so the next message opens a new
+/// one, e.g. in a different working directory.
+async fn reset_conversation_session(state: &AppState, conversation_id: &str) -> Result<(), String> {
+    release_conversation_session(state, conversation_id).await;
+    with_db(state, |conn| {
+        db::set_sdk_session(conn, conversation_id, None).map_err(|e| {
+            tracing::error!("Failed to clear session id: {}", e);
+            "Failed to update conversation.".to_string()
+        })
+    })
+    .await
+}
+
+/// Folders conversations may use as their workspace, with their subfolders.
+#[tauri::command]
+pub async fn list_trusted_folders(
+    state: State<'_, AppState>,
+) -> Result<Vec<TrustedFolder>, String> {
+    with_db(&state, |conn| {
+        db::list_trusted_folders(conn).map_err(|e| {
+            tracing::error!("Failed to list trusted folders: {}", e);
+            "Failed to load trusted folders.".to_string()
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn add_trusted_folder(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<TrustedFolder, String> {
+    let folder = crate::workspace::normalize(&path)
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    let folder = TrustedFolder {
+        path: folder.to_string_lossy().into_owned(),
+        created_at: now_timestamp(),
+    };
+    with_db(&state, |conn| {
+        db::insert_trusted_folder(conn, &folder).map_err(|e| {
+            tracing::error!("Failed to save trusted folder: {}", e);
+            "Failed to save trusted folder.".to_string()
+        })
+    })
+    .await?;
+    tracing::info!("Trusted folder {}", folder.path);
+    Ok(folder)
+}
+
+/// Removes a folder from the allowlist. Conversations working in it go back
+/// to the sandbox: their sessions are replaced right away, so this is refused
+/// while one of them is streaming a reply.
+#[tauri::command]
+pub async fn remove_trusted_folder(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    let affected = with_db(&state, |conn| {
+        let workspaces = db::list_workspace_folders(conn).map_err(|e| {
+            tracing::error!("Failed to list workspace folders: {}", e);
+            "Failed to remove trusted folder.".to_string()
+        })?;
+        Ok(workspaces
+            .into_iter()
+            .filter(|(_, folder)| std::path::Path::new(folder).starts_with(&path))
+            .map(|(conversation_id, _)| conversation_id)
+            .collect::<Vec<_>>())
+    })
+    .await?;
+    for conversation_id in &affected {
+        require_idle(
+            &state,
+            conversation_id,
+            "Wait for the replies in this folder to finish before removing it.",
+        )
+        .await?;
+    }
+    with_db(&state, |conn| {
+        if !db::delete_trusted_folder(conn, &path).map_err(|e| {
+            tracing::error!("Failed to delete trusted folder: {}", e);
+            "Failed to remove trusted folder.".to_string()
+        })? {
+            return Err(AppError::NotFound("Trusted folder not found".into()).into());
+        }
+        Ok(())
+    })
+    .await?;
+    // Their current sessions still run in the folder
+    for conversation_id in affected {
+        reset_conversation_session(&state, &conversation_id).await?;
+    }
+    Ok(())
+}
+
+/// Sets the workspace folder of a conversation, or clears it with `None`. The
+/// folder must be trusted. The conversation's session is replaced so the next
+/// message runs in the new folder.
+#[tauri::command]
+pub async fn set_conversation_workspace(
+    state: State<'_, AppState>,
+    conversation_id: String,
+    folder: Option<String>,
+) -> Result<Conversation, String> {
+    let folder = folder
+        .map(|f| crate::workspace::normalize(&f))
+        .transpose()
+        .map_err(|e| -> String { AppError::Validation(e).into() })?
+        .map(|f| f.to_string_lossy().into_owned());
+    require_idle(
+        &state,
+        &conversation_id,
+        "Wait for the current reply to finish before changing the workspace.",
+    )
+    .await?;
+    let convo = with_db(&state, |conn| {
+        if let Some(folder) = &folder {
+            let trusted = db::list_trusted_folders(conn).map_err(|e| {
+                tracing::error!("Failed to list trusted folders: {}", e);
+                "Failed to load trusted folders.".to_string()
+            })?;
+            if !crate::workspace::is_trusted(std::path::Path::new(folder), &trusted) {
+                return Err(AppError::Validation(format!(
+                    "{} is not in a trusted folder. Add it to the trusted folders first.",
+                    folder
+                ))
+                .into());
+            }
+        }
+        if !db::set_workspace_folder(conn, &conversation_id, folder.as_deref()).map_err(|e| {
+            tracing::error!("Failed to set workspace folder: {}", e);
+            "Failed to update conversation.".to_string()
+        })? {
+            return Err(AppError::NotFound("Conversation not found".into()).into());
+        }
+        require_conversation(conn, &conversation_id)
+    })
+    .await?;
+    reset_conversation_session(&state, &conversation_id).await?;
+    tracing::info!(
+        "Conversation {} now works in {}",
+        conversation_id,
+        folder.as_deref().unwrap_or("the sandbox")
+    );
+    Ok(convo)
+}
+
+/// MCP servers, enabled or not. Enabled ones are given to new sessions.
+#[tauri::command]
+pub async fn list_mcp_servers(state: State<'_, AppState>) -> Result<Vec<McpServer>, String> {
+    with_db(&state, |conn| {
+        db::list_mcp_servers(conn).map_err(|e| {
+            tracing::error!("Failed to list MCP servers: {}", e);
+            "Failed to load MCP servers.".to_string()
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn add_mcp_server(
+    state: State<'_, AppState>,
+    server: McpServerInput,
+) -> Result<McpServer, String> {
+    let input = server
+        .validate()
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    let server = McpServer {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: input.name,
+        enabled: input.enabled,
+        transport: input.transport,
+        created_at: now_timestamp(),
+    };
+    with_db(&state, |conn| {
+        require_unique_server_name(conn, &server.name, None)?;
+        db::insert_mcp_server(conn, &server).map_err(|e| {
+            tracing::error!("Failed to save MCP server: {}", e);
+            "Failed to save MCP server.".to_string()
+        })
+    })
+    .await?;
+    tracing::info!("Added MCP server {}", server.name);
+    Ok(server)
+}
+
+/// Changes an MCP server. Sessions that are already open keep the servers
+/// they were created with.
+#[tauri::command]
+pub async fn update_mcp_server(
+    state: State<'_, AppState>,
+    server_id: String,
+    server: McpServerInput,
+) -> Result<McpServer, String> {
+    let input = server
+        .validate()
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    with_db(&state, |conn| {
+        let existing = db::list_mcp_servers(conn)
+            .map_err(|e| {
+                tracing::error!("Failed to list MCP servers: {}", e);
+                "Failed to load MCP servers.".to_string()
+            })?
+            .into_iter()
+            .find(|s| s.id == server_id)
+            .ok_or_else(|| -> String {
+                AppError::NotFound("MCP server not found".into()).into()
+            })?;
+        require_unique_server_name(conn, &input.name, Some(&server_id))?;
+        let server = McpServer {
+            name: input.name,
+            enabled: input.enabled,
+            transport: input.transport,
+            ..existing
+        };
+        db::update_mcp_server(conn, &server).map_err(|e| {
+            tracing::error!("Failed to update MCP server: {}", e);
+            "Failed to save MCP server.".to_string()
+        })?;
+        Ok(server)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_mcp_server(
+    state: State<'_, AppState>,
+    server_id: String,
+) -> Result<(), String> {
+    let deleted = with_db(&state, |conn| {
+        db::delete_mcp_server(conn, &server_id).map_err(|e| {
+            tracing::error!("Failed to delete MCP server: {}", e);
+            "Failed to delete MCP server.".to_string()
+        })
+    })
+    .await?;
+    if !deleted {
+        return Err(AppError::NotFound("MCP server not found".into()).into());
+    }
+    Ok(())
+}
+
+/// Adds the servers from the Copilot CLI's own MCP config. Servers whose name
+/// is already taken are skipped, so importing twice is harmless.
+#[tauri::command]
+pub async fn import_cli_mcp_servers(state: State<'_, AppState>) -> Result<McpImport, String> {
+    let path = crate::mcp::cli_config_path().ok_or_else(|| -> String {
+        AppError::NotFound("Could not find your home folder.".into()).into()
+    })?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::NotFound(format!(
+                "No Copilot CLI MCP config at {}",
+                path.display()
+            ))
+            .into());
+        }
+        Err(e) => {
+            tracing::error!("Failed to read {}: {}", path.display(), e);
+            return Err(
+                AppError::Internal("Failed to read the Copilot CLI MCP config.".into()).into(),
+            );
+        }
+    };
+    let (entries, mut skipped) = crate::mcp::parse_cli_config(&text)
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    let created_at = now_timestamp();
+    let imported = with_db(&state, |conn| {
+        let existing = db::list_mcp_servers(conn).map_err(|e| {
+            tracing::error!("Failed to list MCP servers: {}", e);
+            "Failed to load MCP servers.".to_string()
+        })?;
+        let mut imported = Vec::new();
+        for entry in entries {
+            if existing.iter().any(|s| s.name == entry.name) {
+                skipped.push(entry.name);
+                continue;
+            }
+            let server = McpServer {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: entry.name,
+                enabled: entry.enabled,
+                transport: entry.transport,
+                created_at: created_at.clone(),
+            };
+            db::insert_mcp_server(conn, &server).map_err(|e| {
+                tracing::error!("Failed to save MCP server: {}", e);
+                "Failed to save MCP server.".to_string()
+            })?;
+            imported.push(server);
+        }
+        Ok(imported)
+    })
+    .await?;
+    tracing::info!(
+        "Imported {} MCP server(s) from {}, skipped {}",
+        imported.len(),
+        path.display(),
+        skipped.len()
+    );
+    Ok(McpImport { imported, skipped })
+}
+
+/// Starts or connects to a server and lists the tools it reports, so a
+/// configuration can be checked before it is saved.
+#[tauri::command]
+pub async fn test_mcp_server(transport: McpTransport) -> Result<Vec<McpToolInfo>, String> {
+    let input = McpServerInput {
+        name: "test".to_string(),
+        enabled: true,
+        transport,
+    }
+    .validate()
+    .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    crate::mcp::test_server(&input.transport)
+        .await
+        .map_err(|e| {
+            tracing::warn!("MCP server test failed: {}", e);
+            AppError::Network(e).into()
+        })
+}
+
+fn load_persona(conn: &rusqlite::Connection, id: &str) -> Result<Persona, String> {
+    db::get_persona(conn, id)
+        .map_err(|e| {
+            tracing::error!("Failed to load persona: {}", e);
+            "Failed to load personas.".to_string()
+        })?
+        .ok_or_else(|| AppError::NotFound("Persona not found".into()).into())
+}
+
+/// Checks that the persona name is free and its MCP servers exist.
+fn check_persona(
+    conn: &rusqlite::Connection,
+    input: &PersonaInput,
+    except_id: Option<&str>,
+) -> Result<(), String> {
+    let personas = db::list_personas(conn).map_err(|e| {
+        tracing::error!("Failed to list personas: {}", e);
+        "Failed to load personas.".to_string()
+    })?;
+    if personas
+        .iter()
+        .any(|p| p.name == input.name && Some(p.id.as_str()) != except_id)
+    {
+        return Err(AppError::Validation(format!(
+            "A persona named {} already exists.",
+            input.name
+        ))
+        .into());
+    }
+    if let Some(ids) = &input.mcp_servers {
+        let servers = db::list_mcp_servers(conn).map_err(|e| {
+            tracing::error!("Failed to list MCP servers: {}", e);
+            "Failed to load MCP servers.".to_string()
+        })?;
+        if let Some(unknown) = ids.iter().find(|id| !servers.iter().any(|s| &s.id == *id)) {
+            return Err(AppError::Validation(format!("Unknown MCP server: {}", unknown)).into());
+        }
+    }
+    Ok(())
+}
+
+/// Conversations started with `persona_id`, read before it changes.
+fn persona_conversations(
+    conn: &rusqlite::Connection,
+    persona_id: &str,
+) -> Result<Vec<String>, String> {
+    db::list_persona_conversations(conn, persona_id).map_err(|e| {
+        tracing::error!("Failed to list conversations of persona: {}", e);
+        "Failed to load personas.".to_string()
+    })
+}
+
+/// Conversations of `persona_id`, refused while one of them is streaming a
+/// reply because changing the persona replaces their sessions.
+async fn idle_persona_conversations(
+    state: &AppState,
+    persona_id: &str,
+) -> Result<Vec<String>, String> {
+    let conversations = with_db(state, |conn| persona_conversations(conn, persona_id)).await?;
+    for conversation_id in &conversations {
+        require_idle(
+            state,
+            conversation_id,
+            "Wait for the replies using this persona to finish before changing it.",
+        )
+        .await?;
+    }
+    Ok(conversations)
+}
+
+/// Replaces the sessions of conversations whose persona changed, so the
+/// next message opens one with the new prompt, model, reasoning and
+/// servers instead of resuming a session created with the old ones.
+async fn reset_persona_sessions(
+    state: &AppState,
+    conversations: Vec<String>,
+) -> Result<(), String> {
+    for conversation_id in conversations {
+        reset_conversation_session(state, &conversation_id).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_personas(state: State<'_, AppState>) -> Result<Vec<Persona>, String> {
+    with_db(&state, |conn| {
+        db::list_personas(conn).map_err(|e| {
+            tracing::error!("Failed to list personas: {}", e);
+            "Failed to load personas.".to_string()
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn add_persona(
+    state: State<'_, AppState>,
+    persona: PersonaInput,
+) -> Result<Persona, String> {
+    let input = persona
+        .validate()
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    with_db(&state, |conn| {
+        check_persona(conn, &input, None)?;
+        let now = now_timestamp();
+        let persona = Persona {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: input.name,
+            system_prompt: input.system_prompt,
+            model: input.model,
+            reasoning_effort: input.reasoning_effort,
+            tools: input.tools,
+            mcp_servers: input.mcp_servers,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+        db::insert_persona(conn, &persona).map_err(|e| {
+            tracing::error!("Failed to save persona: {}", e);
+            "Failed to save persona.".to_string()
+        })?;
+        Ok(persona)
+    })
+    .await
+}
+
+/// Changes a persona. The sessions of its conversations are replaced so their
+/// next message uses the new settings, which is refused while one of them is
+/// streaming a reply. Tools already turned on or off in its conversations
+/// stay that way.
+#[tauri::command]
+pub async fn update_persona(
+    state: State<'_, AppState>,
+    persona_id: String,
+    persona: PersonaInput,
+) -> Result<Persona, String> {
+    let input = persona
+        .validate()
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    let conversations = idle_persona_conversations(&state, &persona_id).await?;
+    let persona = with_db(&state, |conn| {
+        let existing = load_persona(conn, &persona_id)?;
+        check_persona(conn, &input, Some(&existing.id))?;
+        let persona = Persona {
+            name: input.name,
+            system_prompt: input.system_prompt,
+            model: input.model,
+            reasoning_effort: input.reasoning_effort,
+            tools: input.tools,
+            mcp_servers: input.mcp_servers,
+            updated_at: now_timestamp(),
+            ..existing
+        };
+        db::update_persona(conn, &persona).map_err(|e| {
+            tracing::error!("Failed to update persona: {}", e);
+            "Failed to save persona.".to_string()
+        })?;
+        Ok(persona)
+    })
+    .await?;
+    reset_persona_sessions(&state, conversations).await?;
+    Ok(persona)
+}
+
+#[tauri::command]
+pub async fn delete_persona(state: State<'_, AppState>, persona_id: String) -> Result<(), String> {
+    let conversations = idle_persona_conversations(&state, &persona_id).await?;
+    let deleted = with_db(&state, |conn| {
+        db::delete_persona(conn, &persona_id).map_err(|e| {
+            tracing::error!("Failed to delete persona: {}", e);
+            "Failed to delete persona.".to_string()
+        })
+    })
+    .await?;
+    if !deleted {
+        return Err(AppError::NotFound("Persona not found".into()).into());
+    }
+    reset_persona_sessions(&state, conversations).await
+}
+
+fn require_unique_template_name(
+    conn: &rusqlite::Connection,
+    name: &str,
+    except_id: Option<&str>,
+) -> Result<(), String> {
+    let templates = db::list_prompt_templates(conn).map_err(|e| {
+        tracing::error!("Failed to list prompt templates: {}", e);
+        "Failed to load prompt templates.".to_string()
+    })?;
+    if templates
+        .iter()
+        .any(|t| t.name == name && Some(t.id.as_str()) != except_id)
+    {
+        return Err(
+            AppError::Validation(format!("A template named /{} already exists.", name)).into(),
+        );
+    }
+    Ok(())
+}
+
+fn load_template(conn: &rusqlite::Connection, key: &str) -> Result<PromptTemplate, String> {
+    db::get_prompt_template(conn, key)
+        .map_err(|e| {
+            tracing::error!("Failed to load prompt template: {}", e);
+            "Failed to load prompt templates.".to_string()
+        })?
+        .ok_or_else(|| AppError::NotFound("Prompt template not found".into()).into())
+}
+
+#[tauri::command]
+pub async fn list_prompt_templates(
+    state: State<'_, AppState>,
+) -> Result<Vec<PromptTemplate>, String> {
+    with_db(&state, |conn| {
+        db::list_prompt_templates(conn).map_err(|e| {
+            tracing::error!("Failed to list prompt templates: {}", e);
+            "Failed to load prompt templates.".to_string()
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn add_prompt_template(
+    state: State<'_, AppState>,
+    template: PromptTemplateInput,
+) -> Result<PromptTemplate, String> {
+    let input = template
+        .validate()
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    let now = now_timestamp();
+    let template = PromptTemplate {
+        id: uuid::Uuid::new_v4().to_string(),
+        variables: crate::templates::variables(&input.body),
+        name: input.name,
+        description: input.description,
+        body: input.body,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    with_db(&state, |conn| {
+        require_unique_template_name(conn, &template.name, None)?;
+        db::insert_prompt_template(conn, &template).map_err(|e| {
+            tracing::error!("Failed to save prompt template: {}", e);
+            "Failed to save prompt template.".to_string()
+        })
+    })
+    .await?;
+    Ok(template)
+}
+
+#[tauri::command]
+pub async fn update_prompt_template(
+    state: State<'_, AppState>,
+    template_id: String,
+    template: PromptTemplateInput,
+) -> Result<PromptTemplate, String> {
+    let input = template
+        .validate()
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    with_db(&state, |conn| {
+        let existing = load_template(conn, &template_id)?;
+        require_unique_template_name(conn, &input.name, Some(&existing.id))?;
+        let template = PromptTemplate {
+            variables: crate::templates::variables(&input.body),
+            name: input.name,
+            description: input.description,
+            body: input.body,
+            updated_at: now_timestamp(),
+            ..existing
+        };
+        db::update_prompt_template(conn, &template).map_err(|e| {
+            tracing::error!("Failed to update prompt template: {}", e);
+            "Failed to save prompt template.".to_string()
+        })?;
+        Ok(template)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn destroy_session(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
-    let mut sessions = state.sessions.write().await;
-    if let Some(info) = sessions.remove(&session_id) {
-        // Cancel the event-processing task so it stops emitting events immediately
-        info.cancel_token.cancel();
-        tracing::info!("Session {} destroyed and event loop cancelled", session_id);
+pub async fn delete_prompt_template(
+    state: State<'_, AppState>,
+    template_id: String,
+) -> Result<(), String> {
+    let deleted = with_db(&state, |conn| {
+        db::delete_prompt_template(conn, &template_id).map_err(|e| {
+            tracing::error!("Failed to delete prompt template: {}", e);
+            "Failed to delete prompt template.".to_string()
+        })
+    })
+    .await?;
+    if !deleted {
+        return Err(AppError::NotFound("Prompt template not found".into()).into());
     }
     Ok(())
 }
 
-#[derive(serde::Serialize, Clone, Debug)]
-#[allow(dead_code)]
-pub struct MessageDeltaEvent {
-    pub session_id: String,
-    pub delta: String,
-}
-
-#[derive(serde::Serialize, Clone, Debug)]
-#[allow(dead_code)]
-pub struct MessageCompleteEvent {
-    pub session_id: String,
-    pub content: String,
-}
-
-#[derive(serde::Serialize, Clone, Debug)]
-#[allow(dead_code)]
-pub struct SessionErrorEvent {
-    pub session_id: String,
-    pub message: String,
+/// Fills in template `template` (an id or a slash-command name) with
+/// `values`. When a conversation, session and channel are given the result
+/// is also sent like a typed message.
+#[tauri::command]
+pub async fn render_template(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    template: String,
+    values: std::collections::HashMap<String, String>,
+    conversation_id: Option<String>,
+    session_id: Option<String>,
+    on_event: Option<Channel<StreamEvent>>,
+) -> Result<RenderedTemplate, String> {
+    let key = template.trim().trim_start_matches('/').to_string();
+    let template = with_db(&state, |conn| load_template(conn, &key)).await?;
+    let content = crate::templates::render(&template.body, &values)
+        .map_err(|e| -> String { AppError::Validation(e).into() })?;
+    let receipt = match (conversation_id, session_id, on_event) {
+        (Some(conversation_id), Some(session_id), Some(on_event)) => Some(
+            send_message(
+                app,
+                state,
+                conversation_id,
+                session_id,
+                content.clone(),
+                None,
+                on_event,
+            )
+            .await?,
+        ),
+        (None, None, None) => None,
+        _ => {
+            return Err(AppError::Validation(
+                "Sending a template needs a conversation, a session and a channel.".into(),
+            )
+            .into())
+        }
+    };
+    Ok(RenderedTemplate { content, receipt })
 }
 
-#[derive(serde::Serialize, Clone, Debug)]
-#[allow(dead_code)]
-pub struct SessionIdleEvent {
-    pub session_id: String,
+/// Expands `/name text` into the named template, with the text as the value
+/// of its first variable. Returns `None` when `input` is not a known slash
+/// command so it can be sent as typed.
+#[tauri::command]
+pub async fn expand_slash_command(
+    state: State<'_, AppState>,
+    input: String,
+) -> Result<Option<String>, String> {
+    let Some((name, text)) = crate::templates::parse_slash_command(&input) else {
+        return Ok(None);
+    };
+    let template = with_db(&state, |conn| {
+        db::get_prompt_template(conn, &name).map_err(|e| {
+            tracing::error!("Failed to load prompt template: {}", e);
+            "Failed to load prompt templates.".to_string()
+        })
+    })
+    .await?;
+    let Some(template) = template.filter(|t| t.name == name) else {
+        return Ok(None);
+    };
+    let values = crate::templates::slash_values(&template, text);
+    crate::templates::render(&template.body, &values)
+        .map(Some)
+        .map_err(|e| AppError::Validation(format!("/{}: {}", template.name, e)).into())
 }
 
-#[derive(serde::Serialize, Clone, Debug)]
-#[allow(dead_code)]
-pub struct UsageEvent {
-    pub session_id: String,
-    pub input_tokens: Option<f64>,
-    pub output_tokens: Option<f64>,
+/// Generates a new reply to the last user message on the active branch. The
+/// previous reply is kept as a sibling of the new one.
+#[tauri::command]
+pub async fn regenerate_message(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    conversation_id: String,
+    model: Option<String>,
+    on_event: Channel<StreamEvent>,
+) -> Result<SessionStart, String> {
+    require_idle(
+        &state,
+        &conversation_id,
+        "Wait for the current reply to finish before regenerating.",
+    )
+    .await?;
+    let (prompt, prefix, previous_leaf, budget, convo_model) = with_db(&state, |conn| {
+        let convo = require_conversation(conn, &conversation_id)?;
+        let mut path = db::get_conversation_history(conn, &conversation_id).map_err(|e| {
+            tracing::error!("Failed to get conversation history: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
+        let position = path
+            .iter()
+            .rposition(|m| m.role == "user")
+            .ok_or_else(|| -> String {
+                AppError::Validation("There is no message to regenerate.".into()).into()
+            })?;
+        path.truncate(position + 1);
+        let mut prompt = path.remove(position);
+        db::attach_attachments(conn, &conversation_id, std::slice::from_mut(&mut prompt)).map_err(
+            |e| {
+                tracing::error!("Failed to get attachments: {}", e);
+                "Failed to load conversation messages.".to_string()
+            },
+        )?;
+        let previous_leaf = db::get_active_leaf(conn, &conversation_id).map_err(|e| {
+            tracing::error!("Failed to get active message: {}", e);
+            "Failed to load conversation.".to_string()
+        })?;
+        Ok((
+            prompt,
+            path,
+            previous_leaf,
+            context_budget(conn)?,
+            convo.model,
+        ))
+    })
+    .await?;
+
+    let (bound_model, system_prompt) = bound_session_config(&state, &conversation_id).await;
+    let model = model.or(bound_model).or(convo_model);
+    let start = open_session(
+        &state,
+        &conversation_id,
+        model,
+        system_prompt,
+        &prefix,
+        budget,
+    )
+    .await?;
+
+    // The new reply hangs off the prompt, so the old one leaves the active branch.
+    set_active_leaf(&state, &conversation_id, &prompt.id).await?;
+    if let Err(e) = start_turn(
+        &app,
+        &state,
+        &start.session_id,
+        &prompt,
+        StorePrompt::Existing,
+        on_event,
+    )
+    .await
+    {
+        if let Some(leaf) = previous_leaf {
+            let _ = set_active_leaf(&state, &conversation_id, &leaf).await;
+        }
+        return Err(e);
+    }
+    Ok(start)
 }
 
+/// Sends an edited copy of an earlier user message as a new sibling of it and
+/// streams a reply on the new branch. The original branch is left untouched.
 #[tauri::command]
-pub async fn send_message(
+pub async fn edit_message(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
-    session_id: String,
+    conversation_id: String,
+    message_id: String,
     content: String,
-) -> Result<(), String> {
+    model: Option<String>,
+    on_event: Channel<StreamEvent>,
+) -> Result<(SessionStart, Message), String> {
     if content.len() > MAX_MESSAGE_SIZE {
         return Err(AppError::Validation(
             "Message is too large. Please shorten your message.".into(),
@@ -400,178 +3160,138 @@ pub async fn send_message(
     if content.trim().is_empty() {
         return Err(AppError::Validation("Message cannot be empty.".into()).into());
     }
-
-    let session = {
-        let sessions = state.sessions.read().await;
-        let session_info = sessions
-            .get(&session_id)
-            .ok_or::<String>(AppError::NotFound("Session not found".into()).into())?;
-        (
-            session_info.session.clone(),
-            session_info.cancel_token.clone(),
-        )
+    require_idle(
+        &state,
+        &conversation_id,
+        "Wait for the current reply to finish before editing a message.",
+    )
+    .await?;
+
+    let (original, prefix, budget, convo_model) = with_db(&state, |conn| {
+        let convo = require_conversation(conn, &conversation_id)?;
+        let mut original = db::get_message(conn, &message_id)
+            .map_err(|e| {
+                tracing::error!("Failed to get message: {}", e);
+                "Failed to load message.".to_string()
+            })?
+            .filter(|m| m.conversation_id == conversation_id && m.role == "user")
+            .ok_or_else(|| -> String { AppError::NotFound("Message not found".into()).into() })?;
+        // The edited copy keeps the original's attachments
+        db::attach_attachments(conn, &conversation_id, std::slice::from_mut(&mut original))
+            .map_err(|e| {
+                tracing::error!("Failed to get attachments: {}", e);
+                "Failed to load message.".to_string()
+            })?;
+        let prefix = match original.parent_id {
+            Some(ref parent_id) => db::get_path_to_message(conn, &conversation_id, parent_id)
+                .map_err(|e| {
+                    tracing::error!("Failed to get conversation history: {}", e);
+                    "Failed to load conversation messages.".to_string()
+                })?,
+            None => Vec::new(),
+        };
+        Ok((original, prefix, context_budget(conn)?, convo.model))
+    })
+    .await?;
+
+    let edited = Message {
+        id: uuid::Uuid::new_v4().to_string(),
+        conversation_id: conversation_id.clone(),
+        role: "user".to_string(),
+        content,
+        created_at: now_timestamp(),
+        interrupted: false,
+        parent_id: original.parent_id,
+        sibling_index: 0,
+        sibling_count: 0,
+        tool_calls: Vec::new(),
+        attachments: original.attachments,
     };
 
-    let mut events = session.0.subscribe();
-
-    session.0.send(&*content).await.map_err(|e| -> String {
-        tracing::error!("Failed to send message (session {}): {}", session_id, e);
-        AppError::Network("Failed to send message. Please try again.".into()).into()
-    })?;
+    let (bound_model, system_prompt) = bound_session_config(&state, &conversation_id).await;
+    let model = model.or(bound_model).or(convo_model);
+    let start = open_session(
+        &state,
+        &conversation_id,
+        model,
+        system_prompt,
+        &prefix,
+        budget,
+    )
+    .await?;
+    let stored = start_turn(
+        &app,
+        &state,
+        &start.session_id,
+        &edited,
+        StorePrompt::AtParent,
+        on_event,
+    )
+    .await?;
+    Ok((start, stored.unwrap_or(edited)))
+}
 
-    let sid = session_id;
-    let app_handle = app.clone();
-    let verbose = crate::is_verbose();
-    let timeout = event_timeout();
-    let cancel = session.1;
-    tokio::spawn(async move {
-        if verbose {
-            tracing::debug!(
-                "[VERBOSE] Event loop started for session {} (event timeout: {:?})",
-                sid,
-                timeout
-            );
-        }
-        let mut accumulated_content = String::with_capacity(4096);
-        let mut got_any_delta = false;
-        loop {
-            if cancel.is_cancelled() {
-                if verbose {
-                    tracing::debug!("[VERBOSE] Event loop cancelled for session {}", sid);
-                }
-                break;
-            }
-            tokio::select! {
-                _ = cancel.cancelled() => {
-                    if verbose {
-                        tracing::debug!("[VERBOSE] Event loop cancelled (select) for session {}", sid);
-                    }
-                    break;
-                }
-                result = tokio::time::timeout(timeout, events.recv()) => {
-                    match result {
-                        Ok(Ok(event)) => {
-                            if cancel.is_cancelled() { break; }
-                            match &event.data {
-                                copilot_sdk::SessionEventData::AssistantReasoningDelta(delta) => {
-                                    if verbose {
-                                        tracing::debug!("[VERBOSE] Thinking delta (session {}): {} bytes", sid, delta.delta_content.len());
-                                    }
-                                    let _ = app_handle.emit("copilot:thinking-delta", serde_json::json!({
-                                        "session_id": &sid,
-                                        "delta": &delta.delta_content,
-                                    }));
-                                }
-                                copilot_sdk::SessionEventData::AssistantReasoning(reasoning) => {
-                                    if verbose {
-                                        tracing::debug!("[VERBOSE] Thinking complete (session {}): {} chars", sid, reasoning.content.len());
-                                    }
-                                    let _ = app_handle.emit("copilot:thinking-complete", serde_json::json!({
-                                        "session_id": &sid,
-                                        "content": &reasoning.content,
-                                    }));
-                                }
-                                copilot_sdk::SessionEventData::AssistantMessageDelta(delta) => {
-                                    got_any_delta = true;
-                                    accumulated_content.push_str(&delta.delta_content);
-                                    if verbose {
-                                        tracing::debug!("[VERBOSE] Delta (session {}): {} bytes", sid, delta.delta_content.len());
-                                    }
-                                    let _ = app_handle.emit("copilot:message-delta", serde_json::json!({
-                                        "session_id": &sid,
-                                        "delta": &delta.delta_content,
-                                    }));
-                                }
-                                copilot_sdk::SessionEventData::AssistantMessage(msg) => {
-                                    if verbose {
-                                        tracing::debug!("[VERBOSE] Complete message (session {}): {} chars", sid, msg.content.len());
-                                    }
-                                    let _ = app_handle.emit("copilot:message-complete", serde_json::json!({
-                                        "session_id": &sid,
-                                        "content": &msg.content,
-                                    }));
-                                }
-                                copilot_sdk::SessionEventData::SessionIdle(_) => {
-                                    if verbose {
-                                        tracing::debug!("[VERBOSE] Session idle: {}", sid);
-                                    }
-                                    let _ = app_handle.emit("copilot:session-idle", serde_json::json!({
-                                        "session_id": &sid,
-                                    }));
-                                    break;
-                                }
-                                copilot_sdk::SessionEventData::SessionError(err) => {
-                                    tracing::error!("Session error ({}): {}", sid, err.message);
-                                    let _ = app_handle.emit("copilot:session-error", serde_json::json!({
-                                        "session_id": &sid,
-                                        "message": &err.message,
-                                    }));
-                                    break;
-                                }
-                                copilot_sdk::SessionEventData::AssistantUsage(usage) => {
-                                    if verbose {
-                                        tracing::debug!("[VERBOSE] Usage (session {}): in={:?} out={:?}", sid, usage.input_tokens, usage.output_tokens);
-                                    }
-                                    let _ = app_handle.emit("copilot:usage", serde_json::json!({
-                                        "session_id": &sid,
-                                        "input_tokens": usage.input_tokens,
-                                        "output_tokens": usage.output_tokens,
-                                    }));
-                                }
-                                _ => {
-                                    if verbose {
-                                        tracing::debug!("[VERBOSE] Unhandled event for session {}", sid);
-                                    }
-                                }
-                            }
-                        }
-                        Ok(Err(e)) => {
-                            if verbose {
-                                tracing::debug!("[VERBOSE] Event channel closed for session {}: {}", sid, e);
-                            }
-                            if got_any_delta {
-                                let _ = app_handle.emit("copilot:message-complete", serde_json::json!({
-                                    "session_id": &sid,
-                                    "content": &accumulated_content,
-                                }));
-                            }
-                            let _ = app_handle.emit("copilot:session-idle", serde_json::json!({
-                                "session_id": &sid,
-                            }));
-                            break;
-                        }
-                        Err(_) => {
-                            tracing::warn!("Event timeout ({:?}) for session {} — flushing partial response", timeout, sid);
-                            if got_any_delta {
-                                let _ = app_handle.emit("copilot:message-complete", serde_json::json!({
-                                    "session_id": &sid,
-                                    "content": &accumulated_content,
-                                }));
-                                let _ = app_handle.emit("copilot:session-error", serde_json::json!({
-                                    "session_id": &sid,
-                                    "message": format!("Response timed out after {:?} — partial content shown above.", timeout),
-                                }));
-                            } else {
-                                let _ = app_handle.emit("copilot:session-error", serde_json::json!({
-                                    "session_id": &sid,
-                                    "message": format!("No response received within {:?}. The model may be processing a complex request — try again or increase COPILOT_EVENT_TIMEOUT_SECS.", timeout),
-                                }));
-                            }
-                            let _ = app_handle.emit("copilot:session-idle", serde_json::json!({
-                                "session_id": &sid,
-                            }));
-                            break;
-                        }
-                    }
-                }
-            }
-        }
-        if verbose {
-            tracing::debug!("[VERBOSE] Event loop ended for session {}", sid);
+/// Shows sibling `sibling_index` of `message_id` and returns the messages on
+/// the resulting active branch. The conversation's session is released so the
+/// next prompt is answered with the newly selected history.
+#[tauri::command]
+pub async fn switch_branch(
+    state: State<'_, AppState>,
+    conversation_id: String,
+    message_id: String,
+    sibling_index: u32,
+) -> Result<Vec<Message>, String> {
+    require_idle(
+        &state,
+        &conversation_id,
+        "Wait for the current reply to finish before switching branches.",
+    )
+    .await?;
+    let messages = with_db(&state, |conn| {
+        let found =
+            db::select_branch(conn, &conversation_id, &message_id, sibling_index).map_err(|e| {
+                tracing::error!("Failed to switch branch: {}", e);
+                "Failed to switch branch.".to_string()
+            })?;
+        if !found {
+            return Err(AppError::NotFound("Message not found".into()).into());
         }
-    });
+        // The stored session saw the old branch, so it must not be resumed
+        db::set_sdk_session(conn, &conversation_id, None).map_err(|e| {
+            tracing::error!("Failed to forget stored session: {}", e);
+            "Failed to switch branch.".to_string()
+        })?;
+        let mut messages = db::get_conversation_history(conn, &conversation_id).map_err(|e| {
+            tracing::error!("Failed to get conversation history: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
+        db::attach_tool_calls(conn, &conversation_id, &mut messages).map_err(|e| {
+            tracing::error!("Failed to get tool calls: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
+        db::attach_attachments(conn, &conversation_id, &mut messages).map_err(|e| {
+            tracing::error!("Failed to get attachments: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
+        Ok(messages)
+    })
+    .await?;
+    release_conversation_session(&state, &conversation_id).await;
+    Ok(messages)
+}
 
-    Ok(())
+async fn set_active_leaf(
+    state: &AppState,
+    conversation_id: &str,
+    message_id: &str,
+) -> Result<(), String> {
+    with_db(state, |conn| {
+        db::set_active_leaf(conn, conversation_id, message_id).map_err(|e| {
+            tracing::error!("Failed to set active message: {}", e);
+            "Failed to update conversation.".to_string()
+        })
+    })
+    .await
 }
 
 #[tauri::command]
@@ -603,11 +3323,19 @@ pub async fn get_conversation(
                 "Failed to load conversation.".to_string()
             })?
             .ok_or("Conversation not found")?;
-        let msgs =
-            db::get_conversation_messages(conn, &conversation_id, limit, offset).map_err(|e| {
+        let mut msgs = db::get_conversation_messages(conn, &conversation_id, limit, offset)
+            .map_err(|e| {
                 tracing::error!("Failed to get conversation messages: {}", e);
                 "Failed to load conversation messages.".to_string()
             })?;
+        db::attach_tool_calls(conn, &conversation_id, &mut msgs).map_err(|e| {
+            tracing::error!("Failed to get tool calls: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
+        db::attach_attachments(conn, &conversation_id, &mut msgs).map_err(|e| {
+            tracing::error!("Failed to get attachments: {}", e);
+            "Failed to load conversation messages.".to_string()
+        })?;
 
         Ok((convo, msgs))
     })
@@ -619,6 +3347,7 @@ pub async fn create_conversation(
     state: State<'_, AppState>,
     title: Option<String>,
     model: Option<String>,
+    persona_id: Option<String>,
 ) -> Result<Conversation, String> {
     let id = uuid::Uuid::new_v4().to_string();
     let title = title
@@ -632,32 +3361,100 @@ pub async fn create_conversation(
     }
 
     with_db(&state, |conn| {
-        db::create_conversation(conn, &id, &title, model.as_deref()).map_err(|e| {
+        let persona = persona_id
+            .as_deref()
+            .map(|persona_id| load_persona(conn, persona_id))
+            .transpose()?;
+        let model = model
+            .as_deref()
+            .or_else(|| persona.as_ref().and_then(|p| p.model.as_deref()));
+        let failed = |e: rusqlite::Error| {
             tracing::error!("Failed to create conversation: {}", e);
             "Failed to create conversation.".to_string()
+        };
+        let convo = db::create_conversation(conn, &id, &title, model).map_err(failed)?;
+        let Some(persona) = persona else {
+            return Ok(convo);
+        };
+        db::set_conversation_persona(conn, &id, &persona).map_err(failed)?;
+        Ok(Conversation {
+            persona_id: Some(persona.id),
+            ..convo
         })
     })
     .await
 }
 
+/// Copies a conversation up to and including `message_id` into a new
+/// conversation and opens a session for it seeded with that prefix.
+#[tauri::command]
+pub async fn fork_conversation(
+    state: State<'_, AppState>,
+    conversation_id: String,
+    message_id: String,
+) -> Result<ForkedConversation, String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let (conversation, messages, budget) = with_db(&state, |conn| {
+        let (conversation, messages) =
+            db::fork_conversation(conn, &conversation_id, &message_id, &id)
+                .map_err(|e| {
+                    tracing::error!("Failed to fork conversation: {}", e);
+                    "Failed to fork conversation.".to_string()
+                })?
+                .ok_or_else(|| -> String {
+                    AppError::NotFound("Message not found".into()).into()
+                })?;
+        Ok((conversation, messages, context_budget(conn)?))
+    })
+    .await?;
+
+    let (bound_model, system_prompt) = bound_session_config(&state, &conversation_id).await;
+    let session = match open_session(
+        &state,
+        &conversation.id,
+        bound_model.or_else(|| conversation.model.clone()),
+        system_prompt,
+        &messages,
+        budget,
+    )
+    .await
+    {
+        Ok(start) => Some(start),
+        Err(e) => {
+            tracing::warn!("Forked conversation {} without a session: {}", id, e);
+            None
+        }
+    };
+
+    Ok(ForkedConversation {
+        conversation,
+        messages,
+        session,
+    })
+}
+
 #[tauri::command]
 pub async fn delete_conversation(
     state: State<'_, AppState>,
     conversation_id: String,
 ) -> Result<(), String> {
+    release_conversation_session(&state, &conversation_id).await;
+
     with_db(&state, |conn| {
         db::delete_conversation(conn, &conversation_id).map_err(|e| {
             tracing::error!("Failed to delete conversation: {}", e);
             "Failed to delete conversation.".to_string()
         })
     })
-    .await
+    .await?;
+    release_sandbox(&state, &conversation_id).await;
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn save_message(state: State<'_, AppState>, message: Message) -> Result<(), String> {
     with_db(&state, |conn| {
-        db::save_message(conn, &message).map_err(|e| {
+        db::save_message(conn, &message).map(|_| ()).map_err(|e| {
             tracing::error!("Failed to save message: {}", e);
             "Failed to save message.".to_string()
         })
@@ -682,11 +3479,48 @@ pub async fn get_settings(state: State<'_, AppState>) -> Result<Settings, String
             tracing::error!("Failed to get setting 'system_prompt': {}", e);
             "Failed to load settings.".to_string()
         })?;
+        let context_budget_chars = crate::db::get_setting(conn, "context_budget_chars")
+            .map_err(|e| {
+                tracing::error!("Failed to get setting 'context_budget_chars': {}", e);
+                "Failed to load settings.".to_string()
+            })?
+            .and_then(|v| v.parse().ok());
+        let session_idle_ttl_secs = crate::db::get_setting(conn, "session_idle_ttl_secs")
+            .map_err(|e| {
+                tracing::error!("Failed to get setting 'session_idle_ttl_secs': {}", e);
+                "Failed to load settings.".to_string()
+            })?
+            .and_then(|v| v.parse().ok());
+        let max_sessions = crate::db::get_setting(conn, "max_sessions")
+            .map_err(|e| {
+                tracing::error!("Failed to get setting 'max_sessions': {}", e);
+                "Failed to load settings.".to_string()
+            })?
+            .and_then(|v| v.parse().ok());
+        let send_retry_limit = crate::db::get_setting(conn, "send_retry_limit")
+            .map_err(|e| {
+                tracing::error!("Failed to get setting 'send_retry_limit': {}", e);
+                "Failed to load settings.".to_string()
+            })?
+            .and_then(|v| v.parse().ok());
+        let response_deadlines = Some(response_deadlines(conn)?);
+        let sandbox_retention_hours = crate::db::get_setting(conn, "sandbox_retention_hours")
+            .map_err(|e| {
+                tracing::error!("Failed to get setting 'sandbox_retention_hours': {}", e);
+                "Failed to load settings.".to_string()
+            })?
+            .and_then(|v| v.parse().ok());
 
         Ok(Settings {
             theme,
             default_model,
             system_prompt,
+            context_budget_chars,
+            session_idle_ttl_secs,
+            max_sessions,
+            send_retry_limit,
+            response_deadlines,
+            sandbox_retention_hours,
         })
     })
     .await
@@ -707,6 +3541,35 @@ pub async fn update_settings(state: State<'_, AppState>, settings: Settings) ->
             return Err("System prompt value is too large.".to_string());
         }
     }
+    if let Some(budget) = settings.context_budget_chars {
+        if budget as usize > MAX_MESSAGE_SIZE {
+            return Err("Context budget is too large.".to_string());
+        }
+    }
+    if settings.max_sessions == Some(0) {
+        return Err("Session limit must be at least 1.".to_string());
+    }
+    if settings
+        .send_retry_limit
+        .is_some_and(|n| n > MAX_SEND_RETRY_LIMIT)
+    {
+        return Err(format!(
+            "Retry limit can be at most {}.",
+            MAX_SEND_RETRY_LIMIT
+        ));
+    }
+    let deadlines = settings
+        .response_deadlines
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    if deadlines
+        .as_ref()
+        .is_some_and(|json| json.len() > MAX_SETTING_VALUE_SIZE)
+    {
+        return Err("Response time limits are too large.".to_string());
+    }
 
     with_db(&state, |conn| {
         crate::db::set_setting(conn, "theme", &settings.theme).map_err(|e| {
@@ -725,6 +3588,48 @@ pub async fn update_settings(state: State<'_, AppState>, settings: Settings) ->
                 "Failed to save settings.".to_string()
             })?;
         }
+        if let Some(budget) = settings.context_budget_chars {
+            crate::db::set_setting(conn, "context_budget_chars", &budget.to_string()).map_err(
+                |e| {
+                    tracing::error!("Failed to set setting 'context_budget_chars': {}", e);
+                    "Failed to save settings.".to_string()
+                },
+            )?;
+        }
+        if let Some(ttl) = settings.session_idle_ttl_secs {
+            crate::db::set_setting(conn, "session_idle_ttl_secs", &ttl.to_string()).map_err(
+                |e| {
+                    tracing::error!("Failed to set setting 'session_idle_ttl_secs': {}", e);
+                    "Failed to save settings.".to_string()
+                },
+            )?;
+        }
+        if let Some(max) = settings.max_sessions {
+            crate::db::set_setting(conn, "max_sessions", &max.to_string()).map_err(|e| {
+                tracing::error!("Failed to set setting 'max_sessions': {}", e);
+                "Failed to save settings.".to_string()
+            })?;
+        }
+        if let Some(limit) = settings.send_retry_limit {
+            crate::db::set_setting(conn, "send_retry_limit", &limit.to_string()).map_err(|e| {
+                tracing::error!("Failed to set setting 'send_retry_limit': {}", e);
+                "Failed to save settings.".to_string()
+            })?;
+        }
+        if let Some(ref json) = deadlines {
+            crate::db::set_setting(conn, "response_deadlines", json).map_err(|e| {
+                tracing::error!("Failed to set setting 'response_deadlines': {}", e);
+                "Failed to save settings.".to_string()
+            })?;
+        }
+        if let Some(hours) = settings.sandbox_retention_hours {
+            crate::db::set_setting(conn, "sandbox_retention_hours", &hours.to_string()).map_err(
+                |e| {
+                    tracing::error!("Failed to set setting 'sandbox_retention_hours': {}", e);
+                    "Failed to save settings.".to_string()
+                },
+            )?;
+        }
 
         Ok(())
     })
@@ -788,6 +3693,12 @@ mod tests {
             theme: "dark".to_string(),
             default_model: Some("gpt-4o".to_string()),
             system_prompt: Some("Be helpful".to_string()),
+            context_budget_chars: Some(10_000),
+            session_idle_ttl_secs: Some(600),
+            max_sessions: Some(4),
+            send_retry_limit: Some(2),
+            response_deadlines: Some(DeadlineSettings::default()),
+            sandbox_retention_hours: Some(0),
         };
         let json = serde_json::to_string(&settings).unwrap();
         assert!(json.contains("dark"));
@@ -816,6 +3727,8 @@ mod tests {
             id: "gpt-4o".to_string(),
             name: "GPT-4o".to_string(),
             provider: Some("OpenAI".to_string()),
+            supports_vision: true,
+            context_window_tokens: Some(128_000),
         };
         let json = serde_json::to_string(&model).unwrap();
         assert!(json.contains("gpt-4o"));
@@ -833,6 +3746,10 @@ mod tests {
             model: Some("gpt-4o".to_string()),
             created_at: "2026-01-01T00:00:00Z".to_string(),
             updated_at: "2026-01-01T00:00:00Z".to_string(),
+            forked_from_conversation_id: None,
+            forked_from_message_id: None,
+            workspace_folder: None,
+            persona_id: None,
         };
         let json = serde_json::to_string(&convo).unwrap();
         let parsed: Conversation = serde_json::from_str(&json).unwrap();
@@ -848,6 +3765,12 @@ mod tests {
             role: "user".to_string(),
             content: "Hello world".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         let json = serde_json::to_string(&msg).unwrap();
         let parsed: Message = serde_json::from_str(&json).unwrap();
@@ -856,41 +3779,92 @@ mod tests {
     }
 
     #[test]
-    fn test_event_payloads_serialize() {
-        let delta = MessageDeltaEvent {
-            session_id: "s1".to_string(),
+    fn test_message_interrupted_defaults_to_false() {
+        // Messages sent by older frontends omit the flag
+        let json = r#"{"id":"m-1","conversation_id":"c-1","role":"assistant","content":"Hi","created_at":"2026-01-01T00:00:00Z"}"#;
+        let parsed: Message = serde_json::from_str(json).unwrap();
+        assert!(!parsed.interrupted);
+    }
+
+    #[test]
+    fn test_stream_event_payloads_serialize() {
+        let delta = StreamEvent::MessageDelta {
+            seq: 3,
             delta: "Hello ".to_string(),
         };
-        let json = serde_json::to_string(&delta).unwrap();
-        assert!(json.contains("Hello "));
+        let json = serde_json::to_value(&delta).unwrap();
+        assert_eq!(json["type"], "message_delta");
+        assert_eq!(json["seq"], 3);
+        assert_eq!(json["delta"], "Hello ");
 
-        let complete = MessageCompleteEvent {
-            session_id: "s1".to_string(),
-            content: "Hello world".to_string(),
+        let error = StreamEvent::Error {
+            seq: 0,
+            message: "Connection failed".to_string(),
         };
-        let json = serde_json::to_string(&complete).unwrap();
-        assert!(json.contains("Hello world"));
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["message"], "Connection failed");
 
-        let error = SessionErrorEvent {
-            session_id: "s1".to_string(),
-            message: "Connection failed".to_string(),
+        let idle = serde_json::to_value(StreamEvent::Idle { seq: 7 }).unwrap();
+        assert_eq!(idle, serde_json::json!({"type": "idle", "seq": 7}));
+
+        let aborted = StreamEvent::Aborted {
+            seq: 2,
+            content: "Partial".to_string(),
+            message_id: Some("m-1".to_string()),
         };
-        let json = serde_json::to_string(&error).unwrap();
-        assert!(json.contains("Connection failed"));
+        let json = serde_json::to_value(&aborted).unwrap();
+        assert_eq!(json["type"], "aborted");
+        assert_eq!(json["message_id"], "m-1");
+
+        let tool = StreamEvent::ToolProgress {
+            seq: 4,
+            tool_call_id: "t1".to_string(),
+            message: "Compiling".to_string(),
+        };
+        let json = serde_json::to_value(&tool).unwrap();
+        assert_eq!(json["type"], "tool_progress");
+        assert_eq!(json["tool_call_id"], "t1");
+    }
 
-        let idle = SessionIdleEvent {
+    #[test]
+    fn test_session_start_serialize() {
+        let start = SessionStart {
             session_id: "s1".to_string(),
+            reused: false,
+            resumed: false,
+            carried_over: vec!["m1".to_string(), "m2".to_string()],
+            omitted: 3,
         };
-        assert!(serde_json::to_string(&idle).is_ok());
+        let json = serde_json::to_string(&start).unwrap();
+        let parsed: SessionStart = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.carried_over, vec!["m1", "m2"]);
+        assert_eq!(parsed.omitted, 3);
+        assert!(!parsed.reused);
+        assert!(!parsed.resumed);
+    }
 
-        let usage = UsageEvent {
-            session_id: "s1".to_string(),
-            input_tokens: Some(100.0),
-            output_tokens: Some(50.0),
+    #[test]
+    fn test_send_receipt_serialize_queued() {
+        let receipt = SendReceipt {
+            message: Message {
+                id: "m1".to_string(),
+                conversation_id: "c1".to_string(),
+                role: "user".to_string(),
+                content: "Next".to_string(),
+                created_at: "2026-01-01T00:00:00Z".to_string(),
+                interrupted: false,
+                parent_id: None,
+                sibling_index: 0,
+                sibling_count: 0,
+                tool_calls: Vec::new(),
+                attachments: Vec::new(),
+            },
+            queue_position: Some(2),
         };
-        let json = serde_json::to_string(&usage).unwrap();
-        assert!(json.contains("100"));
-        assert!(json.contains("50"));
+        let json: serde_json::Value = serde_json::to_value(&receipt).unwrap();
+        assert_eq!(json["queue_position"], 2);
+        assert_eq!(json["message"]["id"], "m1");
     }
 
     #[test]
@@ -899,12 +3873,20 @@ mod tests {
             theme: "light".to_string(),
             default_model: None,
             system_prompt: None,
+            context_budget_chars: None,
+            session_idle_ttl_secs: None,
+            max_sessions: None,
+            send_retry_limit: None,
+            response_deadlines: None,
+            sandbox_retention_hours: None,
         };
         let json = serde_json::to_string(&settings).unwrap();
         let parsed: Settings = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed.theme, "light");
         assert!(parsed.default_model.is_none());
         assert!(parsed.system_prompt.is_none());
+        assert!(parsed.context_budget_chars.is_none());
+        assert!(parsed.max_sessions.is_none());
     }
 
     #[test]
@@ -946,6 +3928,12 @@ mod tests {
             role: "user".to_string(),
             content: "Testing roundtrip".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         db::save_message(conn, &msg).unwrap();
 
@@ -957,14 +3945,41 @@ mod tests {
         assert_eq!(convos.len(), 1);
     }
 
+    #[test]
+    fn test_now_timestamp_matches_webview_format() {
+        let ts = now_timestamp();
+        // e.g. 2026-01-01T00:00:00.000Z, as produced by Date.toISOString()
+        assert_eq!(ts.len(), 24);
+        assert!(ts.ends_with('Z'));
+        assert!(chrono::DateTime::parse_from_rfc3339(&ts).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_release_conversation_session_clears_binding() {
+        let state = AppState::new();
+        state
+            .conversation_sessions
+            .write()
+            .await
+            .insert("convo-1".to_string(), "stale-session".to_string());
+
+        release_conversation_session(&state, "convo-1").await;
+        assert!(state.conversation_sessions.read().await.is_empty());
+
+        // Releasing an unbound conversation is a no-op
+        release_conversation_session(&state, "convo-2").await;
+    }
+
     #[test]
     fn test_usage_event_with_none_tokens() {
-        let usage = UsageEvent {
-            session_id: "s1".to_string(),
+        let usage = StreamEvent::Usage {
+            seq: 1,
             input_tokens: None,
             output_tokens: None,
         };
-        let json = serde_json::to_string(&usage).unwrap();
-        assert!(json.contains("null"));
+        let json = serde_json::to_value(&usage).unwrap();
+        assert_eq!(json["type"], "usage");
+        assert!(json["input_tokens"].is_null());
+        assert!(json["output_tokens"].is_null());
     }
 }
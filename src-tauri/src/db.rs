@@ -1,5 +1,13 @@
+use crate::attachments::Attachment;
 use crate::commands::{Conversation, Message};
-use rusqlite::{params, Connection, Result as SqlResult};
+use crate::mcp::{McpServer, McpTransport};
+use crate::permissions::{PermissionDecision, PermissionRule};
+use crate::personas::Persona;
+use crate::templates::PromptTemplate;
+use crate::tool_log::ToolCall;
+use crate::tools::{SearchHit, ToolAuditEntry};
+use crate::workspace::TrustedFolder;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
 
 /// Derives a database encryption key from the database file path.
 /// This provides basic encryption at rest tied to the current file location.
@@ -78,7 +86,10 @@ pub fn init_schema(conn: &Connection) -> SqlResult<()> {
             title TEXT NOT NULL,
             model TEXT,
             created_at TEXT NOT NULL DEFAULT (datetime('now')),
-            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            active_leaf_id TEXT,
+            forked_from_conversation_id TEXT,
+            forked_from_message_id TEXT
         );
 
         CREATE TABLE IF NOT EXISTS messages (
@@ -87,15 +98,196 @@ pub fn init_schema(conn: &Connection) -> SqlResult<()> {
             role TEXT NOT NULL,
             content TEXT NOT NULL,
             created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            interrupted INTEGER NOT NULL DEFAULT 0,
+            parent_id TEXT,
+            sibling_index INTEGER NOT NULL DEFAULT 0,
             FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
         );
 
         CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(conversation_id);
+
+        CREATE TABLE IF NOT EXISTS tool_calls (
+            message_id TEXT NOT NULL,
+            id TEXT NOT NULL,
+            conversation_id TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            tool_name TEXT NOT NULL,
+            arguments TEXT,
+            progress TEXT,
+            result TEXT,
+            error TEXT,
+            success INTEGER,
+            started_at TEXT NOT NULL,
+            duration_ms INTEGER,
+            PRIMARY KEY (message_id, id),
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_tool_calls_conversation ON tool_calls(conversation_id);
+
+        CREATE TABLE IF NOT EXISTS attachments (
+            message_id TEXT NOT NULL,
+            id TEXT NOT NULL,
+            conversation_id TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            sha256 TEXT NOT NULL,
+            PRIMARY KEY (message_id, id),
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_attachments_conversation ON attachments(conversation_id);
+
+        CREATE TABLE IF NOT EXISTS attachment_data (
+            sha256 TEXT PRIMARY KEY,
+            data BLOB NOT NULL,
+            thumbnail BLOB
+        );
+
+        CREATE TABLE IF NOT EXISTS document_text (
+            sha256 TEXT PRIMARY KEY,
+            text TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS permission_rules (
+            id TEXT PRIMARY KEY,
+            tool TEXT NOT NULL,
+            path_pattern TEXT,
+            conversation_id TEXT,
+            decision TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS tool_audit (
+            id TEXT PRIMARY KEY,
+            conversation_id TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            tool_name TEXT NOT NULL,
+            arguments TEXT NOT NULL,
+            success INTEGER NOT NULL,
+            outcome TEXT NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_tool_audit_conversation ON tool_audit(conversation_id);
+
+        CREATE TABLE IF NOT EXISTS trusted_folders (
+            path TEXT PRIMARY KEY,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS mcp_servers (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            transport TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS personas (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            system_prompt TEXT,
+            model TEXT,
+            reasoning_effort TEXT,
+            tools TEXT,
+            mcp_servers TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS prompt_templates (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            description TEXT,
+            body TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
     ",
     )?;
+    ensure_column(
+        conn,
+        "messages",
+        "interrupted",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    ensure_column(conn, "conversations", "active_leaf_id", "TEXT")?;
+    ensure_column(conn, "conversations", "forked_from_conversation_id", "TEXT")?;
+    ensure_column(conn, "conversations", "forked_from_message_id", "TEXT")?;
+    ensure_column(conn, "conversations", "sdk_session_id", "TEXT")?;
+    ensure_column(conn, "conversations", "sdk_session_model", "TEXT")?;
+    ensure_column(conn, "conversations", "disabled_tools", "TEXT")?;
+    ensure_column(conn, "conversations", "workspace_folder", "TEXT")?;
+    ensure_column(conn, "conversations", "persona_id", "TEXT")?;
+    ensure_column(conn, "attachment_data", "thumbnail", "BLOB")?;
+    ensure_column(
+        conn,
+        "messages",
+        "sibling_index",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    if ensure_column(conn, "messages", "parent_id", "TEXT")? {
+        backfill_message_tree(conn)?;
+    }
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_messages_parent ON messages(parent_id);")?;
     Ok(())
 }
 
+/// Adds `column` to `table` when the database was created before it existed.
+/// Returns whether the column had to be added.
+fn ensure_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> SqlResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<SqlResult<Vec<String>>>()?;
+    if columns.iter().any(|c| c == column) {
+        return Ok(false);
+    }
+    conn.execute_batch(&format!(
+        "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+    ))?;
+    Ok(true)
+}
+
+/// Links messages stored before branching existed into one chain per
+/// conversation, in creation order, and makes the newest message the active leaf.
+fn backfill_message_tree(conn: &Connection) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    let rows: Vec<(String, String)> = {
+        let mut stmt = tx.prepare(
+            "SELECT id, conversation_id FROM messages ORDER BY conversation_id, created_at ASC, rowid ASC",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<SqlResult<_>>()?
+    };
+    let mut previous: Option<(String, String)> = None;
+    for (id, conversation_id) in rows {
+        let parent_id = match &previous {
+            Some((prev_convo, prev_id)) if *prev_convo == conversation_id => Some(prev_id.clone()),
+            _ => None,
+        };
+        tx.execute(
+            "UPDATE messages SET parent_id = ?1, sibling_index = 0 WHERE id = ?2",
+            params![parent_id, id],
+        )?;
+        tx.execute(
+            "UPDATE conversations SET active_leaf_id = ?1 WHERE id = ?2",
+            params![id, conversation_id],
+        )?;
+        previous = Some((conversation_id, id));
+    }
+    tx.commit()
+}
+
 pub fn get_setting(conn: &Connection, key: &str) -> SqlResult<Option<String>> {
     let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
     let mut rows = stmt.query(params![key])?;
@@ -114,117 +306,1096 @@ pub fn set_setting(conn: &Connection, key: &str, value: &str) -> SqlResult<()> {
     Ok(())
 }
 
-pub fn list_conversations(
+const CONVERSATION_COLUMNS: &str =
+    "id, title, model, created_at, updated_at, forked_from_conversation_id, forked_from_message_id, workspace_folder, persona_id";
+
+fn conversation_from_row(row: &rusqlite::Row) -> SqlResult<Conversation> {
+    Ok(Conversation {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        model: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        forked_from_conversation_id: row.get(5)?,
+        forked_from_message_id: row.get(6)?,
+        workspace_folder: row.get(7)?,
+        persona_id: row.get(8)?,
+    })
+}
+
+pub fn list_conversations(
+    conn: &Connection,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> SqlResult<Vec<Conversation>> {
+    let limit = limit.unwrap_or(50);
+    let offset = offset.unwrap_or(0);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {CONVERSATION_COLUMNS} FROM conversations ORDER BY updated_at DESC LIMIT ?1 OFFSET ?2"
+    ))?;
+    let rows = stmt.query_map(params![limit, offset], conversation_from_row)?;
+    rows.collect()
+}
+
+pub fn create_conversation(
+    conn: &Connection,
+    id: &str,
+    title: &str,
+    model: Option<&str>,
+) -> SqlResult<Conversation> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO conversations (id, title, model) VALUES (?1, ?2, ?3)",
+        params![id, title, model],
+    )?;
+    let convo = tx.query_row(
+        &format!("SELECT {CONVERSATION_COLUMNS} FROM conversations WHERE id = ?1"),
+        params![id],
+        conversation_from_row,
+    )?;
+    tx.commit()?;
+    Ok(convo)
+}
+
+pub fn get_conversation(conn: &Connection, id: &str) -> SqlResult<Option<Conversation>> {
+    conn.query_row(
+        &format!("SELECT {CONVERSATION_COLUMNS} FROM conversations WHERE id = ?1"),
+        params![id],
+        conversation_from_row,
+    )
+    .optional()
+}
+
+/// Copies conversation `source_id` and the branch from its first message down
+/// to `message_id` into a new conversation `new_id`, recording where it was
+/// forked from. The copies get fresh ids. Returns `None` when `message_id` is
+/// not a message of `source_id`.
+pub fn fork_conversation(
+    conn: &Connection,
+    source_id: &str,
+    message_id: &str,
+    new_id: &str,
+) -> SqlResult<Option<(Conversation, Vec<Message>)>> {
+    let tx = conn.unchecked_transaction()?;
+    let path = get_path_to_message(&tx, source_id, message_id)?;
+    if path.last().map(|m| m.id.as_str()) != Some(message_id) {
+        return Ok(None);
+    }
+    tx.execute(
+        "INSERT INTO conversations (id, title, model, forked_from_conversation_id, forked_from_message_id, workspace_folder, persona_id, disabled_tools)
+         SELECT ?1, title, model, id, ?3, workspace_folder, persona_id, disabled_tools FROM conversations WHERE id = ?2",
+        params![new_id, source_id, message_id],
+    )?;
+
+    let mut copies = Vec::with_capacity(path.len());
+    let mut parent_id: Option<String> = None;
+    for msg in path {
+        let source_message_id = msg.id.clone();
+        let copy = insert_message_in_tx(
+            &tx,
+            &Message {
+                id: uuid::Uuid::new_v4().to_string(),
+                conversation_id: new_id.to_string(),
+                parent_id: parent_id.take(),
+                ..msg
+            },
+        )?;
+        tx.execute(
+            "INSERT INTO tool_calls (message_id, id, conversation_id, position, tool_name, arguments, progress, result, error, success, started_at, duration_ms)
+             SELECT ?1, id, ?2, position, tool_name, arguments, progress, result, error, success, started_at, duration_ms
+             FROM tool_calls WHERE message_id = ?3",
+            params![copy.id, new_id, source_message_id],
+        )?;
+        tx.execute(
+            "INSERT INTO attachments (message_id, id, conversation_id, position, name, mime_type, size, sha256)
+             SELECT ?1, id, ?2, position, name, mime_type, size, sha256
+             FROM attachments WHERE message_id = ?3",
+            params![copy.id, new_id, source_message_id],
+        )?;
+        parent_id = Some(copy.id.clone());
+        copies.push(copy);
+    }
+    attach_tool_calls(&tx, new_id, &mut copies)?;
+    attach_attachments(&tx, new_id, &mut copies)?;
+
+    let convo = tx.query_row(
+        &format!("SELECT {CONVERSATION_COLUMNS} FROM conversations WHERE id = ?1"),
+        params![new_id],
+        conversation_from_row,
+    )?;
+    tx.commit()?;
+    Ok(Some((convo, copies)))
+}
+
+/// Columns read by `message_from_row`; the messages table must be aliased `m`.
+const MESSAGE_COLUMNS: &str = "m.id, m.conversation_id, m.role, m.content, m.created_at, m.interrupted, m.parent_id, m.sibling_index, \
+     (SELECT COUNT(*) FROM messages s WHERE s.conversation_id = m.conversation_id AND s.parent_id IS m.parent_id)";
+
+fn message_from_row(row: &rusqlite::Row) -> SqlResult<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        created_at: row.get(4)?,
+        interrupted: row.get(5)?,
+        parent_id: row.get(6)?,
+        sibling_index: row.get(7)?,
+        sibling_count: row.get(8)?,
+        tool_calls: Vec::new(),
+        attachments: Vec::new(),
+    })
+}
+
+/// Returns the messages from the root of the tree down to `leaf_id`, oldest first.
+fn get_message_path(
+    conn: &Connection,
+    conversation_id: &str,
+    leaf_id: &str,
+    limit: i64,
+    offset: i64,
+) -> SqlResult<Vec<Message>> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH RECURSIVE path(id, depth) AS (
+             SELECT ?1, 0
+             UNION ALL
+             SELECT m.parent_id, path.depth + 1 FROM messages m JOIN path ON m.id = path.id
+             WHERE m.parent_id IS NOT NULL
+         )
+         SELECT {MESSAGE_COLUMNS} FROM path JOIN messages m ON m.id = path.id
+         WHERE m.conversation_id = ?2 ORDER BY path.depth DESC LIMIT ?3 OFFSET ?4"
+    ))?;
+    let rows = stmt.query_map(
+        params![leaf_id, conversation_id, limit, offset],
+        message_from_row,
+    )?;
+    rows.collect()
+}
+
+/// Returns the messages on the conversation's active branch, oldest first.
+pub fn get_conversation_messages(
+    conn: &Connection,
+    conversation_id: &str,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> SqlResult<Vec<Message>> {
+    let limit = limit.unwrap_or(100);
+    let offset = offset.unwrap_or(0);
+    match get_active_leaf(conn, conversation_id)? {
+        Some(leaf_id) => get_message_path(conn, conversation_id, &leaf_id, limit, offset),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Returns every message on the conversation's active branch, oldest first.
+pub fn get_conversation_history(
+    conn: &Connection,
+    conversation_id: &str,
+) -> SqlResult<Vec<Message>> {
+    // A negative LIMIT means "no limit" in SQLite.
+    get_conversation_messages(conn, conversation_id, Some(-1), None)
+}
+
+/// Returns the messages from the root down to `message_id`, oldest first.
+pub fn get_path_to_message(
+    conn: &Connection,
+    conversation_id: &str,
+    message_id: &str,
+) -> SqlResult<Vec<Message>> {
+    get_message_path(conn, conversation_id, message_id, -1, 0)
+}
+
+pub fn get_message(conn: &Connection, id: &str) -> SqlResult<Option<Message>> {
+    conn.query_row(
+        &format!("SELECT {MESSAGE_COLUMNS} FROM messages m WHERE m.id = ?1"),
+        params![id],
+        message_from_row,
+    )
+    .optional()
+}
+
+pub fn get_active_leaf(conn: &Connection, conversation_id: &str) -> SqlResult<Option<String>> {
+    conn.query_row(
+        "SELECT active_leaf_id FROM conversations WHERE id = ?1",
+        params![conversation_id],
+        |row| row.get::<_, Option<String>>(0),
+    )
+    .optional()
+    .map(Option::flatten)
+}
+
+pub fn set_active_leaf(
+    conn: &Connection,
+    conversation_id: &str,
+    message_id: &str,
+) -> SqlResult<()> {
+    conn.execute(
+        "UPDATE conversations SET active_leaf_id = ?1 WHERE id = ?2",
+        params![message_id, conversation_id],
+    )?;
+    Ok(())
+}
+
+/// The SDK session last bound to `conversation_id` and the model it was
+/// created with, so it can be resumed after a restart.
+pub fn get_sdk_session(
+    conn: &Connection,
+    conversation_id: &str,
+) -> SqlResult<Option<(String, Option<String>)>> {
+    conn.query_row(
+        "SELECT sdk_session_id, sdk_session_model FROM conversations WHERE id = ?1",
+        params![conversation_id],
+        |row| {
+            Ok(row
+                .get::<_, Option<String>>(0)?
+                .map(|id| (id, row.get(1).ok().flatten())))
+        },
+    )
+    .optional()
+    .map(Option::flatten)
+}
+
+/// Records the SDK session bound to `conversation_id`; `None` forgets it.
+pub fn set_sdk_session(
+    conn: &Connection,
+    conversation_id: &str,
+    session: Option<(&str, Option<&str>)>,
+) -> SqlResult<()> {
+    let (id, model) = session.unzip();
+    conn.execute(
+        "UPDATE conversations SET sdk_session_id = ?1, sdk_session_model = ?2 WHERE id = ?3",
+        params![id, model.flatten(), conversation_id],
+    )?;
+    Ok(())
+}
+
+/// Makes the branch through sibling `sibling_index` of `message_id` active,
+/// following the newest child at every level below it. Returns `false` when
+/// no such sibling exists in the conversation.
+pub fn select_branch(
+    conn: &Connection,
+    conversation_id: &str,
+    message_id: &str,
+    sibling_index: u32,
+) -> SqlResult<bool> {
+    let sibling: Option<String> = conn
+        .query_row(
+            "SELECT s.id FROM messages m
+             JOIN messages s ON s.conversation_id = m.conversation_id AND s.parent_id IS m.parent_id
+             WHERE m.id = ?1 AND m.conversation_id = ?2 AND s.sibling_index = ?3",
+            params![message_id, conversation_id, sibling_index],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(mut leaf) = sibling else {
+        return Ok(false);
+    };
+    while let Some(child) = conn
+        .query_row(
+            "SELECT id FROM messages WHERE parent_id = ?1 ORDER BY sibling_index DESC LIMIT 1",
+            params![leaf],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+    {
+        leaf = child;
+    }
+    set_active_leaf(conn, conversation_id, &leaf)?;
+    Ok(true)
+}
+
+pub fn delete_conversation(conn: &Connection, id: &str) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM tool_calls WHERE conversation_id = ?1",
+        params![id],
+    )?;
+    tx.execute(
+        "DELETE FROM attachments WHERE conversation_id = ?1",
+        params![id],
+    )?;
+    tx.execute(
+        "DELETE FROM permission_rules WHERE conversation_id = ?1",
+        params![id],
+    )?;
+    tx.execute(
+        "DELETE FROM tool_audit WHERE conversation_id = ?1",
+        params![id],
+    )?;
+    tx.execute(
+        "DELETE FROM messages WHERE conversation_id = ?1",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM conversations WHERE id = ?1", params![id])?;
+    tx.commit()
+}
+
+/// Appends a message to the conversation. A message without a `parent_id` is
+/// attached to the current active leaf; use `insert_message` to store a new root.
+pub fn save_message(conn: &Connection, msg: &Message) -> SqlResult<Message> {
+    if msg.parent_id.is_some() {
+        return insert_message(conn, msg);
+    }
+    let parent_id = get_active_leaf(conn, &msg.conversation_id)?;
+    insert_message(
+        conn,
+        &Message {
+            parent_id,
+            ..msg.clone()
+        },
+    )
+}
+
+/// Stores a message under exactly `msg.parent_id` (`None` makes it a root),
+/// after any existing siblings, and makes it the conversation's active leaf.
+/// Returns the message as stored, with its sibling position filled in.
+pub fn insert_message(conn: &Connection, msg: &Message) -> SqlResult<Message> {
+    let tx = conn.unchecked_transaction()?;
+    let stored = insert_message_in_tx(&tx, msg)?;
+    tx.commit()?;
+    Ok(stored)
+}
+
+/// `insert_message` for callers that already hold a transaction.
+fn insert_message_in_tx(tx: &Connection, msg: &Message) -> SqlResult<Message> {
+    let sibling_index: u32 = tx.query_row(
+        "SELECT COUNT(*) FROM messages WHERE conversation_id = ?1 AND parent_id IS ?2",
+        params![msg.conversation_id, msg.parent_id],
+        |row| row.get(0),
+    )?;
+    tx.execute(
+        "INSERT INTO messages (id, conversation_id, role, content, created_at, interrupted, parent_id, sibling_index) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            msg.id,
+            msg.conversation_id,
+            msg.role,
+            msg.content,
+            msg.created_at,
+            msg.interrupted,
+            msg.parent_id,
+            sibling_index
+        ],
+    )?;
+    tx.execute(
+        "UPDATE conversations SET updated_at = datetime('now'), active_leaf_id = ?2 WHERE id = ?1",
+        params![msg.conversation_id, msg.id],
+    )?;
+    Ok(Message {
+        sibling_index,
+        sibling_count: sibling_index + 1,
+        ..msg.clone()
+    })
+}
+
+/// Stores the tool calls made for `message_id`, in order.
+pub fn save_tool_calls(
+    conn: &Connection,
+    conversation_id: &str,
+    message_id: &str,
+    calls: &[ToolCall],
+) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    for (position, call) in calls.iter().enumerate() {
+        tx.execute(
+            "INSERT OR REPLACE INTO tool_calls (message_id, id, conversation_id, position, tool_name, arguments, progress, result, error, success, started_at, duration_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                message_id,
+                call.id,
+                conversation_id,
+                position as i64,
+                call.tool_name,
+                call.arguments,
+                call.progress,
+                call.result,
+                call.error,
+                call.success,
+                call.started_at,
+                call.duration_ms
+            ],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Fills in `tool_calls` for each of the conversation's `messages`.
+pub fn attach_tool_calls(
+    conn: &Connection,
+    conversation_id: &str,
+    messages: &mut [Message],
+) -> SqlResult<()> {
+    let mut stmt = conn.prepare(
+        "SELECT message_id, id, tool_name, arguments, progress, result, error, success, started_at, duration_ms
+         FROM tool_calls WHERE conversation_id = ?1 ORDER BY message_id, position",
+    )?;
+    let rows = stmt.query_map(params![conversation_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ToolCall {
+                id: row.get(1)?,
+                tool_name: row.get(2)?,
+                arguments: row.get(3)?,
+                progress: row.get(4)?,
+                result: row.get(5)?,
+                error: row.get(6)?,
+                success: row.get(7)?,
+                started_at: row.get(8)?,
+                duration_ms: row.get(9)?,
+            },
+        ))
+    })?;
+    let mut by_message: std::collections::HashMap<String, Vec<ToolCall>> =
+        std::collections::HashMap::new();
+    for row in rows {
+        let (message_id, call) = row?;
+        by_message.entry(message_id).or_default().push(call);
+    }
+    for message in messages.iter_mut() {
+        if let Some(calls) = by_message.remove(&message.id) {
+            message.tool_calls = calls;
+        }
+    }
+    Ok(())
+}
+
+/// Stores the contents of an attachment, and the thumbnail of an image,
+/// once per distinct file.
+pub fn save_attachment_data(
+    conn: &Connection,
+    sha256: &str,
+    data: &[u8],
+    thumbnail: Option<&[u8]>,
+) -> SqlResult<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO attachment_data (sha256, data, thumbnail) VALUES (?1, ?2, ?3)",
+        params![sha256, data, thumbnail],
+    )?;
+    Ok(())
+}
+
+pub fn get_attachment_data(conn: &Connection, sha256: &str) -> SqlResult<Option<Vec<u8>>> {
+    conn.query_row(
+        "SELECT data FROM attachment_data WHERE sha256 = ?1",
+        params![sha256],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Caches the text extracted from a document so re-sending it is instant.
+pub fn save_document_text(conn: &Connection, sha256: &str, text: &str) -> SqlResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO document_text (sha256, text) VALUES (?1, ?2)",
+        params![sha256, text],
+    )?;
+    Ok(())
+}
+
+pub fn get_document_text(conn: &Connection, sha256: &str) -> SqlResult<Option<String>> {
+    conn.query_row(
+        "SELECT text FROM document_text WHERE sha256 = ?1",
+        params![sha256],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Deletes contents, and extracted document text, no message refers to any
+/// more, e.g. of deleted conversations or of prompts that were never sent.
+/// Returns how many contents were deleted.
+pub fn prune_attachment_data(conn: &Connection) -> SqlResult<usize> {
+    conn.execute(
+        "DELETE FROM document_text WHERE sha256 NOT IN (SELECT sha256 FROM attachments)",
+        [],
+    )?;
+    conn.execute(
+        "DELETE FROM attachment_data WHERE sha256 NOT IN (SELECT sha256 FROM attachments)",
+        [],
+    )
+}
+
+/// Links `attachments` to `message_id`, in order.
+pub fn save_attachments(
+    conn: &Connection,
+    conversation_id: &str,
+    message_id: &str,
+    attachments: &[Attachment],
+) -> SqlResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    for (position, attachment) in attachments.iter().enumerate() {
+        tx.execute(
+            "INSERT OR REPLACE INTO attachments (message_id, id, conversation_id, position, name, mime_type, size, sha256)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                message_id,
+                attachment.id,
+                conversation_id,
+                position as i64,
+                attachment.name,
+                attachment.mime_type,
+                attachment.size,
+                attachment.sha256
+            ],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Fills in `attachments` for each of the conversation's `messages`.
+pub fn attach_attachments(
+    conn: &Connection,
+    conversation_id: &str,
+    messages: &mut [Message],
+) -> SqlResult<()> {
+    let mut stmt = conn.prepare(
+        "SELECT a.message_id, a.id, a.name, a.mime_type, a.size, a.sha256, d.thumbnail
+         FROM attachments a LEFT JOIN attachment_data d ON d.sha256 = a.sha256
+         WHERE a.conversation_id = ?1 ORDER BY a.message_id, a.position",
+    )?;
+    let rows = stmt.query_map(params![conversation_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            Attachment {
+                id: row.get(1)?,
+                name: row.get(2)?,
+                mime_type: row.get(3)?,
+                size: row.get(4)?,
+                sha256: row.get(5)?,
+                thumbnail: row
+                    .get::<_, Option<Vec<u8>>>(6)?
+                    .map(|jpeg| crate::attachments::thumbnail_url(&jpeg)),
+            },
+        ))
+    })?;
+    let mut by_message: std::collections::HashMap<String, Vec<Attachment>> =
+        std::collections::HashMap::new();
+    for row in rows {
+        let (message_id, attachment) = row?;
+        by_message.entry(message_id).or_default().push(attachment);
+    }
+    for message in messages.iter_mut() {
+        if let Some(attachments) = by_message.remove(&message.id) {
+            message.attachments = attachments;
+        }
+    }
+    Ok(())
+}
+
+pub fn insert_permission_rule(conn: &Connection, rule: &PermissionRule) -> SqlResult<()> {
+    let decision = match rule.decision {
+        PermissionDecision::Allow => "allow",
+        PermissionDecision::Deny => "deny",
+    };
+    conn.execute(
+        "INSERT INTO permission_rules (id, tool, path_pattern, conversation_id, decision, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            rule.id,
+            rule.tool,
+            rule.path_pattern,
+            rule.conversation_id,
+            decision,
+            rule.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Global rules plus those of `conversation_id`, newest first. `None` lists
+/// every rule.
+pub fn list_permission_rules(
+    conn: &Connection,
+    conversation_id: Option<&str>,
+) -> SqlResult<Vec<PermissionRule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, tool, path_pattern, conversation_id, decision, created_at FROM permission_rules
+         WHERE ?1 IS NULL OR conversation_id IS NULL OR conversation_id = ?1
+         ORDER BY created_at DESC",
+    )?;
+    let rows = stmt.query_map(params![conversation_id], |row| {
+        let decision: String = row.get(4)?;
+        Ok(PermissionRule {
+            id: row.get(0)?,
+            tool: row.get(1)?,
+            path_pattern: row.get(2)?,
+            conversation_id: row.get(3)?,
+            decision: if decision == "allow" {
+                PermissionDecision::Allow
+            } else {
+                PermissionDecision::Deny
+            },
+            created_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Returns whether a rule was deleted.
+pub fn delete_permission_rule(conn: &Connection, id: &str) -> SqlResult<bool> {
+    let deleted = conn.execute("DELETE FROM permission_rules WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Names of the built-in tools turned off in `conversation_id`.
+pub fn get_disabled_tools(conn: &Connection, conversation_id: &str) -> SqlResult<Vec<String>> {
+    let raw: Option<String> = conn
+        .query_row(
+            "SELECT disabled_tools FROM conversations WHERE id = ?1",
+            params![conversation_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    Ok(raw
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
+}
+
+pub fn set_disabled_tools(
+    conn: &Connection,
+    conversation_id: &str,
+    disabled: &[String],
+) -> SqlResult<()> {
+    let raw = serde_json::to_string(disabled).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE conversations SET disabled_tools = ?1 WHERE id = ?2",
+        params![raw, conversation_id],
+    )?;
+    Ok(())
+}
+
+/// Newest messages containing `query` (case-insensitive for ASCII), across
+/// every conversation.
+pub fn search_messages(conn: &Connection, query: &str, limit: u32) -> SqlResult<Vec<SearchHit>> {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    let mut stmt = conn.prepare(
+        "SELECT m.conversation_id, c.title, m.role, m.content, m.created_at
+         FROM messages m JOIN conversations c ON c.id = m.conversation_id
+         WHERE m.content LIKE ?1 ESCAPE '\\'
+         ORDER BY m.created_at DESC LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![format!("%{}%", escaped), limit], |row| {
+        let content: String = row.get(3)?;
+        Ok(SearchHit {
+            conversation_id: row.get(0)?,
+            conversation_title: row.get(1)?,
+            role: row.get(2)?,
+            snippet: snippet(&content, query),
+            created_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Byte range of the first case-insensitive match of `query` in `text`.
+/// Compares lowercased characters one by one, since lowercasing can change
+/// byte lengths and offsets into a lowercased copy may not fall on
+/// character boundaries of `text`.
+fn find_ignore_case(text: &str, query: &str) -> Option<(usize, usize)> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    text.char_indices().find_map(|(start, _)| {
+        let mut matched = 0;
+        for (offset, c) in text[start..].char_indices() {
+            if matched == query.len() {
+                return Some((start, start + offset));
+            }
+            for lower in c.to_lowercase() {
+                if query.get(matched) != Some(&lower) {
+                    return None;
+                }
+                matched += 1;
+            }
+        }
+        (matched == query.len()).then_some((start, text.len()))
+    })
+}
+
+/// About 80 characters either side of the first match of `query` in `text`.
+fn snippet(text: &str, query: &str) -> String {
+    const CONTEXT: usize = 80;
+    let (at, matched_end) = find_ignore_case(text, query).unwrap_or((0, 0));
+    let start = text[..at]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[matched_end..]
+        .char_indices()
+        .nth(CONTEXT)
+        .map_or(text.len(), |(i, _)| matched_end + i);
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    out.push_str(text[start..end].trim());
+    if end < text.len() {
+        out.push('…');
+    }
+    out
+}
+
+pub fn insert_tool_audit(conn: &Connection, entry: &ToolAuditEntry) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO tool_audit (id, conversation_id, session_id, tool_name, arguments, success, outcome, duration_ms, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            entry.id,
+            entry.conversation_id,
+            entry.session_id,
+            entry.tool_name,
+            entry.arguments,
+            entry.success,
+            entry.outcome,
+            entry.duration_ms,
+            entry.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Logged tool calls, newest first. `None` lists every conversation.
+pub fn list_tool_audit(
+    conn: &Connection,
+    conversation_id: Option<&str>,
+    limit: u32,
+) -> SqlResult<Vec<ToolAuditEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, conversation_id, session_id, tool_name, arguments, success, outcome, duration_ms, created_at
+         FROM tool_audit WHERE ?1 IS NULL OR conversation_id = ?1
+         ORDER BY created_at DESC LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![conversation_id, limit], |row| {
+        Ok(ToolAuditEntry {
+            id: row.get(0)?,
+            conversation_id: row.get(1)?,
+            session_id: row.get(2)?,
+            tool_name: row.get(3)?,
+            arguments: row.get(4)?,
+            success: row.get(5)?,
+            outcome: row.get(6)?,
+            duration_ms: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Sets or clears the workspace folder of `conversation_id`; returns whether
+/// the conversation exists.
+pub fn set_workspace_folder(
+    conn: &Connection,
+    conversation_id: &str,
+    folder: Option<&str>,
+) -> SqlResult<bool> {
+    let updated = conn.execute(
+        "UPDATE conversations SET workspace_folder = ?1 WHERE id = ?2",
+        params![folder, conversation_id],
+    )?;
+    Ok(updated > 0)
+}
+
+pub fn list_conversation_ids(conn: &Connection) -> SqlResult<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT id FROM conversations")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// `(conversation id, workspace folder)` of every conversation that has one.
+pub fn list_workspace_folders(conn: &Connection) -> SqlResult<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, workspace_folder FROM conversations WHERE workspace_folder IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn list_trusted_folders(conn: &Connection) -> SqlResult<Vec<TrustedFolder>> {
+    let mut stmt = conn.prepare("SELECT path, created_at FROM trusted_folders ORDER BY path")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TrustedFolder {
+            path: row.get(0)?,
+            created_at: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Adds `folder` to the allowlist; adding it again keeps the first entry.
+pub fn insert_trusted_folder(conn: &Connection, folder: &TrustedFolder) -> SqlResult<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO trusted_folders (path, created_at) VALUES (?1, ?2)",
+        params![folder.path, folder.created_at],
+    )?;
+    Ok(())
+}
+
+/// Returns whether the folder was on the allowlist.
+pub fn delete_trusted_folder(conn: &Connection, path: &str) -> SqlResult<bool> {
+    let deleted = conn.execute("DELETE FROM trusted_folders WHERE path = ?1", params![path])?;
+    Ok(deleted > 0)
+}
+
+fn mcp_server_from_row(row: &rusqlite::Row) -> SqlResult<McpServer> {
+    let transport: String = row.get(3)?;
+    Ok(McpServer {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        enabled: row.get(2)?,
+        transport: serde_json::from_str::<McpTransport>(&transport).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        created_at: row.get(4)?,
+    })
+}
+
+/// Configured MCP servers in the order they were added.
+pub fn list_mcp_servers(conn: &Connection) -> SqlResult<Vec<McpServer>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, enabled, transport, created_at FROM mcp_servers ORDER BY created_at, name",
+    )?;
+    let rows = stmt.query_map([], mcp_server_from_row)?;
+    rows.collect()
+}
+
+pub fn insert_mcp_server(conn: &Connection, server: &McpServer) -> SqlResult<()> {
+    let transport = serde_json::to_string(&server.transport).unwrap_or_default();
+    conn.execute(
+        "INSERT INTO mcp_servers (id, name, enabled, transport, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            server.id,
+            server.name,
+            server.enabled,
+            transport,
+            server.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Replaces the editable fields of server `server.id`; returns whether it exists.
+pub fn update_mcp_server(conn: &Connection, server: &McpServer) -> SqlResult<bool> {
+    let transport = serde_json::to_string(&server.transport).unwrap_or_default();
+    let updated = conn.execute(
+        "UPDATE mcp_servers SET name = ?1, enabled = ?2, transport = ?3 WHERE id = ?4",
+        params![server.name, server.enabled, transport, server.id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Returns whether a server was deleted.
+pub fn delete_mcp_server(conn: &Connection, id: &str) -> SqlResult<bool> {
+    let deleted = conn.execute("DELETE FROM mcp_servers WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+fn prompt_template_from_row(row: &rusqlite::Row) -> SqlResult<PromptTemplate> {
+    let body: String = row.get(3)?;
+    Ok(PromptTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        variables: crate::templates::variables(&body),
+        body,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+/// Saved prompt templates by name.
+pub fn list_prompt_templates(conn: &Connection) -> SqlResult<Vec<PromptTemplate>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, description, body, created_at, updated_at FROM prompt_templates ORDER BY name",
+    )?;
+    let rows = stmt.query_map([], prompt_template_from_row)?;
+    rows.collect()
+}
+
+/// The template with id or name `key`.
+pub fn get_prompt_template(conn: &Connection, key: &str) -> SqlResult<Option<PromptTemplate>> {
+    conn.query_row(
+        "SELECT id, name, description, body, created_at, updated_at FROM prompt_templates
+         WHERE id = ?1 OR name = ?1",
+        params![key],
+        prompt_template_from_row,
+    )
+    .optional()
+}
+
+pub fn insert_prompt_template(conn: &Connection, template: &PromptTemplate) -> SqlResult<()> {
+    conn.execute(
+        "INSERT INTO prompt_templates (id, name, description, body, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            template.id,
+            template.name,
+            template.description,
+            template.body,
+            template.created_at,
+            template.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Replaces the editable fields of template `template.id`; returns whether it exists.
+pub fn update_prompt_template(conn: &Connection, template: &PromptTemplate) -> SqlResult<bool> {
+    let updated = conn.execute(
+        "UPDATE prompt_templates SET name = ?1, description = ?2, body = ?3, updated_at = ?4
+         WHERE id = ?5",
+        params![
+            template.name,
+            template.description,
+            template.body,
+            template.updated_at,
+            template.id
+        ],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Returns whether a template was deleted.
+pub fn delete_prompt_template(conn: &Connection, id: &str) -> SqlResult<bool> {
+    let deleted = conn.execute("DELETE FROM prompt_templates WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+fn json_list(value: &Option<Vec<String>>) -> Option<String> {
+    value
+        .as_ref()
+        .map(|v| serde_json::to_string(v).unwrap_or_else(|_| "[]".to_string()))
+}
+
+fn persona_from_row(row: &rusqlite::Row) -> SqlResult<Persona> {
+    let list = |i: usize| -> SqlResult<Option<Vec<String>>> {
+        let raw: Option<String> = row.get(i)?;
+        Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+    };
+    Ok(Persona {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        system_prompt: row.get(2)?,
+        model: row.get(3)?,
+        reasoning_effort: row.get(4)?,
+        tools: list(5)?,
+        mcp_servers: list(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+const PERSONA_COLUMNS: &str =
+    "id, name, system_prompt, model, reasoning_effort, tools, mcp_servers, created_at, updated_at";
+
+/// Saved personas by name.
+pub fn list_personas(conn: &Connection) -> SqlResult<Vec<Persona>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {PERSONA_COLUMNS} FROM personas ORDER BY name"
+    ))?;
+    let rows = stmt.query_map([], persona_from_row)?;
+    rows.collect()
+}
+
+pub fn get_persona(conn: &Connection, id: &str) -> SqlResult<Option<Persona>> {
+    conn.query_row(
+        &format!("SELECT {PERSONA_COLUMNS} FROM personas WHERE id = ?1"),
+        params![id],
+        persona_from_row,
+    )
+    .optional()
+}
+
+/// The persona `conversation_id` was started with, if it still exists.
+pub fn get_conversation_persona(
     conn: &Connection,
-    limit: Option<i64>,
-    offset: Option<i64>,
-) -> SqlResult<Vec<Conversation>> {
-    let limit = limit.unwrap_or(50);
-    let offset = offset.unwrap_or(0);
-    let mut stmt = conn.prepare(
-        "SELECT id, title, model, created_at, updated_at FROM conversations ORDER BY updated_at DESC LIMIT ?1 OFFSET ?2",
-    )?;
-    let rows = stmt.query_map(params![limit, offset], |row| {
-        Ok(Conversation {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            model: row.get(2)?,
-            created_at: row.get(3)?,
-            updated_at: row.get(4)?,
-        })
-    })?;
-    rows.collect()
+    conversation_id: &str,
+) -> SqlResult<Option<Persona>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM personas WHERE id = (SELECT persona_id FROM conversations WHERE id = ?1)",
+            PERSONA_COLUMNS
+        ),
+        params![conversation_id],
+        persona_from_row,
+    )
+    .optional()
 }
 
-pub fn create_conversation(
-    conn: &Connection,
-    id: &str,
-    title: &str,
-    model: Option<&str>,
-) -> SqlResult<Conversation> {
-    let tx = conn.unchecked_transaction()?;
-    tx.execute(
-        "INSERT INTO conversations (id, title, model) VALUES (?1, ?2, ?3)",
-        params![id, title, model],
-    )?;
-    let convo = tx.query_row(
-        "SELECT id, title, model, created_at, updated_at FROM conversations WHERE id = ?1",
-        params![id],
-        |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                model: row.get(2)?,
-                created_at: row.get(3)?,
-                updated_at: row.get(4)?,
-            })
-        },
+pub fn insert_persona(conn: &Connection, persona: &Persona) -> SqlResult<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO personas ({PERSONA_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+        ),
+        params![
+            persona.id,
+            persona.name,
+            persona.system_prompt,
+            persona.model,
+            persona.reasoning_effort,
+            json_list(&persona.tools),
+            json_list(&persona.mcp_servers),
+            persona.created_at,
+            persona.updated_at
+        ],
     )?;
-    tx.commit()?;
-    Ok(convo)
+    Ok(())
 }
 
-pub fn get_conversation(conn: &Connection, id: &str) -> SqlResult<Option<Conversation>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, title, model, created_at, updated_at FROM conversations WHERE id = ?1",
+/// Replaces the editable fields of persona `persona.id`; returns whether it exists.
+pub fn update_persona(conn: &Connection, persona: &Persona) -> SqlResult<bool> {
+    let updated = conn.execute(
+        "UPDATE personas SET name = ?1, system_prompt = ?2, model = ?3, reasoning_effort = ?4,
+         tools = ?5, mcp_servers = ?6, updated_at = ?7 WHERE id = ?8",
+        params![
+            persona.name,
+            persona.system_prompt,
+            persona.model,
+            persona.reasoning_effort,
+            json_list(&persona.tools),
+            json_list(&persona.mcp_servers),
+            persona.updated_at,
+            persona.id
+        ],
     )?;
-    let mut rows = stmt.query(params![id])?;
-    if let Some(row) = rows.next()? {
-        Ok(Some(Conversation {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            model: row.get(2)?,
-            created_at: row.get(3)?,
-            updated_at: row.get(4)?,
-        }))
-    } else {
-        Ok(None)
-    }
+    Ok(updated > 0)
 }
 
-pub fn get_conversation_messages(
-    conn: &Connection,
-    conversation_id: &str,
-    limit: Option<i64>,
-    offset: Option<i64>,
-) -> SqlResult<Vec<Message>> {
-    let limit = limit.unwrap_or(100);
-    let offset = offset.unwrap_or(0);
-    let mut stmt = conn.prepare(
-        "SELECT id, conversation_id, role, content, created_at FROM messages WHERE conversation_id = ?1 ORDER BY created_at ASC LIMIT ?2 OFFSET ?3",
-    )?;
-    let rows = stmt.query_map(params![conversation_id, limit, offset], |row| {
-        Ok(Message {
-            id: row.get(0)?,
-            conversation_id: row.get(1)?,
-            role: row.get(2)?,
-            content: row.get(3)?,
-            created_at: row.get(4)?,
-        })
-    })?;
-    rows.collect()
+/// Ids of the conversations started with persona `persona_id`.
+pub fn list_persona_conversations(conn: &Connection, persona_id: &str) -> SqlResult<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT id FROM conversations WHERE persona_id = ?1")?;
+    let ids = stmt
+        .query_map(params![persona_id], |row| row.get(0))?
+        .collect::<SqlResult<Vec<String>>>()?;
+    Ok(ids)
 }
 
-pub fn delete_conversation(conn: &Connection, id: &str) -> SqlResult<()> {
+/// Deletes a persona; its conversations carry on with the global settings.
+/// Returns whether a persona was deleted.
+pub fn delete_persona(conn: &Connection, id: &str) -> SqlResult<bool> {
     let tx = conn.unchecked_transaction()?;
     tx.execute(
-        "DELETE FROM messages WHERE conversation_id = ?1",
+        "UPDATE conversations SET persona_id = NULL WHERE persona_id = ?1",
         params![id],
     )?;
-    tx.execute("DELETE FROM conversations WHERE id = ?1", params![id])?;
-    tx.commit()
+    let deleted = tx.execute("DELETE FROM personas WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(deleted > 0)
 }
 
-pub fn save_message(conn: &Connection, msg: &Message) -> SqlResult<()> {
+/// Starts `conversation_id` with `persona`: records it and turns off the
+/// built-in tools the persona does not use.
+pub fn set_conversation_persona(
+    conn: &Connection,
+    conversation_id: &str,
+    persona: &Persona,
+) -> SqlResult<()> {
     let tx = conn.unchecked_transaction()?;
     tx.execute(
-        "INSERT INTO messages (id, conversation_id, role, content, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![msg.id, msg.conversation_id, msg.role, msg.content, msg.created_at],
-    )?;
-    tx.execute(
-        "UPDATE conversations SET updated_at = datetime('now') WHERE id = ?1",
-        params![msg.conversation_id],
+        "UPDATE conversations SET persona_id = ?1 WHERE id = ?2",
+        params![persona.id, conversation_id],
     )?;
+    set_disabled_tools(&tx, conversation_id, &persona.disabled_tools())?;
     tx.commit()
 }
 
@@ -265,6 +1436,44 @@ mod tests {
         assert!(tables.contains(&"messages".to_string()));
     }
 
+    #[test]
+    fn test_init_schema_migrates_old_messages_table() {
+        let tmp = NamedTempFile::new().unwrap();
+        let conn = open_db(tmp.path().to_str().unwrap()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (
+                id TEXT PRIMARY KEY,
+                conversation_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );",
+        )
+        .unwrap();
+
+        init_schema(&conn).unwrap();
+        // Running it again must not try to add the column twice
+        init_schema(&conn).unwrap();
+
+        create_conversation(&conn, "c1", "Test", None).unwrap();
+        let msg = crate::commands::Message {
+            id: "m1".to_string(),
+            conversation_id: "c1".to_string(),
+            role: "assistant".to_string(),
+            content: "Partial".to_string(),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: true,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
+        };
+        save_message(&conn, &msg).unwrap();
+        let msgs = get_conversation_messages(&conn, "c1", None, None).unwrap();
+        assert!(msgs[0].interrupted);
+    }
+
     #[test]
     fn test_settings_crud() {
         let (conn, _tmp) = setup_test_db();
@@ -320,6 +1529,12 @@ mod tests {
             role: "user".to_string(),
             content: "Hello!".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         save_message(&conn, &msg1).unwrap();
 
@@ -329,6 +1544,12 @@ mod tests {
             role: "assistant".to_string(),
             content: "Hi there!".to_string(),
             created_at: "2026-01-01T00:00:01Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         save_message(&conn, &msg2).unwrap();
 
@@ -338,6 +1559,596 @@ mod tests {
         assert_eq!(msgs[1].role, "assistant");
     }
 
+    #[test]
+    fn test_conversation_history_is_unpaginated() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "convo-1", "Test", None).unwrap();
+
+        for i in 0..120 {
+            let msg = crate::commands::Message {
+                id: format!("msg-{i:03}"),
+                conversation_id: "convo-1".to_string(),
+                role: "user".to_string(),
+                content: format!("Message {i}"),
+                created_at: format!("2026-01-01T00:{:02}:{:02}Z", i / 60, i % 60),
+                interrupted: false,
+                parent_id: None,
+                sibling_index: 0,
+                sibling_count: 0,
+                tool_calls: Vec::new(),
+                attachments: Vec::new(),
+            };
+            save_message(&conn, &msg).unwrap();
+        }
+
+        let history = get_conversation_history(&conn, "convo-1").unwrap();
+        assert_eq!(history.len(), 120);
+        assert_eq!(history[0].id, "msg-000");
+        assert_eq!(history[119].id, "msg-119");
+    }
+
+    fn tree_msg(id: &str, role: &str, parent_id: Option<&str>) -> crate::commands::Message {
+        crate::commands::Message {
+            id: id.to_string(),
+            conversation_id: "convo-1".to_string(),
+            role: role.to_string(),
+            content: format!("content of {id}"),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: parent_id.map(str::to_string),
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_regenerated_reply_becomes_active_sibling() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "convo-1", "Test", None).unwrap();
+
+        save_message(&conn, &tree_msg("u1", "user", None)).unwrap();
+        save_message(&conn, &tree_msg("a1", "assistant", Some("u1"))).unwrap();
+        let regenerated = insert_message(&conn, &tree_msg("a2", "assistant", Some("u1"))).unwrap();
+        assert_eq!(regenerated.sibling_index, 1);
+        assert_eq!(regenerated.sibling_count, 2);
+
+        let msgs = get_conversation_messages(&conn, "convo-1", None, None).unwrap();
+        let ids: Vec<&str> = msgs.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["u1", "a2"]);
+        assert_eq!(msgs[1].sibling_count, 2);
+    }
+
+    #[test]
+    fn test_select_branch_follows_newest_descendant() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "convo-1", "Test", None).unwrap();
+
+        // u1 -> a1 -> u2 -> a2, then u1 is edited into u1b -> a3
+        save_message(&conn, &tree_msg("u1", "user", None)).unwrap();
+        save_message(&conn, &tree_msg("a1", "assistant", None)).unwrap();
+        save_message(&conn, &tree_msg("u2", "user", None)).unwrap();
+        save_message(&conn, &tree_msg("a2", "assistant", None)).unwrap();
+        insert_message(&conn, &tree_msg("u1b", "user", None)).unwrap();
+        insert_message(&conn, &tree_msg("a3", "assistant", Some("u1b"))).unwrap();
+
+        let ids = |conn: &Connection| -> Vec<String> {
+            get_conversation_history(conn, "convo-1")
+                .unwrap()
+                .into_iter()
+                .map(|m| m.id)
+                .collect()
+        };
+        assert_eq!(ids(&conn), vec!["u1b", "a3"]);
+
+        assert!(select_branch(&conn, "convo-1", "u1b", 0).unwrap());
+        assert_eq!(ids(&conn), vec!["u1", "a1", "u2", "a2"]);
+
+        assert!(select_branch(&conn, "convo-1", "u1", 1).unwrap());
+        assert_eq!(ids(&conn), vec!["u1b", "a3"]);
+
+        assert!(!select_branch(&conn, "convo-1", "u1", 5).unwrap());
+        assert!(!select_branch(&conn, "other", "u1", 0).unwrap());
+    }
+
+    #[test]
+    fn test_fork_conversation_copies_prefix() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "convo-1", "Source", Some("gpt-4o")).unwrap();
+        save_message(&conn, &tree_msg("u1", "user", None)).unwrap();
+        save_message(&conn, &tree_msg("a1", "assistant", None)).unwrap();
+        save_message(&conn, &tree_msg("u2", "user", None)).unwrap();
+        save_message(&conn, &tree_msg("a2", "assistant", None)).unwrap();
+        set_disabled_tools(&conn, "convo-1", &["read_file".to_string()]).unwrap();
+
+        let (fork, copies) = fork_conversation(&conn, "convo-1", "a1", "fork-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(fork.title, "Source");
+        assert_eq!(fork.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(fork.forked_from_conversation_id.as_deref(), Some("convo-1"));
+        assert_eq!(fork.forked_from_message_id.as_deref(), Some("a1"));
+        assert_eq!(
+            get_disabled_tools(&conn, "fork-1").unwrap(),
+            vec!["read_file"]
+        );
+
+        let stored = get_conversation_history(&conn, "fork-1").unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].content, "content of u1");
+        assert_eq!(stored[1].content, "content of a1");
+        assert_eq!(stored[1].parent_id.as_deref(), Some(stored[0].id.as_str()));
+        assert!(stored.iter().all(|m| m.id != "u1" && m.id != "a1"));
+        assert_eq!(copies.last().unwrap().id, stored[1].id);
+
+        // The source is left untouched
+        assert_eq!(get_conversation_history(&conn, "convo-1").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_fork_conversation_rejects_foreign_message() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "convo-1", "Source", None).unwrap();
+        create_conversation(&conn, "other", "Other", None).unwrap();
+        save_message(&conn, &tree_msg("u1", "user", None)).unwrap();
+
+        assert!(fork_conversation(&conn, "other", "u1", "fork-1")
+            .unwrap()
+            .is_none());
+        assert!(get_conversation(&conn, "fork-1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_init_schema_backfills_message_tree() {
+        let tmp = NamedTempFile::new().unwrap();
+        let conn = open_db(tmp.path().to_str().unwrap()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE conversations (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL DEFAULT 'New Chat',
+                model TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE TABLE messages (
+                id TEXT PRIMARY KEY,
+                conversation_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            INSERT INTO conversations (id, title) VALUES ('c1', 'Old');
+            INSERT INTO messages (id, conversation_id, role, content, created_at) VALUES
+                ('m2', 'c1', 'assistant', 'Hi', '2026-01-01T00:00:01Z'),
+                ('m1', 'c1', 'user', 'Hello', '2026-01-01T00:00:00Z');",
+        )
+        .unwrap();
+
+        init_schema(&conn).unwrap();
+
+        let msgs = get_conversation_messages(&conn, "c1", None, None).unwrap();
+        let ids: Vec<&str> = msgs.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "m2"]);
+        assert_eq!(msgs[1].parent_id.as_deref(), Some("m1"));
+        assert_eq!(get_active_leaf(&conn, "c1").unwrap().as_deref(), Some("m2"));
+    }
+
+    #[test]
+    fn test_sdk_session_roundtrip() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "c1", "Chat", None).unwrap();
+        assert_eq!(get_sdk_session(&conn, "c1").unwrap(), None);
+
+        set_sdk_session(&conn, "c1", Some(("sdk-1", Some("gpt-4o")))).unwrap();
+        assert_eq!(
+            get_sdk_session(&conn, "c1").unwrap(),
+            Some(("sdk-1".to_string(), Some("gpt-4o".to_string())))
+        );
+
+        set_sdk_session(&conn, "c1", None).unwrap();
+        assert_eq!(get_sdk_session(&conn, "c1").unwrap(), None);
+        assert_eq!(get_sdk_session(&conn, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_tool_calls_roundtrip_and_fork() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "convo-1", "Source", None).unwrap();
+        save_message(&conn, &tree_msg("u1", "user", None)).unwrap();
+        save_message(&conn, &tree_msg("a1", "assistant", None)).unwrap();
+        let calls = vec![
+            ToolCall {
+                id: "t1".to_string(),
+                tool_name: "bash".to_string(),
+                arguments: Some(r#"{"command":"ls"}"#.to_string()),
+                progress: None,
+                result: Some("Cargo.toml".to_string()),
+                error: None,
+                success: Some(true),
+                started_at: "2026-01-01T00:00:00.000Z".to_string(),
+                duration_ms: Some(120),
+            },
+            ToolCall {
+                id: "t2".to_string(),
+                tool_name: "view".to_string(),
+                arguments: None,
+                progress: None,
+                result: None,
+                error: Some("No such file".to_string()),
+                success: Some(false),
+                started_at: "2026-01-01T00:00:01.000Z".to_string(),
+                duration_ms: Some(5),
+            },
+        ];
+        save_tool_calls(&conn, "convo-1", "a1", &calls).unwrap();
+
+        let mut msgs = get_conversation_history(&conn, "convo-1").unwrap();
+        attach_tool_calls(&conn, "convo-1", &mut msgs).unwrap();
+        assert!(msgs[0].tool_calls.is_empty());
+        assert_eq!(msgs[1].tool_calls, calls);
+
+        let (_, copies) = fork_conversation(&conn, "convo-1", "a1", "fork-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(copies[1].tool_calls, calls);
+
+        delete_conversation(&conn, "convo-1").unwrap();
+        let mut forked = get_conversation_history(&conn, "fork-1").unwrap();
+        attach_tool_calls(&conn, "fork-1", &mut forked).unwrap();
+        assert_eq!(forked[1].tool_calls.len(), 2);
+    }
+
+    #[test]
+    fn test_attachments_roundtrip_fork_and_prune() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "convo-1", "Source", None).unwrap();
+        save_message(&conn, &tree_msg("u1", "user", None)).unwrap();
+        let attachment = Attachment {
+            id: "f1".to_string(),
+            name: "build.log".to_string(),
+            mime_type: "text/plain".to_string(),
+            size: 5,
+            sha256: "abc".to_string(),
+            thumbnail: None,
+        };
+        save_attachment_data(&conn, "abc", b"hello", None).unwrap();
+        // The same contents are stored once
+        save_attachment_data(&conn, "abc", b"hello", None).unwrap();
+        save_attachment_data(&conn, "unsent", b"bye", None).unwrap();
+        save_attachment_data(&conn, "img", b"png", Some(b"jpeg")).unwrap();
+        save_document_text(&conn, "abc", "hello").unwrap();
+        save_document_text(&conn, "unsent", "bye").unwrap();
+        let image = Attachment {
+            id: "f2".to_string(),
+            name: "shot.png".to_string(),
+            mime_type: "image/png".to_string(),
+            size: 3,
+            sha256: "img".to_string(),
+            thumbnail: Some(crate::attachments::thumbnail_url(b"jpeg")),
+        };
+        let both = vec![attachment, image];
+        save_attachments(&conn, "convo-1", "u1", &both).unwrap();
+
+        let mut msgs = get_conversation_history(&conn, "convo-1").unwrap();
+        attach_attachments(&conn, "convo-1", &mut msgs).unwrap();
+        assert_eq!(msgs[0].attachments, both);
+
+        let (_, copies) = fork_conversation(&conn, "convo-1", "u1", "fork-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(copies[0].attachments, both);
+
+        assert_eq!(prune_attachment_data(&conn).unwrap(), 1);
+        assert_eq!(get_document_text(&conn, "unsent").unwrap(), None);
+        delete_conversation(&conn, "convo-1").unwrap();
+        assert_eq!(prune_attachment_data(&conn).unwrap(), 0);
+        assert_eq!(
+            get_attachment_data(&conn, "abc").unwrap(),
+            Some(b"hello".to_vec())
+        );
+        delete_conversation(&conn, "fork-1").unwrap();
+        assert_eq!(
+            get_document_text(&conn, "abc").unwrap(),
+            Some("hello".to_string())
+        );
+        assert_eq!(prune_attachment_data(&conn).unwrap(), 2);
+        assert_eq!(get_attachment_data(&conn, "abc").unwrap(), None);
+        assert_eq!(get_document_text(&conn, "abc").unwrap(), None);
+    }
+
+    #[test]
+    fn test_permission_rules_crud() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "c1", "Chat", None).unwrap();
+        let rule = |id: &str, conversation_id: Option<&str>, decision| PermissionRule {
+            id: id.to_string(),
+            tool: "shell".to_string(),
+            path_pattern: Some("git *".to_string()),
+            conversation_id: conversation_id.map(str::to_string),
+            decision,
+            created_at: format!("2026-01-01T00:00:0{}.000Z", id.len()),
+        };
+        insert_permission_rule(&conn, &rule("global", None, PermissionDecision::Allow)).unwrap();
+        insert_permission_rule(
+            &conn,
+            &rule("c1-rule", Some("c1"), PermissionDecision::Deny),
+        )
+        .unwrap();
+        insert_permission_rule(&conn, &rule("c2", Some("c2"), PermissionDecision::Deny)).unwrap();
+
+        let for_c1 = list_permission_rules(&conn, Some("c1")).unwrap();
+        let ids: Vec<&str> = for_c1.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["c1-rule", "global"]);
+        assert_eq!(for_c1[0].decision, PermissionDecision::Deny);
+        assert_eq!(list_permission_rules(&conn, None).unwrap().len(), 3);
+
+        assert!(delete_permission_rule(&conn, "global").unwrap());
+        assert!(!delete_permission_rule(&conn, "global").unwrap());
+
+        // Deleting the conversation drops its rules
+        delete_conversation(&conn, "c1").unwrap();
+        assert_eq!(list_permission_rules(&conn, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_search_messages_escapes_wildcards() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "c1", "Rust notes", None).unwrap();
+        for (id, content) in [("m1", "Use 100% safe code"), ("m2", "Nothing here")] {
+            save_message(
+                &conn,
+                &Message {
+                    id: id.to_string(),
+                    conversation_id: "c1".to_string(),
+                    role: "user".to_string(),
+                    content: content.to_string(),
+                    created_at: "2026-01-01T00:00:00Z".to_string(),
+                    interrupted: false,
+                    parent_id: None,
+                    sibling_index: 0,
+                    sibling_count: 0,
+                    tool_calls: Vec::new(),
+                    attachments: Vec::new(),
+                },
+            )
+            .unwrap();
+        }
+
+        let hits = search_messages(&conn, "100% SAFE", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].conversation_title, "Rust notes");
+        assert_eq!(hits[0].snippet, "Use 100% safe code");
+        assert!(search_messages(&conn, "0%_", 10).unwrap().is_empty());
+
+        let long = format!("{}needle{}", "a".repeat(200), "b".repeat(200));
+        let cut = snippet(&long, "needle");
+        assert!(cut.starts_with('…') && cut.ends_with('…'));
+        assert!(cut.contains("needle"));
+
+        // Lowercasing the Kelvin sign shrinks it and İ grows, so byte
+        // offsets into a lowercased copy would not line up with the text
+        let tricky = format!("\u{212A}\u{130}NEEDLE{}", "z".repeat(200));
+        assert_eq!(find_ignore_case(&tricky, "needle"), Some((5, 11)));
+        assert!(snippet(&tricky, "needle").starts_with("\u{212A}\u{130}NEEDLE"));
+        assert_eq!(find_ignore_case("\u{130}x", "i\u{307}X"), Some((0, 3)));
+        assert_eq!(find_ignore_case("\u{212A}\u{130}", "needle"), None);
+    }
+
+    #[test]
+    fn test_disabled_tools_and_audit() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "c1", "Chat", None).unwrap();
+        assert!(get_disabled_tools(&conn, "c1").unwrap().is_empty());
+        set_disabled_tools(&conn, "c1", &["read_file".to_string()]).unwrap();
+        assert_eq!(get_disabled_tools(&conn, "c1").unwrap(), vec!["read_file"]);
+
+        let entry = |id: &str, conversation_id: &str| ToolAuditEntry {
+            id: id.to_string(),
+            conversation_id: conversation_id.to_string(),
+            session_id: "s1".to_string(),
+            tool_name: "current_datetime".to_string(),
+            arguments: "{}".to_string(),
+            success: true,
+            outcome: "64 bytes".to_string(),
+            duration_ms: 2,
+            created_at: format!("2026-01-01T00:00:0{}.000Z", id.len()),
+        };
+        insert_tool_audit(&conn, &entry("a", "c1")).unwrap();
+        insert_tool_audit(&conn, &entry("bb", "c1")).unwrap();
+        insert_tool_audit(&conn, &entry("ccc", "c2")).unwrap();
+        let for_c1 = list_tool_audit(&conn, Some("c1"), 10).unwrap();
+        assert_eq!(for_c1[0], entry("bb", "c1"));
+        assert_eq!(for_c1.len(), 2);
+        assert_eq!(list_tool_audit(&conn, None, 2).unwrap().len(), 2);
+
+        delete_conversation(&conn, "c1").unwrap();
+        assert_eq!(list_tool_audit(&conn, None, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_workspace_folder_and_allowlist() {
+        let (conn, _tmp) = setup_test_db();
+        create_conversation(&conn, "c1", "Chat", None).unwrap();
+        assert_eq!(
+            get_conversation(&conn, "c1")
+                .unwrap()
+                .unwrap()
+                .workspace_folder,
+            None
+        );
+        assert!(set_workspace_folder(&conn, "c1", Some("/home/me/project")).unwrap());
+        assert!(!set_workspace_folder(&conn, "missing", None).unwrap());
+        create_conversation(&conn, "c2", "Other", None).unwrap();
+        let mut ids = list_conversation_ids(&conn).unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["c1", "c2"]);
+        assert_eq!(
+            list_workspace_folders(&conn).unwrap(),
+            vec![("c1".to_string(), "/home/me/project".to_string())]
+        );
+        assert_eq!(
+            get_conversation(&conn, "c1")
+                .unwrap()
+                .unwrap()
+                .workspace_folder
+                .as_deref(),
+            Some("/home/me/project")
+        );
+
+        let folder = |path: &str, created_at: &str| TrustedFolder {
+            path: path.to_string(),
+            created_at: created_at.to_string(),
+        };
+        insert_trusted_folder(&conn, &folder("/home/me/project", "2026-01-01")).unwrap();
+        insert_trusted_folder(&conn, &folder("/home/me/project", "2026-02-01")).unwrap();
+        insert_trusted_folder(&conn, &folder("/data", "2026-03-01")).unwrap();
+        assert_eq!(
+            list_trusted_folders(&conn).unwrap(),
+            vec![
+                folder("/data", "2026-03-01"),
+                folder("/home/me/project", "2026-01-01")
+            ]
+        );
+        assert!(delete_trusted_folder(&conn, "/data").unwrap());
+        assert!(!delete_trusted_folder(&conn, "/data").unwrap());
+    }
+
+    #[test]
+    fn test_mcp_servers_crud() {
+        let (conn, _tmp) = setup_test_db();
+        let mut server = McpServer {
+            id: "s1".to_string(),
+            name: "files".to_string(),
+            enabled: true,
+            transport: McpTransport::Local {
+                command: "npx".to_string(),
+                args: vec!["fs-server".to_string()],
+                env: [("ROOT".to_string(), "/tmp".to_string())].into(),
+                cwd: None,
+            },
+            created_at: "2026-01-01T00:00:00.000Z".to_string(),
+        };
+        insert_mcp_server(&conn, &server).unwrap();
+        assert_eq!(list_mcp_servers(&conn).unwrap(), vec![server.clone()]);
+
+        // Names are unique
+        let duplicate = McpServer {
+            id: "s2".to_string(),
+            ..server.clone()
+        };
+        assert!(insert_mcp_server(&conn, &duplicate).is_err());
+
+        server.enabled = false;
+        server.transport = McpTransport::Remote {
+            url: "https://example.com/mcp".to_string(),
+            headers: Default::default(),
+        };
+        assert!(update_mcp_server(&conn, &server).unwrap());
+        assert_eq!(list_mcp_servers(&conn).unwrap(), vec![server]);
+
+        assert!(delete_mcp_server(&conn, "s1").unwrap());
+        assert!(!delete_mcp_server(&conn, "s1").unwrap());
+        assert!(list_mcp_servers(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_personas_crud_and_conversations() {
+        let (conn, _tmp) = setup_test_db();
+        let mut persona = Persona {
+            id: "p1".to_string(),
+            name: "Reviewer".to_string(),
+            system_prompt: Some("Review carefully".to_string()),
+            model: Some("gpt-4o".to_string()),
+            reasoning_effort: Some("high".to_string()),
+            tools: Some(vec!["read_file".to_string()]),
+            mcp_servers: None,
+            created_at: "2024-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2024-01-01T00:00:00.000Z".to_string(),
+        };
+        insert_persona(&conn, &persona).unwrap();
+        assert_eq!(list_personas(&conn).unwrap(), vec![persona.clone()]);
+        assert_eq!(get_persona(&conn, "p1").unwrap(), Some(persona.clone()));
+        let duplicate = Persona {
+            id: "p2".to_string(),
+            ..persona.clone()
+        };
+        assert!(insert_persona(&conn, &duplicate).is_err());
+
+        persona.mcp_servers = Some(vec!["server-1".to_string()]);
+        persona.system_prompt = None;
+        assert!(update_persona(&conn, &persona).unwrap());
+        assert_eq!(get_persona(&conn, "p1").unwrap(), Some(persona.clone()));
+
+        create_conversation(&conn, "c1", "Chat", None).unwrap();
+        assert_eq!(get_conversation_persona(&conn, "c1").unwrap(), None);
+        set_conversation_persona(&conn, "c1", &persona).unwrap();
+        let convo = get_conversation(&conn, "c1").unwrap().unwrap();
+        assert_eq!(convo.persona_id.as_deref(), Some("p1"));
+        assert_eq!(
+            get_conversation_persona(&conn, "c1").unwrap(),
+            Some(persona.clone())
+        );
+        assert_eq!(
+            get_disabled_tools(&conn, "c1").unwrap(),
+            persona.disabled_tools()
+        );
+        assert_eq!(list_persona_conversations(&conn, "p1").unwrap(), vec!["c1"]);
+
+        assert!(delete_persona(&conn, "p1").unwrap());
+        assert!(list_persona_conversations(&conn, "p1").unwrap().is_empty());
+        assert!(!delete_persona(&conn, "p1").unwrap());
+        assert_eq!(
+            get_conversation(&conn, "c1").unwrap().unwrap().persona_id,
+            None
+        );
+        assert!(list_personas(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prompt_templates_crud() {
+        let (conn, _tmp) = setup_test_db();
+        let mut template = PromptTemplate {
+            id: "t1".to_string(),
+            name: "review".to_string(),
+            description: None,
+            body: "Review {{diff}} for {{focus|bugs}}".to_string(),
+            variables: crate::templates::variables("Review {{diff}} for {{focus|bugs}}"),
+            created_at: "2024-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2024-01-01T00:00:00.000Z".to_string(),
+        };
+        insert_prompt_template(&conn, &template).unwrap();
+        assert_eq!(
+            list_prompt_templates(&conn).unwrap(),
+            vec![template.clone()]
+        );
+        assert_eq!(
+            get_prompt_template(&conn, "review").unwrap(),
+            Some(template.clone())
+        );
+        assert_eq!(
+            get_prompt_template(&conn, "t1").unwrap(),
+            Some(template.clone())
+        );
+        assert_eq!(get_prompt_template(&conn, "missing").unwrap(), None);
+
+        let duplicate = PromptTemplate {
+            id: "t2".to_string(),
+            ..template.clone()
+        };
+        assert!(insert_prompt_template(&conn, &duplicate).is_err());
+
+        template.body = "Summarize {{text}}".to_string();
+        template.variables = crate::templates::variables(&template.body);
+        template.description = Some("Short summary".to_string());
+        assert!(update_prompt_template(&conn, &template).unwrap());
+        assert_eq!(list_prompt_templates(&conn).unwrap(), vec![template]);
+
+        assert!(delete_prompt_template(&conn, "t1").unwrap());
+        assert!(!delete_prompt_template(&conn, "t1").unwrap());
+        assert!(list_prompt_templates(&conn).unwrap().is_empty());
+    }
+
     #[test]
     fn test_conversation_title_update() {
         let (conn, _tmp) = setup_test_db();
@@ -360,6 +2171,12 @@ mod tests {
             role: "user".to_string(),
             content: "Hello".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         save_message(&conn, &msg).unwrap();
 
@@ -413,6 +2230,12 @@ mod tests {
                 role: role.to_string(),
                 content: format!("Message {i}"),
                 created_at: format!("2026-01-01T00:00:0{i}Z"),
+                interrupted: false,
+                parent_id: None,
+                sibling_index: 0,
+                sibling_count: 0,
+                tool_calls: Vec::new(),
+                attachments: Vec::new(),
             };
             save_message(&conn, &msg).unwrap();
         }
@@ -494,6 +2317,12 @@ mod tests {
             role: "assistant".to_string(),
             content: "".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         save_message(&conn, &msg).unwrap();
 
@@ -516,6 +2345,12 @@ mod tests {
             role: "user".to_string(),
             content: "SELECT * FROM users WHERE name = 'Robert'; DROP TABLE users;--".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         save_message(&conn, &msg).unwrap();
 
@@ -537,6 +2372,12 @@ mod tests {
             role: "user".to_string(),
             content: "こんにちは世界 🌍 café résumé naïve".to_string(),
             created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
         };
         save_message(&conn, &msg).unwrap();
 
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use ts_rs::TS;
+
+const DEFAULT_IDLE_TIMEOUT_SECS: u32 = 120;
+
+/// Response time limits in seconds. `None` inherits the default (or, for a
+/// model override, the global value); `0` disables the limit.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, TS)]
+#[ts(export)]
+pub struct ResponseDeadlines {
+    /// Longest gap between two streamed events.
+    pub idle_timeout_secs: Option<u32>,
+    /// Longest wait for the first thinking or reply text.
+    pub first_token_timeout_secs: Option<u32>,
+    /// Longest a whole reply may take.
+    pub total_timeout_secs: Option<u32>,
+}
+
+/// Global response limits plus overrides keyed by model id, stored as the
+/// `response_deadlines` setting.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, TS)]
+#[ts(export)]
+pub struct DeadlineSettings {
+    #[serde(default)]
+    pub default: ResponseDeadlines,
+    #[serde(default)]
+    pub models: HashMap<String, ResponseDeadlines>,
+}
+
+/// Which limit ended a turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeadlineKind {
+    Idle,
+    FirstToken,
+    Total,
+}
+
+/// Limits in force for one turn; `None` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadlines {
+    pub idle: Option<Duration>,
+    pub first_token: Option<Duration>,
+    pub total: Option<Duration>,
+}
+
+/// Idle timeout used when none is configured. COPILOT_EVENT_TIMEOUT_SECS
+/// still overrides the built-in default.
+fn default_idle_secs() -> u32 {
+    std::env::var("COPILOT_EVENT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS)
+}
+
+fn limit(secs: u32) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs as u64))
+}
+
+impl Deadlines {
+    /// Resolves the limits for `model`: its override first, then the global
+    /// setting, then the defaults.
+    pub fn resolve(settings: &DeadlineSettings, model: Option<&str>) -> Self {
+        let model_override = model.and_then(|m| settings.models.get(m));
+        let pick = |field: fn(&ResponseDeadlines) -> Option<u32>| {
+            model_override
+                .and_then(field)
+                .or_else(|| field(&settings.default))
+        };
+        Self {
+            idle: limit(pick(|d| d.idle_timeout_secs).unwrap_or_else(default_idle_secs)),
+            first_token: pick(|d| d.first_token_timeout_secs).and_then(limit),
+            total: pick(|d| d.total_timeout_secs).and_then(limit),
+        }
+    }
+
+    /// How long to wait for the next event, and the limit that expires then.
+    /// `None` when no limit applies.
+    pub fn next_expiry(
+        &self,
+        now: Instant,
+        started: Instant,
+        last_event: Instant,
+        got_first_token: bool,
+    ) -> Option<(Duration, DeadlineKind)> {
+        let remaining = |since: Instant, limit: Duration| limit.saturating_sub(now - since);
+        [
+            self.idle
+                .map(|d| (remaining(last_event, d), DeadlineKind::Idle)),
+            self.first_token
+                .filter(|_| !got_first_token)
+                .map(|d| (remaining(started, d), DeadlineKind::FirstToken)),
+            self.total
+                .map(|d| (remaining(started, d), DeadlineKind::Total)),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by_key(|(wait, _)| *wait)
+    }
+
+    /// The configured length of `kind`, for error messages.
+    pub fn length(&self, kind: DeadlineKind) -> Duration {
+        match kind {
+            DeadlineKind::Idle => self.idle,
+            DeadlineKind::FirstToken => self.first_token,
+            DeadlineKind::Total => self.total,
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// The error shown when `kind` ends a turn. `partial` is whether some reply
+/// text had already streamed.
+pub fn error_message(kind: DeadlineKind, length: Duration, partial: bool) -> String {
+    match (kind, partial) {
+        (DeadlineKind::Idle, true) => format!(
+            "Response stalled for {:?} — partial content shown above.",
+            length
+        ),
+        (DeadlineKind::Idle, false) => format!(
+            "No response received within {:?}. The model may be processing a complex request — try again or raise the idle timeout in Settings.",
+            length
+        ),
+        (DeadlineKind::FirstToken, _) => format!(
+            "The model did not start answering within {:?}. Try again or raise the first-token timeout in Settings.",
+            length
+        ),
+        (DeadlineKind::Total, true) => format!(
+            "Response stopped at the {:?} response deadline — partial content shown above.",
+            length
+        ),
+        (DeadlineKind::Total, false) => format!(
+            "No complete response within the {:?} response deadline. Try again or raise it in Settings.",
+            length
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadlines(idle: u32, first: u32, total: u32) -> ResponseDeadlines {
+        ResponseDeadlines {
+            idle_timeout_secs: Some(idle),
+            first_token_timeout_secs: Some(first),
+            total_timeout_secs: Some(total),
+        }
+    }
+
+    #[test]
+    fn test_resolve_prefers_model_override() {
+        let mut settings = DeadlineSettings {
+            default: deadlines(60, 30, 600),
+            models: HashMap::new(),
+        };
+        settings.models.insert(
+            "slow-model".to_string(),
+            ResponseDeadlines {
+                first_token_timeout_secs: Some(0),
+                ..Default::default()
+            },
+        );
+
+        let fast = Deadlines::resolve(&settings, Some("gpt-4o"));
+        assert_eq!(fast.first_token, Some(Duration::from_secs(30)));
+
+        let slow = Deadlines::resolve(&settings, Some("slow-model"));
+        assert_eq!(slow.idle, Some(Duration::from_secs(60)));
+        assert_eq!(slow.first_token, None);
+        assert_eq!(slow.total, Some(Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn test_resolve_defaults() {
+        let resolved = Deadlines::resolve(&DeadlineSettings::default(), None);
+        assert!(resolved.idle.is_some());
+        assert_eq!(resolved.first_token, None);
+        assert_eq!(resolved.total, None);
+    }
+
+    #[test]
+    fn test_next_expiry_picks_nearest_limit() {
+        let resolved = Deadlines::resolve(
+            &DeadlineSettings {
+                default: deadlines(60, 30, 100),
+                models: HashMap::new(),
+            },
+            None,
+        );
+        let start = Instant::now();
+
+        // Before the first token the first-token limit is nearest
+        let (wait, kind) = resolved.next_expiry(start, start, start, false).unwrap();
+        assert_eq!(
+            (wait, kind),
+            (Duration::from_secs(30), DeadlineKind::FirstToken)
+        );
+
+        // Once streaming, idle counts from the last event
+        let now = start + Duration::from_secs(50);
+        let (wait, kind) = resolved.next_expiry(now, start, now, true).unwrap();
+        assert_eq!((wait, kind), (Duration::from_secs(50), DeadlineKind::Total));
+
+        let now = start + Duration::from_secs(20);
+        let (wait, kind) = resolved.next_expiry(now, start, now, true).unwrap();
+        assert_eq!((wait, kind), (Duration::from_secs(60), DeadlineKind::Idle));
+    }
+
+    #[test]
+    fn test_next_expiry_without_limits() {
+        let unlimited = Deadlines {
+            idle: None,
+            first_token: None,
+            total: None,
+        };
+        let now = Instant::now();
+        assert!(unlimited.next_expiry(now, now, now, false).is_none());
+    }
+}
@@ -0,0 +1,398 @@
+use quick_xml::events::Event;
+use std::io::{Cursor, Read};
+
+pub const PDF_MIME: &str = "application/pdf";
+pub const DOCX_MIME: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+pub const HTML_MIME: &str = "text/html";
+pub const NOTEBOOK_MIME: &str = "application/x-ipynb+json";
+
+/// Largest document that can be attached, in bytes, before extraction.
+pub const MAX_DOCUMENT_SIZE: u64 = 20 * 1024 * 1024;
+/// Rough number of characters per model token, for sizing document text.
+pub const CHARS_PER_TOKEN: usize = 4;
+/// Documents are sent in pieces of at most this many bytes.
+const CHUNK_BYTES: usize = 16_000;
+/// Room kept for each note about document text that was left out.
+const OMISSION_NOTE_BYTES: usize = 256;
+/// Largest `word/document.xml` read from a DOCX, against zip bombs.
+const MAX_DOCX_XML_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Whether attachments of `mime_type` are sent as extracted text.
+pub fn is_document(mime_type: &str) -> bool {
+    matches!(mime_type, PDF_MIME | DOCX_MIME | HTML_MIME | NOTEBOOK_MIME)
+}
+
+/// The document type of a file called `name` holding `data`, if it is one.
+pub fn detect(name: &str, data: &[u8]) -> Option<&'static str> {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    if data.starts_with(b"%PDF-") {
+        return Some(PDF_MIME);
+    }
+    match extension.as_str() {
+        "docx" if data.starts_with(b"PK\x03\x04") => Some(DOCX_MIME),
+        "html" | "htm" => Some(HTML_MIME),
+        "ipynb" => Some(NOTEBOOK_MIME),
+        _ => None,
+    }
+}
+
+/// Extracts the readable text of a document. Runs locally and can take a
+/// while for large PDFs, so call it off the async runtime.
+pub fn extract(mime_type: &str, data: &[u8]) -> Result<String, String> {
+    let text = match mime_type {
+        PDF_MIME => extract_pdf(data),
+        DOCX_MIME => extract_docx(data),
+        HTML_MIME => Ok(html_to_text(&String::from_utf8_lossy(data))),
+        NOTEBOOK_MIME => extract_notebook(data),
+        _ => Err(format!("{} is not a supported document type.", mime_type)),
+    }?;
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err("The document contains no text.".to_string());
+    }
+    Ok(text)
+}
+
+fn extract_pdf(data: &[u8]) -> Result<String, String> {
+    // The PDF parser panics on some malformed files
+    match std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(data)) {
+        Ok(Ok(text)) => Ok(text),
+        Ok(Err(e)) => {
+            tracing::warn!("Failed to extract PDF text: {}", e);
+            Err("The PDF could not be read.".to_string())
+        }
+        Err(_) => {
+            tracing::warn!("PDF parser panicked");
+            Err("The PDF could not be read.".to_string())
+        }
+    }
+}
+
+fn extract_docx(data: &[u8]) -> Result<String, String> {
+    let unreadable = |e: &dyn std::fmt::Display| {
+        tracing::warn!("Failed to read DOCX: {}", e);
+        "The Word document could not be read.".to_string()
+    };
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| unreadable(&e))?;
+    let entry = archive
+        .by_name("word/document.xml")
+        .map_err(|e| unreadable(&e))?;
+    let mut xml = String::new();
+    entry
+        .take(MAX_DOCX_XML_BYTES)
+        .read_to_string(&mut xml)
+        .map_err(|e| unreadable(&e))?;
+
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut text = String::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event().map_err(|e| unreadable(&e))? {
+            Event::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+            Event::End(e) => match e.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:p" => text.push('\n'),
+                _ => {}
+            },
+            Event::Empty(e) => match e.name().as_ref() {
+                b"w:tab" => text.push('\t'),
+                b"w:br" | b"w:cr" => text.push('\n'),
+                _ => {}
+            },
+            Event::Text(t) if in_text => {
+                text.push_str(&t.decode().map_err(|e| unreadable(&e))?);
+            }
+            Event::GeneralRef(r) if in_text => {
+                if let Ok(Some(c)) = r.resolve_char_ref() {
+                    text.push(c);
+                } else if let Ok(name) = r.decode() {
+                    if let Some(s) = quick_xml::escape::resolve_predefined_entity(&name) {
+                        text.push_str(s);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(text)
+}
+
+#[derive(serde::Deserialize)]
+struct Notebook {
+    cells: Vec<NotebookCell>,
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+#[derive(serde::Deserialize)]
+struct NotebookCell {
+    cell_type: String,
+    #[serde(default)]
+    source: NotebookSource,
+}
+
+/// Cell sources are stored either as one string or as a list of lines.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum NotebookSource {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Default for NotebookSource {
+    fn default() -> Self {
+        NotebookSource::Text(String::new())
+    }
+}
+
+fn extract_notebook(data: &[u8]) -> Result<String, String> {
+    let notebook: Notebook = serde_json::from_slice(data).map_err(|e| {
+        tracing::warn!("Failed to parse notebook: {}", e);
+        "The notebook could not be read.".to_string()
+    })?;
+    let language = notebook.metadata["language_info"]["name"]
+        .as_str()
+        .or_else(|| notebook.metadata["kernelspec"]["language"].as_str())
+        .unwrap_or("");
+    let mut text = String::new();
+    for cell in notebook.cells {
+        let source = match cell.source {
+            NotebookSource::Text(s) => s,
+            NotebookSource::Lines(lines) => lines.concat(),
+        };
+        if source.trim().is_empty() {
+            continue;
+        }
+        if cell.cell_type == "code" {
+            text.push_str(&format!("```{}\n{}\n```\n\n", language, source.trim_end()));
+        } else {
+            text.push_str(source.trim_end());
+            text.push_str("\n\n");
+        }
+    }
+    Ok(text)
+}
+
+/// Text of an HTML page: tags dropped, blocks on their own lines, scripts
+/// and styles removed and common entities decoded.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&decode_entities(&rest[..start]));
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if (name == "script" || name == "style") && !tag.starts_with('/') {
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(at) => &rest[at..],
+                None => "",
+            };
+        } else if is_block(&name) {
+            text.push('\n');
+        }
+    }
+    text.push_str(&decode_entities(rest));
+
+    // Collapse the whitespace of the markup while keeping paragraph breaks
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !line.is_empty() || lines.last().map_or(false, |l| !l.is_empty()) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Tags that start a new line of text.
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "div"
+            | "br"
+            | "li"
+            | "tr"
+            | "pre"
+            | "blockquote"
+            | "table"
+            | "ul"
+            | "ol"
+            | "section"
+            | "article"
+            | "header"
+            | "footer"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+    )
+}
+
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    s.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Splits `text` into pieces of at most `max_bytes`, preferring paragraph
+/// and then line breaks, never inside a UTF-8 character.
+pub fn chunks(text: &str, max_bytes: usize) -> Vec<&str> {
+    let max_bytes = max_bytes.max(1);
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            // A single character wider than `max_bytes`
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let window = &rest[..end];
+        let split = window
+            .rfind("\n\n")
+            .or_else(|| window.rfind('\n'))
+            .filter(|&at| at > 0)
+            .unwrap_or(end);
+        pieces.push(&rest[..split]);
+        rest = rest[split..].trim_start_matches('\n');
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+/// `prompt` followed by the text of each `(name, text)` document, in pieces
+/// marked with where they came from. Pieces that would take the message past
+/// `budget` bytes are left out, with a note saying so.
+pub fn render(prompt: &str, documents: &[(&str, &str)], budget: usize) -> String {
+    // Keep room for a note about every document
+    let budget = budget.saturating_sub(OMISSION_NOTE_BYTES * documents.len());
+    let mut message = prompt.to_string();
+    for (name, text) in documents {
+        let pieces = chunks(text, CHUNK_BYTES);
+        let total = pieces.len();
+        for (i, piece) in pieces.iter().enumerate() {
+            let block = format!(
+                "\n\n[Attached document {}, part {} of {}]\n{}\n[End of {}, part {} of {}]",
+                name,
+                i + 1,
+                total,
+                piece,
+                name,
+                i + 1,
+                total
+            );
+            if message.len() + block.len() > budget {
+                message.push_str(&format!(
+                    "\n\n[Parts {} to {} of {} were left out because they do not fit in one message.]",
+                    i + 1,
+                    total,
+                    name
+                ));
+                break;
+            }
+            message.push_str(&block);
+        }
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_detect_and_extract_documents() {
+        let html = b"<html><head><style>p{}</style><script>var x = '<p>';</script></head>\
+            <body><h1>Title</h1><p>Fish &amp; chips</p><p>Second   line</p></body></html>";
+        assert_eq!(detect("page.HTML", html), Some(HTML_MIME));
+        assert_eq!(
+            extract(HTML_MIME, html).unwrap(),
+            "Title\n\nFish & chips\n\nSecond line"
+        );
+
+        let notebook = br##"{"cells": [
+            {"cell_type": "markdown", "source": ["# Intro\n", "Some text"]},
+            {"cell_type": "code", "source": "print(1)"},
+            {"cell_type": "code", "source": []}
+        ], "metadata": {"language_info": {"name": "python"}}}"##;
+        assert_eq!(detect("a.ipynb", notebook), Some(NOTEBOOK_MIME));
+        assert_eq!(
+            extract(NOTEBOOK_MIME, notebook).unwrap(),
+            "# Intro\nSome text\n\n```python\nprint(1)\n```"
+        );
+
+        let mut docx = Cursor::new(Vec::new());
+        {
+            let mut zip = zip::ZipWriter::new(&mut docx);
+            zip.start_file(
+                "word/document.xml",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+            zip.write_all(
+                br#"<w:document><w:body><w:p><w:r><w:t>Hello</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">A &amp; B</w:t></w:r></w:p><w:p><w:r><w:t>Bye</w:t></w:r></w:p></w:body></w:document>"#,
+            )
+            .unwrap();
+            zip.finish().unwrap();
+        }
+        let docx = docx.into_inner();
+        assert_eq!(detect("report.docx", &docx), Some(DOCX_MIME));
+        assert_eq!(extract(DOCX_MIME, &docx).unwrap(), "Hello\tA & B\nBye");
+
+        assert_eq!(detect("notes.txt", b"plain"), None);
+        assert_eq!(detect("x.docx", b"not a zip"), None);
+        assert!(extract(PDF_MIME, b"%PDF-1.7 garbage").is_err());
+        assert!(extract(HTML_MIME, b"<p> </p>").is_err());
+    }
+
+    #[test]
+    fn test_chunks_and_render_respect_budget() {
+        let text = "alpha\n\nbeta beta\n\ngamma";
+        assert_eq!(chunks(text, 12), vec!["alpha", "beta beta", "gamma"]);
+        assert_eq!(chunks("ééé", 3), vec!["é", "é", "é"]);
+        assert_eq!(chunks("short", 100), vec!["short"]);
+
+        let rendered = render("Summarize", &[("a.pdf", "one")], 1_000);
+        assert!(rendered.starts_with("Summarize\n\n[Attached document a.pdf, part 1 of 1]\none\n"));
+
+        assert_eq!(chunks("é", 1), vec!["é"]);
+
+        let long = "word ".repeat(CHUNK_BYTES / 5 + 10);
+        let budget = CHUNK_BYTES + OMISSION_NOTE_BYTES + 150;
+        let rendered = render("Q", &[("big.pdf", &long)], budget);
+        assert!(rendered.len() <= budget);
+        assert!(rendered.contains("part 1 of 2]"));
+        assert!(!rendered.contains("part 2 of 2]"));
+        assert!(rendered.contains("Parts 2 to 2 of big.pdf were left out"));
+    }
+}
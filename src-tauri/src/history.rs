@@ -0,0 +1,192 @@
+use crate::commands::Message;
+
+/// Default number of characters of stored history replayed into a new session.
+pub const DEFAULT_CONTEXT_BUDGET_CHARS: usize = 48_000;
+
+const TRUNCATION_MARKER: &str = "\n…[truncated]";
+
+/// Stored history selected to seed a fresh session for an existing conversation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistorySeed {
+    /// Transcript block appended to the session's system message.
+    pub transcript: String,
+    /// Ids of the messages included in `transcript`, oldest first.
+    pub carried_over: Vec<String>,
+    /// Number of older messages left out to fit the budget.
+    pub omitted: usize,
+}
+
+fn role_label(role: &str) -> Option<&'static str> {
+    match role {
+        "user" => Some("User"),
+        "assistant" => Some("Assistant"),
+        _ => None,
+    }
+}
+
+/// Truncates `s` to at most `max` characters.
+fn truncate_chars(s: &str, max: usize) -> &str {
+    match s.char_indices().nth(max) {
+        Some((end, _)) => &s[..end],
+        None => s,
+    }
+}
+
+/// Selects the most recent messages that fit in `budget_chars` and renders them
+/// as a transcript the model can pick up from.
+///
+/// Messages are kept whole, newest first, until the budget is exhausted. If even
+/// the newest message does not fit it is truncated rather than dropped, so the
+/// model always sees the last turn. Returns `None` when there is nothing to seed.
+pub fn build_seed(messages: &[Message], budget_chars: usize) -> Option<HistorySeed> {
+    if budget_chars == 0 {
+        return None;
+    }
+
+    let candidates: Vec<(&Message, &str)> = messages
+        .iter()
+        .filter(|m| !m.content.trim().is_empty())
+        .filter_map(|m| role_label(&m.role).map(|label| (m, label)))
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let mut used = 0;
+    let mut entries: Vec<(String, String)> = Vec::new();
+    for (msg, label) in candidates.iter().rev() {
+        let entry = format!("{}: {}", label, msg.content.trim());
+        let chars = entry.chars().count();
+        if used + chars <= budget_chars {
+            used += chars;
+            entries.push((msg.id.clone(), entry));
+        } else {
+            if entries.is_empty() {
+                let keep = budget_chars.saturating_sub(TRUNCATION_MARKER.chars().count());
+                let truncated = format!("{}{}", truncate_chars(&entry, keep), TRUNCATION_MARKER);
+                entries.push((msg.id.clone(), truncated));
+            }
+            break;
+        }
+    }
+    entries.reverse();
+
+    let omitted = candidates.len() - entries.len();
+    let mut transcript = String::from(
+        "The conversation below took place earlier and was restored from local history. \
+         Continue it naturally without repeating it.\n",
+    );
+    if omitted > 0 {
+        transcript.push_str(&format!(
+            "({omitted} earlier message(s) were left out to fit the context budget.)\n"
+        ));
+    }
+    transcript.push_str("<conversation_history>\n");
+    for (_, entry) in &entries {
+        transcript.push_str(entry);
+        transcript.push_str("\n\n");
+    }
+    transcript.push_str("</conversation_history>");
+
+    Some(HistorySeed {
+        transcript,
+        carried_over: entries.into_iter().map(|(id, _)| id).collect(),
+        omitted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(id: &str, role: &str, content: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            conversation_id: "c1".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_build_seed_empty_history() {
+        assert!(build_seed(&[], 1000).is_none());
+        assert!(build_seed(&[msg("m1", "user", "   ")], 1000).is_none());
+        assert!(build_seed(&[msg("m1", "user", "Hi")], 0).is_none());
+    }
+
+    #[test]
+    fn test_build_seed_keeps_everything_within_budget() {
+        let history = vec![
+            msg("m1", "user", "Hello"),
+            msg("m2", "assistant", "Hi there!"),
+        ];
+        let seed = build_seed(&history, 1000).unwrap();
+        assert_eq!(seed.carried_over, vec!["m1", "m2"]);
+        assert_eq!(seed.omitted, 0);
+        assert!(seed.transcript.contains("User: Hello"));
+        assert!(seed.transcript.contains("Assistant: Hi there!"));
+        assert!(seed.transcript.find("User: Hello") < seed.transcript.find("Assistant: Hi"));
+    }
+
+    #[test]
+    fn test_build_seed_drops_oldest_first() {
+        let history = vec![
+            msg("m1", "user", &"a".repeat(100)),
+            msg("m2", "assistant", &"b".repeat(100)),
+            msg("m3", "user", &"c".repeat(100)),
+        ];
+        let seed = build_seed(&history, 250).unwrap();
+        assert_eq!(seed.carried_over, vec!["m2", "m3"]);
+        assert_eq!(seed.omitted, 1);
+        assert!(seed.transcript.contains("1 earlier message(s)"));
+    }
+
+    #[test]
+    fn test_build_seed_truncates_oversized_last_turn() {
+        let history = vec![
+            msg("m1", "user", "short"),
+            msg("m2", "assistant", &"é".repeat(500)),
+        ];
+        let seed = build_seed(&history, 100).unwrap();
+        assert_eq!(seed.carried_over, vec!["m2"]);
+        assert_eq!(seed.omitted, 1);
+        assert!(seed.transcript.contains("[truncated]"));
+        let entry = seed
+            .transcript
+            .lines()
+            .find(|l| l.starts_with("Assistant:"));
+        assert_eq!(
+            entry.unwrap().chars().count(),
+            100 - TRUNCATION_MARKER.chars().count()
+        );
+    }
+
+    #[test]
+    fn test_build_seed_budget_counts_characters() {
+        // 106 characters but 206 bytes each; both fit in 250 characters
+        let history = vec![
+            msg("m1", "user", &"é".repeat(100)),
+            msg("m2", "assistant", &"ü".repeat(100)),
+        ];
+        let seed = build_seed(&history, 250).unwrap();
+        assert_eq!(seed.carried_over, vec!["m1", "m2"]);
+        assert_eq!(seed.omitted, 0);
+    }
+
+    #[test]
+    fn test_build_seed_skips_system_messages() {
+        let history = vec![msg("s1", "system", "internal"), msg("m1", "user", "Hello")];
+        let seed = build_seed(&history, 1000).unwrap();
+        assert_eq!(seed.carried_over, vec!["m1"]);
+        assert_eq!(seed.omitted, 0);
+        assert!(!seed.transcript.contains("internal"));
+    }
+}
@@ -1,7 +1,24 @@
+mod attachments;
+mod coalesce;
 mod commands;
 mod db;
+mod deadlines;
+mod documents;
 pub mod error;
+mod history;
+mod mcp;
+mod permissions;
+mod personas;
+mod queue;
+mod reaper;
+mod retry;
+mod sandbox;
 mod state;
+mod supervisor;
+mod templates;
+mod tool_log;
+mod tools;
+mod workspace;
 
 use state::AppState;
 use tauri::Manager;
@@ -62,13 +79,47 @@ pub fn run() {
                 .expect("Failed to get app data dir");
             std::fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
             let db_path = app_dir.join("copilot-desktop.db");
+            let sandbox_root = sandbox::init(&app_dir)?;
 
             let conn = db::open_db(db_path.to_str().unwrap()).expect("Failed to open database");
             db::init_schema(&conn).expect("Failed to init schema");
 
+            // Attachments of prompts that were never sent, or of deleted
+            // conversations, are no longer referenced by any message
+            match db::prune_attachment_data(&conn) {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Removed {} unused attachment(s)", n),
+                Err(e) => tracing::error!("Failed to prune attachments: {}", e),
+            }
+
+            // Sandboxes of conversations deleted while the app crashed, and
+            // archives that expired while it was not running
+            match db::list_conversation_ids(&conn) {
+                Ok(ids) => {
+                    let retention = commands::sandbox_retention(&conn);
+                    tauri::async_runtime::spawn_blocking(move || {
+                        let removed = sandbox::sweep(
+                            &sandbox_root,
+                            &ids.into_iter().collect(),
+                            retention,
+                            std::time::SystemTime::now(),
+                        );
+                        if removed > 0 {
+                            tracing::info!("Removed {} stale sandbox(es)", removed);
+                        }
+                    });
+                }
+                Err(e) => {
+                    tracing::error!("Failed to list conversations for the sandbox sweep: {}", e)
+                }
+            }
+
             let state = app.state::<AppState>();
             *state.db.blocking_lock() = Some(conn);
 
+            reaper::spawn(app.handle().clone());
+            permissions::spawn(app.handle().clone());
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -80,13 +131,47 @@ pub fn run() {
             commands::create_session,
             commands::destroy_session,
             commands::send_message,
+            commands::abort_message,
+            commands::list_queued_prompts,
+            commands::reorder_queued_prompt,
+            commands::cancel_queued_prompt,
+            commands::regenerate_message,
+            commands::edit_message,
+            commands::switch_branch,
             commands::list_conversations,
             commands::get_conversation,
             commands::create_conversation,
+            commands::fork_conversation,
             commands::delete_conversation,
             commands::save_message,
             commands::get_settings,
             commands::update_settings,
+            commands::answer_permission,
+            commands::list_permission_rules,
+            commands::revoke_permission_rule,
+            commands::list_builtin_tools,
+            commands::set_conversation_tool_enabled,
+            commands::list_tool_audit,
+            commands::list_trusted_folders,
+            commands::add_trusted_folder,
+            commands::remove_trusted_folder,
+            commands::set_conversation_workspace,
+            commands::list_mcp_servers,
+            commands::add_mcp_server,
+            commands::update_mcp_server,
+            commands::delete_mcp_server,
+            commands::import_cli_mcp_servers,
+            commands::test_mcp_server,
+            commands::list_personas,
+            commands::add_persona,
+            commands::update_persona,
+            commands::delete_persona,
+            commands::list_prompt_templates,
+            commands::add_prompt_template,
+            commands::update_prompt_template,
+            commands::delete_prompt_template,
+            commands::render_template,
+            commands::expand_slash_command,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
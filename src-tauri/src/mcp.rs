@@ -0,0 +1,648 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+use ts_rs::TS;
+
+/// How long `test_server` waits for a server to start and list its tools.
+const TEST_TIMEOUT: Duration = Duration::from_secs(20);
+/// Pages of `tools/list` results read before giving up on a server.
+const MAX_TOOL_PAGES: usize = 10;
+/// Most bytes of a local server's stderr kept for error messages.
+const STDERR_TAIL_BYTES: usize = 8 * 1024;
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// How the CLI reaches an MCP server.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum McpTransport {
+    /// A process started by the CLI that speaks MCP over stdio.
+    Local {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        #[serde(default)]
+        cwd: Option<String>,
+    },
+    /// A server reached over HTTP.
+    Remote {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+}
+
+/// An MCP server the user configured.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct McpServer {
+    pub id: String,
+    /// Unique name, also the key the CLI knows the server by.
+    pub name: String,
+    /// Disabled servers are kept but not given to new sessions.
+    pub enabled: bool,
+    pub transport: McpTransport,
+    pub created_at: String,
+}
+
+/// Fields of an MCP server the user can edit.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct McpServerInput {
+    pub name: String,
+    pub enabled: bool,
+    pub transport: McpTransport,
+}
+
+/// A tool reported by an MCP server.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Result of importing the CLI's MCP config.
+#[derive(serde::Serialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct McpImport {
+    pub imported: Vec<McpServer>,
+    /// Names left out because a server with that name exists or the entry
+    /// could not be read.
+    pub skipped: Vec<String>,
+}
+
+impl McpServerInput {
+    /// Trims the fields and checks that the server can be started.
+    pub fn validate(mut self) -> Result<Self, String> {
+        self.name = self.name.trim().to_string();
+        if self.name.is_empty() {
+            return Err("Server name is required.".to_string());
+        }
+        if !self
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err("Server names may only use letters, digits, '-' and '_'.".to_string());
+        }
+        match &mut self.transport {
+            McpTransport::Local {
+                command, args, env, ..
+            } => {
+                *command = command.trim().to_string();
+                if command.is_empty() {
+                    return Err("A command is required for a local server.".to_string());
+                }
+                args.retain(|a| !a.is_empty());
+                if env.keys().any(|k| k.is_empty() || k.contains('=')) {
+                    return Err(
+                        "Environment variable names must not be empty or contain '='.".to_string(),
+                    );
+                }
+            }
+            McpTransport::Remote { url, headers } => {
+                *url = url.trim().to_string();
+                if !(url.starts_with("https://") || url.starts_with("http://")) {
+                    return Err("The server URL must start with http:// or https://.".to_string());
+                }
+                if headers.keys().any(|k| k.trim().is_empty()) {
+                    return Err("Header names must not be empty.".to_string());
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Where the Copilot CLI keeps its MCP config: `$XDG_CONFIG_HOME/.copilot`
+/// when set, otherwise `~/.copilot`.
+pub fn cli_config_path() -> Option<PathBuf> {
+    let base = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .or_else(|| std::env::var("HOME").ok())
+        .or_else(|| std::env::var("USERPROFILE").ok())?;
+    Some(PathBuf::from(base).join(".copilot").join("mcp-config.json"))
+}
+
+/// Reads the `mcpServers` entries of the CLI's config. Entries that cannot be
+/// understood are returned by name in the second list.
+pub fn parse_cli_config(text: &str) -> Result<(Vec<McpServerInput>, Vec<String>), String> {
+    let root: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| format!("Invalid MCP config: {}", e))?;
+    let Some(entries) = root.get("mcpServers").and_then(|v| v.as_object()) else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    let mut servers = Vec::new();
+    let mut skipped = Vec::new();
+    for (name, entry) in entries {
+        match cli_entry(name, entry) {
+            Some(server) => servers.push(server),
+            None => skipped.push(name.clone()),
+        }
+    }
+    Ok((servers, skipped))
+}
+
+fn cli_entry(name: &str, entry: &serde_json::Value) -> Option<McpServerInput> {
+    let strings = |key: &str| -> BTreeMap<String, String> {
+        entry
+            .get(key)
+            .and_then(|v| v.as_object())
+            .map(|map| {
+                map.iter()
+                    .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let kind = entry.get("type").and_then(|v| v.as_str());
+    let command = entry.get("command").and_then(|v| v.as_str());
+    let url = entry.get("url").and_then(|v| v.as_str());
+    let transport = match (kind, command, url) {
+        (Some("local" | "stdio") | None, Some(command), _) => McpTransport::Local {
+            command: command.to_string(),
+            args: entry
+                .get("args")
+                .and_then(|v| v.as_array())
+                .map(|args| {
+                    args.iter()
+                        .filter_map(|a| a.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            env: strings("env"),
+            cwd: entry
+                .get("cwd")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+        },
+        (Some("http" | "sse") | None, _, Some(url)) => McpTransport::Remote {
+            url: url.to_string(),
+            headers: strings("headers"),
+        },
+        _ => return None,
+    };
+    McpServerInput {
+        name: name.to_string(),
+        enabled: true,
+        transport,
+    }
+    .validate()
+    .ok()
+}
+
+/// The enabled servers in the form `SessionConfig` takes them, keyed by name.
+pub fn session_servers(servers: &[McpServer]) -> HashMap<String, copilot_sdk::McpServerConfig> {
+    servers
+        .iter()
+        .filter(|s| s.enabled)
+        .map(|s| {
+            let config = match &s.transport {
+                McpTransport::Local {
+                    command,
+                    args,
+                    env,
+                    cwd,
+                } => copilot_sdk::McpServerConfig::Local(copilot_sdk::McpLocalServerConfig {
+                    tools: vec!["*".to_string()],
+                    command: command.clone(),
+                    args: args.clone(),
+                    env: Some(env.clone().into_iter().collect()),
+                    cwd: cwd.clone(),
+                    ..Default::default()
+                }),
+                McpTransport::Remote { url, headers } => {
+                    copilot_sdk::McpServerConfig::Remote(copilot_sdk::McpRemoteServerConfig {
+                        tools: vec!["*".to_string()],
+                        url: url.clone(),
+                        headers: Some(headers.clone().into_iter().collect()),
+                        ..Default::default()
+                    })
+                }
+            };
+            (s.name.clone(), config)
+        })
+        .collect()
+}
+
+/// Starts or connects to the server, runs the MCP handshake and returns the
+/// tools it reports.
+pub async fn test_server(transport: &McpTransport) -> Result<Vec<McpToolInfo>, String> {
+    let listing = async {
+        match transport {
+            McpTransport::Local {
+                command,
+                args,
+                env,
+                cwd,
+            } => list_local_tools(command, args, env, cwd.as_deref()).await,
+            McpTransport::Remote { url, headers } => list_remote_tools(url, headers).await,
+        }
+    };
+    tokio::time::timeout(TEST_TIMEOUT, listing)
+        .await
+        .map_err(|_| {
+            format!(
+                "The server did not answer within {} seconds.",
+                TEST_TIMEOUT.as_secs()
+            )
+        })?
+}
+
+fn request(id: u64, method: &str, params: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn initialize_request() -> serde_json::Value {
+    request(
+        1,
+        "initialize",
+        serde_json::json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "copilot-desktop", "version": env!("CARGO_PKG_VERSION") }
+        }),
+    )
+}
+
+fn initialized_notification() -> serde_json::Value {
+    serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })
+}
+
+fn list_tools_request(id: u64, cursor: Option<&str>) -> serde_json::Value {
+    let params = match cursor {
+        Some(cursor) => serde_json::json!({ "cursor": cursor }),
+        None => serde_json::json!({}),
+    };
+    request(id, "tools/list", params)
+}
+
+/// The result of the JSON-RPC response to request `id`, if `message` is it.
+fn response_to(message: &serde_json::Value, id: u64) -> Option<Result<serde_json::Value, String>> {
+    if message.get("id").and_then(|v| v.as_u64()) != Some(id) || message.get("method").is_some() {
+        return None;
+    }
+    if let Some(error) = message.get("error") {
+        let text = error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("unknown error");
+        return Some(Err(format!("The server returned an error: {}", text)));
+    }
+    Some(Ok(message.get("result").cloned().unwrap_or_default()))
+}
+
+/// Adds one page of `tools/list` results to `tools`; returns the next cursor.
+fn collect_tools(result: &serde_json::Value, tools: &mut Vec<McpToolInfo>) -> Option<String> {
+    if let Some(list) = result.get("tools").and_then(|t| t.as_array()) {
+        tools.extend(
+            list.iter()
+                .filter_map(|t| serde_json::from_value::<McpToolInfo>(t.clone()).ok()),
+        );
+    }
+    result
+        .get("nextCursor")
+        .and_then(|c| c.as_str())
+        .map(str::to_string)
+}
+
+/// Reads `stderr` until it closes, so a server logging more than a pipe
+/// buffer never blocks on it, and returns its last `STDERR_TAIL_BYTES`.
+fn drain_stderr<R>(mut stderr: R) -> tokio::task::JoinHandle<Vec<u8>>
+where
+    R: tokio::io::AsyncRead + Unpin + Send + 'static,
+{
+    use tokio::io::AsyncReadExt;
+    tokio::spawn(async move {
+        let mut tail = Vec::new();
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = stderr.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+            tail.extend_from_slice(&buf[..n]);
+            if tail.len() > STDERR_TAIL_BYTES {
+                tail.drain(..tail.len() - STDERR_TAIL_BYTES);
+            }
+        }
+        tail
+    })
+}
+
+async fn list_local_tools(
+    command: &str,
+    args: &[String],
+    env: &BTreeMap<String, String>,
+    cwd: Option<&str>,
+) -> Result<Vec<McpToolInfo>, String> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let mut cmd = tokio::process::Command::new(command);
+    cmd.args(args)
+        .envs(env)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(cwd) = cwd {
+        cmd.current_dir(cwd);
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", command, e))?;
+    let mut stdin = child.stdin.take().ok_or("Failed to open server input.")?;
+    let stdout = child.stdout.take().ok_or("Failed to open server output.")?;
+    let stderr = child.stderr.take().map(drain_stderr);
+    let mut lines = BufReader::new(stdout).lines();
+
+    let outcome = async {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        for id in 1..=(MAX_TOOL_PAGES as u64 + 1) {
+            let message = match id {
+                1 => initialize_request(),
+                _ => list_tools_request(id, cursor.as_deref()),
+            };
+            stdin
+                .write_all(format!("{}\n", message).as_bytes())
+                .await
+                .map_err(|e| format!("Failed to write to the server: {}", e))?;
+            // Skip notifications and log lines the server prints to stdout
+            let result = loop {
+                let line = lines
+                    .next_line()
+                    .await
+                    .map_err(|e| format!("Failed to read from the server: {}", e))?
+                    .ok_or("The server exited.")?;
+                let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+                    continue;
+                };
+                if let Some(result) = response_to(&message, id) {
+                    break result?;
+                }
+            };
+            if id == 1 {
+                stdin
+                    .write_all(format!("{}\n", initialized_notification()).as_bytes())
+                    .await
+                    .map_err(|e| format!("Failed to write to the server: {}", e))?;
+                continue;
+            }
+            cursor = collect_tools(&result, &mut tools);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok::<_, String>(tools)
+    }
+    .await;
+
+    let _ = child.start_kill();
+    let Err(error) = outcome else {
+        if let Some(stderr) = stderr {
+            stderr.abort();
+        }
+        return outcome;
+    };
+    // What the server printed on stderr usually says why it failed
+    let mut output = Vec::new();
+    if let Some(mut stderr) = stderr {
+        match tokio::time::timeout(Duration::from_millis(500), &mut stderr).await {
+            Ok(Ok(tail)) => output = tail,
+            _ => stderr.abort(),
+        }
+    }
+    let output = String::from_utf8_lossy(&output);
+    match output.lines().rev().find(|l| !l.trim().is_empty()) {
+        Some(last) => Err(format!("{} {}", error, last.trim())),
+        None => Err(error),
+    }
+}
+
+/// Splits a `text/event-stream` body into the JSON messages of its events.
+fn parse_sse(body: &str) -> Vec<serde_json::Value> {
+    let mut messages = Vec::new();
+    let mut data = String::new();
+    for line in body.lines().chain(std::iter::once("")) {
+        if line.is_empty() {
+            if let Ok(message) = serde_json::from_str(&data) {
+                messages.push(message);
+            }
+            data.clear();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            if !data.is_empty() {
+                data.push('\n');
+            }
+            data.push_str(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    messages
+}
+
+/// A streamable HTTP connection to a remote server.
+struct RemoteSession<'a> {
+    client: reqwest::Client,
+    url: &'a str,
+    headers: &'a BTreeMap<String, String>,
+    session_id: Option<String>,
+}
+
+impl RemoteSession<'_> {
+    /// Posts `message`; for a request, returns the result of its response.
+    async fn post(&mut self, message: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let mut req = self
+            .client
+            .post(self.url)
+            .header(
+                reqwest::header::ACCEPT,
+                "application/json, text/event-stream",
+            )
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(message.to_string());
+        for (name, value) in self.headers {
+            req = req.header(name.as_str(), value.as_str());
+        }
+        if let Some(session_id) = &self.session_id {
+            req = req.header("mcp-session-id", session_id.as_str());
+        }
+        let response = req
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the server: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("The server answered with HTTP {}.", status));
+        }
+        if let Some(session_id) = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            self.session_id = Some(session_id.to_string());
+        }
+        let Some(id) = message.get("id").and_then(|v| v.as_u64()) else {
+            return Ok(serde_json::Value::Null);
+        };
+        let is_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read the server's answer: {}", e))?;
+        let messages = if is_stream {
+            parse_sse(&body)
+        } else {
+            match serde_json::from_str(&body) {
+                Ok(serde_json::Value::Array(batch)) => batch,
+                Ok(message) => vec![message],
+                Err(_) => Vec::new(),
+            }
+        };
+        messages
+            .iter()
+            .find_map(|m| response_to(m, id))
+            .unwrap_or_else(|| Err("The server's answer was not a valid MCP response.".to_string()))
+    }
+}
+
+async fn list_remote_tools(
+    url: &str,
+    headers: &BTreeMap<String, String>,
+) -> Result<Vec<McpToolInfo>, String> {
+    let mut remote = RemoteSession {
+        client: reqwest::Client::new(),
+        url,
+        headers,
+        session_id: None,
+    };
+    remote.post(&initialize_request()).await?;
+    remote.post(&initialized_notification()).await?;
+    let mut tools = Vec::new();
+    let mut cursor: Option<String> = None;
+    for id in 2..=(MAX_TOOL_PAGES as u64 + 1) {
+        let result = remote
+            .post(&list_tools_request(id, cursor.as_deref()))
+            .await?;
+        cursor = collect_tools(&result, &mut tools);
+        if cursor.is_none() {
+            break;
+        }
+    }
+    Ok(tools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(command: &str) -> McpTransport {
+        McpTransport::Local {
+            command: command.to_string(),
+            args: vec!["--stdio".to_string(), String::new()],
+            env: BTreeMap::new(),
+            cwd: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_server_input() {
+        let input = |name: &str, transport| McpServerInput {
+            name: name.to_string(),
+            enabled: true,
+            transport,
+        };
+        let ok = input(" github ", local(" gh-mcp ")).validate().unwrap();
+        assert_eq!(ok.name, "github");
+        assert_eq!(
+            ok.transport,
+            McpTransport::Local {
+                command: "gh-mcp".to_string(),
+                args: vec!["--stdio".to_string()],
+                env: BTreeMap::new(),
+                cwd: None,
+            }
+        );
+        assert!(input("", local("x")).validate().is_err());
+        assert!(input("has space", local("x")).validate().is_err());
+        assert!(input("empty", local("  ")).validate().is_err());
+        let remote = |url: &str| McpTransport::Remote {
+            url: url.to_string(),
+            headers: BTreeMap::new(),
+        };
+        assert!(input("web", remote("https://example.com/mcp"))
+            .validate()
+            .is_ok());
+        assert!(input("web", remote("ftp://example.com"))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_cli_config() {
+        let (servers, skipped) = parse_cli_config(
+            r#"{"mcpServers": {
+                "files": {"type": "local", "command": "npx", "args": ["-y", "fs-server"], "env": {"ROOT": "/tmp"}, "tools": ["*"]},
+                "docs": {"type": "http", "url": "https://docs.example.com/mcp", "headers": {"Authorization": "Bearer x"}},
+                "broken": {"type": "local"}
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(skipped, vec!["broken"]);
+        assert_eq!(servers.len(), 2);
+        let docs = servers.iter().find(|s| s.name == "docs").unwrap();
+        assert!(
+            matches!(&docs.transport, McpTransport::Remote { headers, .. } if headers["Authorization"] == "Bearer x")
+        );
+        let files = servers.iter().find(|s| s.name == "files").unwrap();
+        assert!(
+            matches!(&files.transport, McpTransport::Local { args, env, .. } if args.len() == 2 && env["ROOT"] == "/tmp")
+        );
+
+        assert!(parse_cli_config("{}").unwrap().0.is_empty());
+        assert!(parse_cli_config("not json").is_err());
+    }
+
+    #[test]
+    fn test_protocol_messages() {
+        let response = serde_json::json!({"jsonrpc": "2.0", "id": 2, "result": {
+            "tools": [{"name": "search", "description": "Find things", "inputSchema": {}}, {"name": "plain"}],
+            "nextCursor": "page-2"
+        }});
+        assert!(response_to(&response, 1).is_none());
+        let result = response_to(&response, 2).unwrap().unwrap();
+        let mut tools = Vec::new();
+        assert_eq!(
+            collect_tools(&result, &mut tools).as_deref(),
+            Some("page-2")
+        );
+        assert_eq!(tools[0].description.as_deref(), Some("Find things"));
+        assert_eq!(tools[1].name, "plain");
+
+        let error = serde_json::json!({"jsonrpc": "2.0", "id": 1, "error": {"code": -32601, "message": "nope"}});
+        assert!(response_to(&error, 1)
+            .unwrap()
+            .unwrap_err()
+            .contains("nope"));
+
+        let events = parse_sse("event: message\ndata: {\"id\": 1,\ndata: \"result\": {}}\n\n: ping\n\ndata: {\"id\": 2}\n");
+        assert_eq!(events.len(), 2);
+        assert!(response_to(&events[0], 1).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_stderr_tail_is_bounded() {
+        let mut log = "noise\n".repeat(STDERR_TAIL_BYTES).into_bytes();
+        log.extend_from_slice(b"error: missing API key\n");
+        let tail = drain_stderr(std::io::Cursor::new(log)).await.unwrap();
+        assert_eq!(tail.len(), STDERR_TAIL_BYTES);
+        assert!(String::from_utf8_lossy(&tail).ends_with("error: missing API key\n"));
+    }
+}
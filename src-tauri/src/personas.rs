@@ -0,0 +1,225 @@
+use crate::mcp::McpServer;
+use ts_rs::TS;
+
+/// Reasoning effort levels a persona can ask the model for.
+pub const REASONING_EFFORTS: &[&str] = &["low", "medium", "high"];
+/// Longest persona system prompt, in bytes.
+pub const MAX_SYSTEM_PROMPT_SIZE: usize = 100_000;
+const MAX_NAME_LENGTH: usize = 60;
+
+/// A reusable bundle of system prompt, model and tools a conversation can be
+/// started with.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct Persona {
+    pub id: String,
+    /// Unique name shown when starting a conversation.
+    pub name: String,
+    /// Replaces the system prompt of sessions in the persona's conversations.
+    pub system_prompt: Option<String>,
+    /// Model new conversations start with; the selected one when `None`.
+    pub model: Option<String>,
+    /// One of `REASONING_EFFORTS`; the model's default when `None`.
+    pub reasoning_effort: Option<String>,
+    /// Built-in tools turned on in new conversations; all of them when `None`.
+    pub tools: Option<Vec<String>>,
+    /// Ids of the MCP servers given to sessions, enabled or not; the enabled
+    /// servers when `None`.
+    pub mcp_servers: Option<Vec<String>>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Fields of a persona the user can edit.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct PersonaInput {
+    pub name: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    #[serde(default)]
+    pub mcp_servers: Option<Vec<String>>,
+}
+
+impl PersonaInput {
+    /// Trims the fields and checks the reasoning effort and tool names. MCP
+    /// server ids are checked against the configured servers by the caller.
+    pub fn validate(mut self) -> Result<Self, String> {
+        self.name = self.name.trim().to_string();
+        if self.name.is_empty() {
+            return Err("Persona name is required.".to_string());
+        }
+        if self.name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!(
+                "Persona names can be at most {} characters.",
+                MAX_NAME_LENGTH
+            ));
+        }
+        self.system_prompt = non_empty(self.system_prompt);
+        if self
+            .system_prompt
+            .as_ref()
+            .map_or(false, |p| p.len() > MAX_SYSTEM_PROMPT_SIZE)
+        {
+            return Err("System prompt is too long.".to_string());
+        }
+        self.model = non_empty(self.model);
+        self.reasoning_effort = non_empty(self.reasoning_effort).map(|e| e.to_ascii_lowercase());
+        if let Some(effort) = &self.reasoning_effort {
+            if !REASONING_EFFORTS.contains(&effort.as_str()) {
+                return Err(format!(
+                    "Reasoning effort must be one of {}.",
+                    REASONING_EFFORTS.join(", ")
+                ));
+            }
+        }
+        if let Some(tools) = &mut self.tools {
+            dedup(tools);
+            if let Some(unknown) = tools.iter().find(|t| {
+                !crate::tools::BUILTIN_TOOLS
+                    .iter()
+                    .any(|b| b.name == t.as_str())
+            }) {
+                return Err(format!("Unknown tool: {}", unknown));
+            }
+        }
+        if let Some(servers) = &mut self.mcp_servers {
+            dedup(servers);
+        }
+        Ok(self)
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Trims the names, drops empty ones and keeps the first of each.
+fn dedup(names: &mut Vec<String>) {
+    let mut seen = Vec::with_capacity(names.len());
+    for name in names.drain(..) {
+        let name = name.trim().to_string();
+        if !name.is_empty() && !seen.contains(&name) {
+            seen.push(name);
+        }
+    }
+    *names = seen;
+}
+
+impl Persona {
+    /// Built-in tools to turn off in a conversation started with this persona.
+    pub fn disabled_tools(&self) -> Vec<String> {
+        let Some(tools) = &self.tools else {
+            return Vec::new();
+        };
+        crate::tools::BUILTIN_TOOLS
+            .iter()
+            .filter(|b| !tools.iter().any(|t| t == b.name))
+            .map(|b| b.name.to_string())
+            .collect()
+    }
+
+    /// The servers of `servers` this persona's sessions get, marked enabled.
+    pub fn select_servers(&self, servers: Vec<McpServer>) -> Vec<McpServer> {
+        let Some(ids) = &self.mcp_servers else {
+            return servers;
+        };
+        servers
+            .into_iter()
+            .filter(|s| ids.contains(&s.id))
+            .map(|s| McpServer { enabled: true, ..s })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::McpTransport;
+
+    fn input(name: &str) -> PersonaInput {
+        PersonaInput {
+            name: name.to_string(),
+            system_prompt: Some("  ".to_string()),
+            model: Some(" gpt-4o ".to_string()),
+            reasoning_effort: Some("High".to_string()),
+            tools: Some(vec![
+                "read_file".to_string(),
+                " read_file ".to_string(),
+                String::new(),
+            ]),
+            mcp_servers: Some(vec!["s-github".to_string(), "s-github".to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_validate_persona_input() {
+        let ok = input(" Reviewer ").validate().unwrap();
+        assert_eq!(ok.name, "Reviewer");
+        assert_eq!(ok.system_prompt, None);
+        assert_eq!(ok.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(ok.reasoning_effort.as_deref(), Some("high"));
+        assert_eq!(ok.tools, Some(vec!["read_file".to_string()]));
+        assert_eq!(ok.mcp_servers, Some(vec!["s-github".to_string()]));
+
+        assert!(input(" ").validate().is_err());
+        assert!(input(&"x".repeat(MAX_NAME_LENGTH + 1)).validate().is_err());
+        let mut bad = input("Bad effort");
+        bad.reasoning_effort = Some("extreme".to_string());
+        assert!(bad.validate().is_err());
+        let mut bad = input("Bad tool");
+        bad.tools = Some(vec!["rm_rf".to_string()]);
+        assert!(bad.validate().is_err());
+        let mut long = input("Long");
+        long.system_prompt = Some("x".repeat(MAX_SYSTEM_PROMPT_SIZE + 1));
+        assert!(long.validate().is_err());
+    }
+
+    #[test]
+    fn test_persona_tools_and_servers() {
+        let input = input("Reviewer").validate().unwrap();
+        let mut persona = Persona {
+            id: "p1".to_string(),
+            name: input.name,
+            system_prompt: None,
+            model: None,
+            reasoning_effort: None,
+            tools: input.tools,
+            mcp_servers: input.mcp_servers,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let disabled = persona.disabled_tools();
+        assert!(!disabled.contains(&"read_file".to_string()));
+        assert_eq!(disabled.len(), crate::tools::BUILTIN_TOOLS.len() - 1);
+
+        let server = |name: &str, enabled: bool| McpServer {
+            id: format!("s-{}", name),
+            name: name.to_string(),
+            enabled,
+            transport: McpTransport::Remote {
+                url: "https://example.com/mcp".to_string(),
+                headers: Default::default(),
+            },
+            created_at: String::new(),
+        };
+        let servers = vec![server("github", false), server("jira", true)];
+        assert_eq!(
+            persona.select_servers(servers.clone()),
+            vec![server("github", true)]
+        );
+
+        persona.tools = None;
+        persona.mcp_servers = None;
+        assert!(persona.disabled_tools().is_empty());
+        assert_eq!(persona.select_servers(servers.clone()), servers);
+    }
+}
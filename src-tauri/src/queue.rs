@@ -0,0 +1,155 @@
+use crate::commands::Message;
+use std::collections::VecDeque;
+use ts_rs::TS;
+
+/// Most prompts that can wait behind a running turn in one session.
+pub const MAX_QUEUED_PROMPTS: usize = 20;
+
+/// A prompt waiting for its session to go idle, as reported to the UI.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct QueuedPrompt {
+    pub id: String,
+    pub content: String,
+    pub created_at: String,
+    /// 1-based position in the queue; 1 is sent next.
+    pub position: u32,
+}
+
+/// Prompts sent while a turn was still streaming, in the order they go out.
+/// Each prompt carries `T`, what its turn needs once it is sent.
+pub struct PromptQueue<T> {
+    items: VecDeque<(Message, T)>,
+}
+
+impl<T> Default for PromptQueue<T> {
+    fn default() -> Self {
+        Self {
+            items: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> PromptQueue<T> {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Appends `prompt` and returns its 1-based position.
+    pub fn push(&mut self, prompt: Message, extra: T) -> u32 {
+        self.items.push_back((prompt, extra));
+        self.items.len() as u32
+    }
+
+    pub fn pop(&mut self) -> Option<(Message, T)> {
+        self.items.pop_front()
+    }
+
+    /// Removes prompt `id`. Returns `false` when it is not queued, e.g. because
+    /// it has already been sent.
+    pub fn remove(&mut self, id: &str) -> bool {
+        match self.items.iter().position(|(m, _)| m.id == id) {
+            Some(index) => {
+                self.items.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves prompt `id` to the 1-based `position`, clamped to the queue.
+    /// Returns `false` when it is not queued.
+    pub fn move_to(&mut self, id: &str, position: u32) -> bool {
+        let Some(index) = self.items.iter().position(|(m, _)| m.id == id) else {
+            return false;
+        };
+        let Some(prompt) = self.items.remove(index) else {
+            return false;
+        };
+        let target = (position.max(1) as usize - 1).min(self.items.len());
+        self.items.insert(target, prompt);
+        true
+    }
+
+    pub fn snapshot(&self) -> Vec<QueuedPrompt> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(index, (m, _))| QueuedPrompt {
+                id: m.id.clone(),
+                content: m.content.clone(),
+                created_at: m.created_at.clone(),
+                position: index as u32 + 1,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(id: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            conversation_id: "c1".to_string(),
+            role: "user".to_string(),
+            content: format!("prompt {id}"),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            interrupted: false,
+            parent_id: None,
+            sibling_index: 0,
+            sibling_count: 0,
+            tool_calls: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    fn ids(queue: &PromptQueue<()>) -> Vec<String> {
+        queue.snapshot().into_iter().map(|p| p.id).collect()
+    }
+
+    #[test]
+    fn test_queue_is_fifo() {
+        let mut queue = PromptQueue::default();
+        assert_eq!(queue.push(prompt("a"), ()), 1);
+        assert_eq!(queue.push(prompt("b"), ()), 2);
+        assert_eq!(queue.pop().unwrap().0.id, "a");
+        assert_eq!(queue.pop().unwrap().0.id, "b");
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn test_queue_move_to_clamps_position() {
+        let mut queue = PromptQueue::<()>::default();
+        for id in ["a", "b", "c"] {
+            queue.push(prompt(id), ());
+        }
+        assert!(queue.move_to("c", 1));
+        assert_eq!(ids(&queue), vec!["c", "a", "b"]);
+        assert!(queue.move_to("c", 99));
+        assert_eq!(ids(&queue), vec!["a", "b", "c"]);
+        assert!(queue.move_to("b", 0));
+        assert_eq!(ids(&queue), vec!["b", "a", "c"]);
+        assert!(!queue.move_to("missing", 1));
+    }
+
+    #[test]
+    fn test_queue_remove_and_snapshot_positions() {
+        let mut queue = PromptQueue::<()>::default();
+        for id in ["a", "b", "c"] {
+            queue.push(prompt(id), ());
+        }
+        assert!(queue.remove("b"));
+        assert!(!queue.remove("b"));
+        let snapshot = queue.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[1].id, "c");
+        assert_eq!(snapshot[1].position, 2);
+        assert_eq!(snapshot[1].content, "prompt c");
+    }
+}
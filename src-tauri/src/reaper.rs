@@ -0,0 +1,210 @@
+use crate::state::AppState;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+/// Default time a session may sit idle before it is destroyed.
+pub const DEFAULT_SESSION_IDLE_TTL_SECS: u64 = 30 * 60;
+/// Default number of live sessions; the least recently used idle ones go first.
+pub const DEFAULT_MAX_SESSIONS: usize = 8;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictReason {
+    /// Unused for longer than the idle TTL.
+    Idle,
+    /// Least recently used when there were more sessions than allowed.
+    Limit,
+    /// The Copilot CLI was restarted and the session could not be resumed;
+    /// a replacement was opened from stored history if possible.
+    Restarted,
+}
+
+/// What the reaper needs to know about one session.
+pub struct Candidate<'a> {
+    pub session_id: &'a str,
+    pub last_used: Instant,
+    /// A reply is streaming or prompts are queued; never evicted.
+    pub busy: bool,
+}
+
+/// Picks the sessions to destroy: idle ones past `ttl` (`None` disables the
+/// TTL), then the least recently used until at most `max_sessions` remain.
+pub fn select_evictions(
+    candidates: &[Candidate],
+    now: Instant,
+    ttl: Option<Duration>,
+    max_sessions: usize,
+) -> Vec<(String, EvictReason)> {
+    let mut evicted = Vec::new();
+    let mut remaining: Vec<&Candidate> = Vec::new();
+    for candidate in candidates {
+        let expired = ttl.is_some_and(|ttl| now.duration_since(candidate.last_used) >= ttl);
+        if expired && !candidate.busy {
+            evicted.push((candidate.session_id.to_string(), EvictReason::Idle));
+        } else {
+            remaining.push(candidate);
+        }
+    }
+
+    let mut excess = remaining.len().saturating_sub(max_sessions);
+    remaining.sort_by_key(|c| c.last_used);
+    for candidate in remaining {
+        if excess == 0 {
+            break;
+        }
+        if !candidate.busy {
+            evicted.push((candidate.session_id.to_string(), EvictReason::Limit));
+            excess -= 1;
+        }
+    }
+    evicted
+}
+
+/// Starts the background task that destroys idle sessions, keeps the
+/// session count under the limit and expires archived sandboxes. It sweeps
+/// periodically and whenever a session is created.
+pub fn spawn(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let sessions_changed = app.state::<AppState>().sessions_changed.clone();
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(SWEEP_INTERVAL) => {}
+                _ = sessions_changed.notified() => {}
+            }
+            sweep(&app).await;
+        }
+    });
+}
+
+/// Idle TTL and session limit from the settings table.
+async fn limits(state: &AppState) -> (Option<Duration>, usize) {
+    let db_guard = state.db.lock().await;
+    let Some(conn) = db_guard.as_ref() else {
+        return (
+            Some(Duration::from_secs(DEFAULT_SESSION_IDLE_TTL_SECS)),
+            DEFAULT_MAX_SESSIONS,
+        );
+    };
+    let setting = |key: &str| -> Option<u64> {
+        crate::db::get_setting(conn, key)
+            .map_err(|e| tracing::error!("Failed to get setting '{}': {}", key, e))
+            .ok()
+            .flatten()
+            .and_then(|v| v.parse().ok())
+    };
+    let ttl = match setting("session_idle_ttl_secs").unwrap_or(DEFAULT_SESSION_IDLE_TTL_SECS) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let max_sessions = setting("max_sessions")
+        .map(|n| n.max(1) as usize)
+        .unwrap_or(DEFAULT_MAX_SESSIONS);
+    (ttl, max_sessions)
+}
+
+async fn sweep(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let (ttl, max_sessions) = limits(&state).await;
+
+    let mut sessions = state.sessions.write().await;
+    let mut bindings = state.conversation_sessions.write().await;
+    let candidates: Vec<Candidate> = sessions
+        .iter()
+        .map(|(session_id, info)| Candidate {
+            session_id,
+            last_used: info.last_used,
+            busy: info.turn_token.is_some() || !info.queue.is_empty(),
+        })
+        .collect();
+    let evictions = select_evictions(&candidates, Instant::now(), ttl, max_sessions);
+
+    for (session_id, reason) in evictions {
+        let Some(info) = sessions.remove(&session_id) else {
+            continue;
+        };
+        info.cancel_token.cancel();
+        if bindings.get(&info.conversation_id) == Some(&session_id) {
+            bindings.remove(&info.conversation_id);
+        }
+        tracing::info!(
+            "Session {} evicted from conversation {} ({:?})",
+            session_id,
+            info.conversation_id,
+            reason
+        );
+        emit_session_evicted(app, &session_id, &info.conversation_id, reason);
+    }
+    drop(bindings);
+    drop(sessions);
+
+    // The sandbox of an evicted session stays for the next one; only the
+    // archives of deleted conversations expire
+    crate::commands::expire_sandboxes(&state).await;
+}
+
+/// Tells the UI that `session_id` is gone so the next prompt opens a new one.
+pub fn emit_session_evicted(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    conversation_id: &str,
+    reason: EvictReason,
+) {
+    let _ = app.emit(
+        "copilot:session-evicted",
+        serde_json::json!({
+            "session_id": session_id,
+            "conversation_id": conversation_id,
+            "reason": reason,
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(session_id: &str, last_used: Instant, busy: bool) -> Candidate<'_> {
+        Candidate {
+            session_id,
+            last_used,
+            busy,
+        }
+    }
+
+    #[test]
+    fn test_select_evictions_reaps_expired_idle_sessions() {
+        let now = Instant::now();
+        let old = now - Duration::from_secs(600);
+        let candidates = [
+            candidate("old", old, false),
+            candidate("old-busy", old, true),
+            candidate("fresh", now, false),
+        ];
+        let evicted = select_evictions(&candidates, now, Some(Duration::from_secs(300)), 10);
+        assert_eq!(evicted, vec![("old".to_string(), EvictReason::Idle)]);
+
+        // Without a TTL nothing expires
+        assert!(select_evictions(&candidates, now, None, 10).is_empty());
+    }
+
+    #[test]
+    fn test_select_evictions_enforces_limit_lru_first() {
+        let now = Instant::now();
+        let candidates = [
+            candidate("newest", now, false),
+            candidate("oldest-busy", now - Duration::from_secs(30), true),
+            candidate("older", now - Duration::from_secs(20), false),
+            candidate("old", now - Duration::from_secs(10), false),
+        ];
+        let evicted = select_evictions(&candidates, now, None, 2);
+        assert_eq!(
+            evicted,
+            vec![
+                ("older".to_string(), EvictReason::Limit),
+                ("old".to_string(), EvictReason::Limit),
+            ]
+        );
+    }
+}
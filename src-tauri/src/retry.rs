@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+/// Default number of times a failed send is retried before giving up.
+pub const DEFAULT_SEND_RETRY_LIMIT: u32 = 3;
+
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(15);
+
+/// HTTP status codes of temporary failures, matched as whole words.
+const RETRYABLE_STATUS_CODES: &[&str] = &["429", "500", "502", "503", "504"];
+
+/// Phrases of SDK and API error messages that indicate a temporary problem.
+const RETRYABLE_MARKERS: &[&str] = &[
+    "rate limit",
+    "too many requests",
+    "overloaded",
+    "temporarily unavailable",
+    "service unavailable",
+    "bad gateway",
+    "gateway timeout",
+    "timed out",
+    "request timeout",
+    "connection reset",
+    "connection refused",
+    "connection closed",
+    "broken pipe",
+    "econnreset",
+    "econnrefused",
+    "etimedout",
+    "network error",
+    "network is unreachable",
+    "network unreachable",
+];
+
+/// Whether an error is worth retrying.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorClass {
+    Retryable,
+    Fatal,
+}
+
+/// Classifies an error by its message. Anything not recognised as transient
+/// (authentication, bad requests, content filtering, ...) is fatal.
+pub fn classify(message: &str) -> ErrorClass {
+    let message = message.to_lowercase();
+    let status = message
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| RETRYABLE_STATUS_CODES.contains(&word));
+    if status || RETRYABLE_MARKERS.iter().any(|m| message.contains(m)) {
+        ErrorClass::Retryable
+    } else {
+        ErrorClass::Fatal
+    }
+}
+
+/// Delay before retry `attempt` (1-based): exponential from 500ms, capped at
+/// 15s, with "full jitter" so concurrent sessions do not retry in lockstep.
+/// `jitter` is a sample in `[0, 1)`.
+pub fn backoff_delay(attempt: u32, jitter: f64) -> Duration {
+    let exp = attempt.saturating_sub(1).min(16);
+    let ceiling = BASE_DELAY.saturating_mul(1 << exp).min(MAX_DELAY);
+    ceiling.mul_f64(jitter.clamp(0.0, 1.0)).max(BASE_DELAY / 5)
+}
+
+/// A random sample in `[0, 1)` for `backoff_delay`.
+pub fn jitter() -> f64 {
+    // The low 53 bits of a v4 uuid are all random (version and variant bits sit higher)
+    let bits = uuid::Uuid::new_v4().as_u128() & ((1u128 << 53) - 1);
+    bits as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_transient_errors() {
+        assert_eq!(
+            classify("HTTP 429: Too Many Requests"),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            classify("upstream returned 503 Service Unavailable"),
+            ErrorClass::Retryable
+        );
+        assert_eq!(classify("Connection reset by peer"), ErrorClass::Retryable);
+        assert_eq!(classify("Request timed out"), ErrorClass::Retryable);
+        assert_eq!(classify("status=502"), ErrorClass::Retryable);
+        assert_eq!(
+            classify("Network error: ECONNREFUSED"),
+            ErrorClass::Retryable
+        );
+    }
+
+    #[test]
+    fn test_classify_fatal_errors() {
+        assert_eq!(classify("401 Unauthorized"), ErrorClass::Fatal);
+        assert_eq!(classify("Model not supported"), ErrorClass::Fatal);
+        assert_eq!(classify("Content filtered"), ErrorClass::Fatal);
+        // Status codes only count as whole words, and "network" or "timeout"
+        // alone say nothing about the failure being temporary
+        assert_eq!(
+            classify("Prompt limit is 1500 characters"),
+            ErrorClass::Fatal
+        );
+        assert_eq!(classify("Error code 45030"), ErrorClass::Fatal);
+        assert_eq!(classify("network policy denied"), ErrorClass::Fatal);
+        assert_eq!(classify("Invalid timeout value"), ErrorClass::Fatal);
+    }
+
+    #[test]
+    fn test_backoff_delay_grows_and_caps() {
+        assert_eq!(backoff_delay(1, 1.0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(3, 1.0), Duration::from_secs(2));
+        assert_eq!(backoff_delay(20, 1.0), MAX_DELAY);
+        // Jitter scales within the ceiling but never drops to zero
+        assert_eq!(backoff_delay(3, 0.5), Duration::from_secs(1));
+        assert_eq!(backoff_delay(3, 0.0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_jitter_in_unit_range() {
+        for _ in 0..100 {
+            let j = jitter();
+            assert!((0.0..1.0).contains(&j));
+        }
+    }
+}
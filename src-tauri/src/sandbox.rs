@@ -0,0 +1,307 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default time the sandbox of a deleted conversation is kept before it is
+/// deleted too.
+pub const DEFAULT_SANDBOX_RETENTION_HOURS: u64 = 24;
+
+const ROOT_DIR: &str = "sandbox";
+const CONVERSATIONS_DIR: &str = "conversations";
+const ARCHIVE_DIR: &str = "archive";
+
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Creates the folder holding every sandbox under the app data folder and
+/// makes it the one `root` returns. Refuses a folder that is a symlink or
+/// belongs to another user than `app_data_dir`.
+pub fn init(app_data_dir: &Path) -> io::Result<PathBuf> {
+    let root = app_data_dir.join(ROOT_DIR);
+    create_private_dir(&root)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let owner = std::fs::symlink_metadata(app_data_dir)?.uid();
+        if std::fs::symlink_metadata(&root)?.uid() != owner {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} belongs to another user", root.display()),
+            ));
+        }
+    }
+    Ok(ROOT.get_or_init(|| root).clone())
+}
+
+/// Folder holding every sandbox, and the CLI's own working directory.
+pub fn root() -> PathBuf {
+    ROOT.get()
+        .cloned()
+        .expect("sandbox::init runs when the app starts")
+}
+
+/// Sandbox of `conversation_id` under `root`. `None` for ids that are not
+/// safe to use as a folder name.
+pub fn conversation_dir(root: &Path, conversation_id: &str) -> Option<PathBuf> {
+    let safe = !conversation_id.is_empty()
+        && conversation_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    safe.then(|| root.join(CONVERSATIONS_DIR).join(conversation_id))
+}
+
+/// Creates `dir` and its parents, readable only by the current user. Fails
+/// when `dir` is a symlink rather than a folder.
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    if !is_real_dir(dir) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a folder", dir.display()),
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Creates the sandbox of `conversation_id` if needed and returns it.
+pub fn create(root: &Path, conversation_id: &str) -> io::Result<PathBuf> {
+    let dir = conversation_dir(root, conversation_id)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unsafe conversation id"))?;
+    create_private_dir(root)?;
+    create_private_dir(&root.join(CONVERSATIONS_DIR))?;
+    create_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Whether `path` is a folder itself, not a symlink to one.
+fn is_real_dir(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).map_or(false, |m| m.file_type().is_dir())
+}
+
+/// Clears the sandbox of a deleted conversation: deleted right away when
+/// `retention` is zero, otherwise moved to the archive until `expire` finds
+/// it older than `retention`.
+pub fn release(
+    root: &Path,
+    conversation_id: &str,
+    retention: Duration,
+    now: SystemTime,
+) -> io::Result<()> {
+    let Some(dir) = conversation_dir(root, conversation_id) else {
+        return Ok(());
+    };
+    if !is_real_dir(&dir) {
+        return Ok(());
+    }
+    if retention.is_zero() {
+        return std::fs::remove_dir_all(&dir);
+    }
+    // Attachments live in the encrypted database; never archive plaintext copies
+    discard_attachments(&dir);
+    let archive = root.join(ARCHIVE_DIR);
+    create_private_dir(&archive)?;
+    let stamp = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    std::fs::rename(&dir, archive.join(format!("{}.{}", conversation_id, stamp)))
+}
+
+/// Archived sandboxes as `(path, conversation id, archived at)`. Entries
+/// `release` did not create are left out.
+fn archived(root: &Path) -> Vec<(PathBuf, String, SystemTime)> {
+    let Ok(entries) = std::fs::read_dir(root.join(ARCHIVE_DIR)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| is_real_dir(&entry.path()))
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let (id, stamp) = name.rsplit_once('.')?;
+            conversation_dir(root, id)?;
+            let at = UNIX_EPOCH + Duration::from_secs(stamp.parse().ok()?);
+            Some((entry.path(), id.to_string(), at))
+        })
+        .collect()
+}
+
+/// Deletes attachments a session that ended mid-turn left in `dir`.
+fn discard_attachments(dir: &Path) {
+    let attachments = dir.join(crate::attachments::ATTACHMENTS_DIR);
+    if is_real_dir(&attachments) {
+        if let Err(e) = std::fs::remove_dir_all(&attachments) {
+            tracing::warn!("Failed to remove {}: {}", attachments.display(), e);
+        }
+    }
+}
+
+fn delete(path: &Path) -> bool {
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!("Failed to remove sandbox {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+/// Deletes archived sandboxes older than `retention`. Returns how many were
+/// deleted.
+pub fn expire(root: &Path, retention: Duration, now: SystemTime) -> usize {
+    archived(root)
+        .into_iter()
+        .filter(|(_, _, at)| {
+            now.duration_since(*at)
+                .map_or(false, |age| age >= retention)
+        })
+        .filter(|(path, _, _)| delete(path))
+        .count()
+}
+
+/// Deletes sandboxes nobody will use again: those of conversations that no
+/// longer exist and archived ones older than `retention`. Attachments left
+/// in live sandboxes by an interrupted turn are deleted too. Only folders named
+/// the way `create` and `release` name them are touched. Returns how many
+/// were deleted.
+pub fn sweep(
+    root: &Path,
+    conversations: &HashSet<String>,
+    retention: Duration,
+    now: SystemTime,
+) -> usize {
+    let mut removed = 0;
+    if let Ok(entries) = std::fs::read_dir(root.join(CONVERSATIONS_DIR)) {
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let ours = conversation_dir(root, &name).is_some() && is_real_dir(&entry.path());
+            if !ours {
+                continue;
+            }
+            if !conversations.contains(&name) {
+                removed += usize::from(delete(&entry.path()));
+            } else {
+                discard_attachments(&entry.path());
+            }
+        }
+    }
+    removed + expire(root, retention, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversation_dirs_are_private_and_safe() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = create(root.path(), "c-1").unwrap();
+        assert!(dir.is_dir());
+        assert!(dir.starts_with(root.path()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        assert!(conversation_dir(root.path(), "../escape").is_none());
+        assert!(conversation_dir(root.path(), "").is_none());
+        assert!(create(root.path(), "a/b").is_err());
+    }
+
+    #[test]
+    fn test_release_deletes_or_archives() {
+        let root = tempfile::tempdir().unwrap();
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        let dir = create(root.path(), "gone").unwrap();
+        std::fs::write(dir.join("notes.txt"), "x").unwrap();
+        release(root.path(), "gone", Duration::ZERO, now).unwrap();
+        assert!(!dir.exists());
+        assert!(archived(root.path()).is_empty());
+
+        let dir = create(root.path(), "kept").unwrap();
+        std::fs::write(dir.join("notes.txt"), "x").unwrap();
+        let attachments = dir.join(crate::attachments::ATTACHMENTS_DIR);
+        create_private_dir(&attachments).unwrap();
+        std::fs::write(attachments.join("photo.jpg"), "x").unwrap();
+        release(root.path(), "kept", Duration::from_secs(3600), now).unwrap();
+        assert!(!dir.exists());
+        let archive = archived(root.path());
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive[0].1, "kept");
+        assert_eq!(archive[0].2, now);
+        assert!(archive[0].0.join("notes.txt").exists());
+        assert!(!archive[0]
+            .0
+            .join(crate::attachments::ATTACHMENTS_DIR)
+            .exists());
+
+        // Releasing a conversation without a sandbox is a no-op
+        release(root.path(), "never", Duration::ZERO, now).unwrap();
+        let hour = Duration::from_secs(3600);
+        assert_eq!(expire(root.path(), hour, now + hour / 2), 0);
+        assert_eq!(expire(root.path(), hour, now + hour), 1);
+        assert!(archived(root.path()).is_empty());
+    }
+
+    #[test]
+    fn test_sweep_removes_orphans_and_expired_archives() {
+        let root = tempfile::tempdir().unwrap();
+        let hour = Duration::from_secs(3600);
+        let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        for id in ["live", "deleted", "old", "recent"] {
+            create(root.path(), id).unwrap();
+        }
+        release(root.path(), "old", hour, start).unwrap();
+        release(root.path(), "recent", hour, start + hour).unwrap();
+        std::fs::write(root.path().join("leftover.txt"), "x").unwrap();
+
+        let conversations: HashSet<String> = ["live", "old", "recent"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let removed = sweep(root.path(), &conversations, hour, start + hour + hour / 2);
+        assert_eq!(removed, 2);
+        assert!(conversation_dir(root.path(), "live").unwrap().exists());
+        assert!(!conversation_dir(root.path(), "deleted").unwrap().exists());
+        let archive: Vec<String> = archived(root.path()).into_iter().map(|a| a.1).collect();
+        assert_eq!(archive, vec!["recent"]);
+        // Files the app did not create are never touched
+        assert!(root.path().join("leftover.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_never_followed() {
+        let root = tempfile::tempdir().unwrap();
+        let victim = tempfile::tempdir().unwrap();
+        std::fs::write(victim.path().join("precious.txt"), "x").unwrap();
+
+        let app_dir = root.path().join("app");
+        std::fs::create_dir(&app_dir).unwrap();
+        std::os::unix::fs::symlink(victim.path(), app_dir.join(ROOT_DIR)).unwrap();
+        assert!(init(&app_dir).is_err());
+
+        let conversations = root.path().join(CONVERSATIONS_DIR);
+        std::fs::create_dir(&conversations).unwrap();
+        std::os::unix::fs::symlink(victim.path(), conversations.join("linked")).unwrap();
+        assert!(create(root.path(), "linked").is_err());
+        release(root.path(), "linked", Duration::ZERO, SystemTime::now()).unwrap();
+        assert_eq!(
+            sweep(
+                root.path(),
+                &HashSet::new(),
+                Duration::ZERO,
+                SystemTime::now()
+            ),
+            0
+        );
+        assert!(victim.path().join("precious.txt").exists());
+    }
+}
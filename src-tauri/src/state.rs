@@ -2,24 +2,44 @@ use copilot_sdk::{Client, Session};
 use rusqlite::Connection;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use std::time::Instant;
+use tauri::ipc::Channel;
+use tokio::sync::{Mutex, Notify, RwLock};
 use tokio_util::sync::CancellationToken;
 
 pub struct SessionInfo {
     pub session: Arc<Session>,
-    #[allow(dead_code)]
+    /// Conversation this session is bound to.
+    pub conversation_id: String,
     pub model: Option<String>,
-    #[allow(dead_code)]
     pub system_prompt: Option<String>,
     /// Token used to cancel the event-processing task when the session is destroyed.
     pub cancel_token: CancellationToken,
+    /// Token for the turn currently streaming, cancelled by `abort_message`.
+    /// It is a child of `cancel_token`, so destroying the session ends the turn too.
+    /// `None` while the session is idle.
+    pub turn_token: Option<CancellationToken>,
+    /// Prompts waiting for the current turn to finish, sent in order.
+    pub queue: crate::queue::PromptQueue<Channel<crate::commands::StreamEvent>>,
+    /// When the session was last handed out or finished a turn; the reaper
+    /// destroys idle sessions in least recently used order.
+    pub last_used: Instant,
 }
 
 pub struct AppState {
     pub client: Arc<RwLock<Option<Client>>>,
     pub sessions: Arc<RwLock<HashMap<String, SessionInfo>>>,
+    /// Maps a conversation id to the id of the session currently bound to it.
+    /// Lock `sessions` before this map whenever both are needed.
+    pub conversation_sessions: Arc<RwLock<HashMap<String, String>>>,
     pub db: Arc<Mutex<Option<Connection>>>,
     pub cached_models: RwLock<Option<Vec<crate::commands::ModelInfo>>>,
+    /// Wakes the session reaper when a session is created.
+    pub sessions_changed: Arc<Notify>,
+    /// Cancels the supervisor watching the running client.
+    pub supervisor: Mutex<Option<CancellationToken>>,
+    /// Permission requests waiting for the user.
+    pub permissions: Arc<crate::permissions::PermissionBroker>,
 }
 
 impl AppState {
@@ -27,8 +47,12 @@ impl AppState {
         Self {
             client: Arc::new(RwLock::new(None)),
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            conversation_sessions: Arc::new(RwLock::new(HashMap::new())),
             db: Arc::new(Mutex::new(None)),
             cached_models: RwLock::new(None),
+            sessions_changed: Arc::new(Notify::new()),
+            supervisor: Mutex::new(None),
+            permissions: Arc::new(crate::permissions::PermissionBroker::new()),
         }
     }
 }
@@ -0,0 +1,156 @@
+use crate::state::AppState;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_RESTART_ATTEMPTS: u32 = 5;
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// Lifecycle of the Copilot CLI process, reported with `copilot:client-status`.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientStatus {
+    Starting,
+    Ready,
+    /// The CLI stopped answering health checks.
+    Crashed,
+    Restarting,
+    /// The CLI could not be (re)started; `start_client` has to be called again.
+    Failed,
+}
+
+pub fn emit_status(
+    app: &tauri::AppHandle,
+    status: ClientStatus,
+    attempt: Option<u32>,
+    message: Option<String>,
+) {
+    let _ = app.emit(
+        "copilot:client-status",
+        serde_json::json!({
+            "status": status,
+            "attempt": attempt,
+            "message": message,
+        }),
+    );
+}
+
+/// Delay before restart `attempt` (1-based): 1s, doubling up to 30s.
+pub fn restart_delay(attempt: u32) -> Duration {
+    let exp = attempt.saturating_sub(1).min(16);
+    Duration::from_secs(1 << exp).min(MAX_RESTART_DELAY)
+}
+
+/// Starts watching the running client, replacing any earlier watcher. When
+/// the CLI stops answering it is restarted with backoff and the live sessions
+/// are restored on the new process.
+pub async fn watch(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let token = CancellationToken::new();
+    if let Some(previous) = state.supervisor.lock().await.replace(token.clone()) {
+        previous.cancel();
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => return,
+                _ = tokio::time::sleep(HEALTH_CHECK_INTERVAL) => {}
+            }
+            if is_healthy(&app).await || token.is_cancelled() {
+                continue;
+            }
+            tracing::warn!("Copilot CLI stopped responding; restarting it");
+            emit_status(&app, ClientStatus::Crashed, None, None);
+            if let Err(e) = restart(&app, &token).await {
+                tracing::error!("Giving up on the Copilot CLI: {}", e);
+                emit_status(&app, ClientStatus::Failed, None, Some(e));
+                return;
+            }
+        }
+    });
+}
+
+/// Stops the watcher so an intentional `stop_client` is not mistaken for a crash.
+pub async fn unwatch(state: &AppState) {
+    if let Some(token) = state.supervisor.lock().await.take() {
+        token.cancel();
+    }
+}
+
+async fn is_healthy(app: &tauri::AppHandle) -> bool {
+    let state = app.state::<AppState>();
+    let client_guard = state.client.read().await;
+    let Some(client) = client_guard.as_ref() else {
+        return false;
+    };
+    matches!(
+        tokio::time::timeout(HEALTH_CHECK_TIMEOUT, client.get_auth_status()).await,
+        Ok(Ok(_))
+    )
+}
+
+async fn restart(app: &tauri::AppHandle, token: &CancellationToken) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let previous = state.client.write().await.take();
+    if let Some(client) = previous {
+        if tokio::time::timeout(STOP_TIMEOUT, client.stop())
+            .await
+            .is_err()
+        {
+            tracing::warn!("Timed out stopping the unresponsive Copilot CLI");
+        }
+    }
+
+    let mut last_error = String::new();
+    for attempt in 1..=MAX_RESTART_ATTEMPTS {
+        emit_status(app, ClientStatus::Restarting, Some(attempt), None);
+        tokio::select! {
+            _ = token.cancelled() => return Ok(()),
+            _ = tokio::time::sleep(restart_delay(attempt)) => {}
+        }
+        match crate::commands::launch_client().await {
+            Ok(client) => {
+                *state.client.write().await = Some(client);
+                tracing::info!("Copilot CLI restarted (attempt {})", attempt);
+                emit_status(app, ClientStatus::Ready, None, None);
+                crate::commands::restore_sessions(app).await;
+                return Ok(());
+            }
+            Err(e) => {
+                tracing::warn!("Restart attempt {} failed: {}", attempt, e);
+                last_error = e;
+            }
+        }
+    }
+    Err(format!(
+        "Copilot CLI could not be restarted after {} attempts: {}",
+        MAX_RESTART_ATTEMPTS, last_error
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_delay_doubles_up_to_cap() {
+        assert_eq!(restart_delay(1), Duration::from_secs(1));
+        assert_eq!(restart_delay(2), Duration::from_secs(2));
+        assert_eq!(restart_delay(5), Duration::from_secs(16));
+        assert_eq!(restart_delay(6), MAX_RESTART_DELAY);
+        assert_eq!(restart_delay(100), MAX_RESTART_DELAY);
+    }
+
+    #[test]
+    fn test_client_status_serializes_snake_case() {
+        assert_eq!(
+            serde_json::to_value(ClientStatus::Restarting).unwrap(),
+            "restarting"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use ts_rs::TS;
+
+/// Longest template body, in bytes.
+pub const MAX_TEMPLATE_SIZE: usize = 64 * 1024;
+const MAX_NAME_LENGTH: usize = 40;
+
+/// A `{{name}}` or `{{name|default}}` placeholder in a template.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct TemplateVariable {
+    pub name: String,
+    /// Used when no value is given; `None` makes the variable required.
+    pub default: Option<String>,
+}
+
+/// A reusable prompt the user saved.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct PromptTemplate {
+    pub id: String,
+    /// Unique name, typed as `/name` in the message box.
+    pub name: String,
+    pub description: Option<String>,
+    pub body: String,
+    /// Placeholders of `body` in the order they first appear.
+    pub variables: Vec<TemplateVariable>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Fields of a prompt template the user can edit.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct PromptTemplateInput {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub body: String,
+}
+
+impl PromptTemplateInput {
+    /// Trims the fields and checks the name and the placeholders.
+    pub fn validate(mut self) -> Result<Self, String> {
+        self.name = self
+            .name
+            .trim()
+            .trim_start_matches('/')
+            .to_ascii_lowercase();
+        if self.name.is_empty() {
+            return Err("Template name is required.".to_string());
+        }
+        if self.name.len() > MAX_NAME_LENGTH || !is_name(&self.name) {
+            return Err(format!(
+                "Template names may only use letters, digits, '-' and '_', up to {} characters.",
+                MAX_NAME_LENGTH
+            ));
+        }
+        self.description = self
+            .description
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty());
+        if self.body.trim().is_empty() {
+            return Err("Template text is required.".to_string());
+        }
+        if self.body.len() > MAX_TEMPLATE_SIZE {
+            return Err("Template text is too long.".to_string());
+        }
+        parse(&self.body)?;
+        Ok(self)
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Variable(TemplateVariable),
+}
+
+/// Splits a template body into text and placeholders.
+fn parse(body: &str) -> Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        segments.push(Segment::Text(&rest[..start]));
+        let Some(end) = rest[start..].find("}}") else {
+            return Err("A {{ placeholder is never closed with }}.".to_string());
+        };
+        let inner = &rest[start + 2..start + end];
+        let (name, default) = match inner.split_once('|') {
+            Some((name, default)) => (name.trim(), Some(default.trim().to_string())),
+            None => (inner.trim(), None),
+        };
+        if !is_name(name) {
+            return Err(format!(
+                "{{{{{}}}}} is not a valid placeholder. Use {{{{name}}}} or {{{{name|default}}}}.",
+                inner
+            ));
+        }
+        segments.push(Segment::Variable(TemplateVariable {
+            name: name.to_string(),
+            default,
+        }));
+        rest = &rest[start + end + 2..];
+    }
+    segments.push(Segment::Text(rest));
+    Ok(segments)
+}
+
+/// The placeholders of `body` in the order they first appear. A variable
+/// used more than once takes the first default given for it.
+pub fn variables(body: &str) -> Vec<TemplateVariable> {
+    let mut found: Vec<TemplateVariable> = Vec::new();
+    for segment in parse(body).unwrap_or_default() {
+        let Segment::Variable(variable) = segment else {
+            continue;
+        };
+        match found.iter_mut().find(|v| v.name == variable.name) {
+            Some(existing) => {
+                if existing.default.is_none() {
+                    existing.default = variable.default;
+                }
+            }
+            None => found.push(variable),
+        }
+    }
+    found
+}
+
+/// Fills in the placeholders of `body`. Empty or missing values fall back to
+/// the variable's default; a required variable without a value is an error.
+pub fn render(body: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let defaults = variables(body);
+    let mut missing = Vec::new();
+    let mut out = String::with_capacity(body.len());
+    for segment in parse(body)? {
+        match segment {
+            Segment::Text(text) => out.push_str(text),
+            Segment::Variable(variable) => {
+                let value = values
+                    .get(&variable.name)
+                    .filter(|v| !v.trim().is_empty())
+                    .or_else(|| {
+                        defaults
+                            .iter()
+                            .find(|d| d.name == variable.name)
+                            .and_then(|d| d.default.as_ref())
+                    });
+                match value {
+                    Some(value) => out.push_str(value),
+                    None if !missing.contains(&variable.name) => missing.push(variable.name),
+                    None => {}
+                }
+            }
+        }
+    }
+    if !missing.is_empty() {
+        return Err(format!("Missing a value for {}.", missing.join(", ")));
+    }
+    Ok(out)
+}
+
+/// Splits `/name rest of the text` into the template name and the text.
+pub fn parse_slash_command(input: &str) -> Option<(String, &str)> {
+    let command = input.trim_start().strip_prefix('/')?;
+    let end = command.find(char::is_whitespace).unwrap_or(command.len());
+    let name = &command[..end];
+    if !is_name(name) {
+        return None;
+    }
+    Some((name.to_ascii_lowercase(), command[end..].trim()))
+}
+
+/// Values for a slash command: the text after the command fills the first
+/// variable, the others keep their defaults.
+pub fn slash_values(template: &PromptTemplate, text: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    if let Some(first) = template.variables.first() {
+        if !text.is_empty() {
+            values.insert(first.name.clone(), text.to_string());
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, body: &str) -> PromptTemplateInput {
+        PromptTemplateInput {
+            name: name.to_string(),
+            description: Some("  ".to_string()),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_template_input() {
+        let ok = input(" /Review ", "Review {{diff}}").validate().unwrap();
+        assert_eq!(ok.name, "review");
+        assert_eq!(ok.description, None);
+        assert!(input("", "x").validate().is_err());
+        assert!(input("two words", "x").validate().is_err());
+        assert!(input("blank", "  ").validate().is_err());
+        assert!(input("open", "Hi {{name").validate().is_err());
+        assert!(input("bad", "Hi {{first name}}").validate().is_err());
+        assert!(input("long", &"x".repeat(MAX_TEMPLATE_SIZE + 1))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_variables_and_render() {
+        let body = "Write {{kind|release notes}} for {{ version }} in {{tone | a friendly tone}}. \
+            Mention {{version}} and {{kind}} again.";
+        assert_eq!(
+            variables(body),
+            vec![
+                TemplateVariable {
+                    name: "kind".to_string(),
+                    default: Some("release notes".to_string()),
+                },
+                TemplateVariable {
+                    name: "version".to_string(),
+                    default: None,
+                },
+                TemplateVariable {
+                    name: "tone".to_string(),
+                    default: Some("a friendly tone".to_string()),
+                },
+            ]
+        );
+
+        let mut values = HashMap::new();
+        assert_eq!(
+            render(body, &values).unwrap_err(),
+            "Missing a value for version."
+        );
+        values.insert("version".to_string(), "1.2".to_string());
+        values.insert("tone".to_string(), " ".to_string());
+        assert_eq!(
+            render(body, &values).unwrap(),
+            "Write release notes for 1.2 in a friendly tone. Mention 1.2 and release notes again."
+        );
+        assert_eq!(
+            render("No placeholders", &values).unwrap(),
+            "No placeholders"
+        );
+    }
+
+    #[test]
+    fn test_slash_commands() {
+        assert_eq!(
+            parse_slash_command("  /Review  the login change "),
+            Some(("review".to_string(), "the login change"))
+        );
+        assert_eq!(
+            parse_slash_command("/notes"),
+            Some(("notes".to_string(), ""))
+        );
+        assert_eq!(parse_slash_command("/"), None);
+        assert_eq!(parse_slash_command("/usr/bin is a path"), None);
+        assert_eq!(parse_slash_command("no command"), None);
+
+        let template = PromptTemplate {
+            id: "t1".to_string(),
+            name: "review".to_string(),
+            description: None,
+            body: "Review {{diff}} with {{focus|care}}".to_string(),
+            variables: variables("Review {{diff}} with {{focus|care}}"),
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let values = slash_values(&template, "this diff");
+        assert_eq!(
+            render(&template.body, &values).unwrap(),
+            "Review this diff with care"
+        );
+        assert!(slash_values(&template, "").is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use ts_rs::TS;
+
+/// Longest tool output kept in the transcript; the rest is cut off.
+const MAX_TOOL_OUTPUT_CHARS: usize = 16_000;
+
+/// One tool the agent ran while producing a reply.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct ToolCall {
+    /// The SDK's tool call id; unique within a reply.
+    pub id: String,
+    pub tool_name: String,
+    /// Arguments as JSON text.
+    pub arguments: Option<String>,
+    /// Latest progress message while the tool runs.
+    #[serde(default)]
+    pub progress: Option<String>,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    /// `None` while running, or when the reply ended before the tool did.
+    pub success: Option<bool>,
+    pub started_at: String,
+    pub duration_ms: Option<u32>,
+}
+
+/// Tool calls of one turn in the order they started.
+#[derive(Default)]
+pub struct ToolLog {
+    calls: Vec<ToolCall>,
+    started: HashMap<String, Instant>,
+}
+
+impl ToolLog {
+    pub fn start(
+        &mut self,
+        id: &str,
+        tool_name: &str,
+        arguments: Option<String>,
+        started_at: String,
+        now: Instant,
+    ) -> &ToolCall {
+        self.started.insert(id.to_string(), now);
+        self.calls.push(ToolCall {
+            id: id.to_string(),
+            tool_name: tool_name.to_string(),
+            arguments,
+            progress: None,
+            result: None,
+            error: None,
+            success: None,
+            started_at,
+            duration_ms: None,
+        });
+        self.calls.last().unwrap()
+    }
+
+    /// Records a progress message; `None` for an unknown call.
+    pub fn progress(&mut self, id: &str, message: &str) -> Option<&ToolCall> {
+        let call = self.find(id)?;
+        call.progress = Some(message.to_string());
+        Some(call)
+    }
+
+    /// Records the outcome and duration; `None` for an unknown call.
+    pub fn complete(
+        &mut self,
+        id: &str,
+        success: bool,
+        result: Option<&str>,
+        error: Option<&str>,
+        now: Instant,
+    ) -> Option<&ToolCall> {
+        let elapsed = self.started.remove(id).map(|t| now.duration_since(t));
+        let call = self.find(id)?;
+        call.success = Some(success);
+        call.result = result.map(truncate_output);
+        call.error = error.map(str::to_string);
+        call.progress = None;
+        call.duration_ms = elapsed.map(|d| d.as_millis().min(u32::MAX as u128) as u32);
+        Some(call)
+    }
+
+    pub fn calls(&self) -> &[ToolCall] {
+        &self.calls
+    }
+
+    // Latest call with `id`, in case the SDK reuses ids within a turn
+    fn find(&mut self, id: &str) -> Option<&mut ToolCall> {
+        self.calls.iter_mut().rev().find(|c| c.id == id)
+    }
+}
+
+fn truncate_output(output: &str) -> String {
+    match output.char_indices().nth(MAX_TOOL_OUTPUT_CHARS) {
+        Some((cut, _)) => format!("{}\n… (output truncated)", &output[..cut]),
+        None => output.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_tool_log_records_outcome_and_duration() {
+        let start = Instant::now();
+        let mut log = ToolLog::default();
+        log.start(
+            "t1",
+            "bash",
+            Some(r#"{"command":"ls"}"#.into()),
+            "2026-01-01T00:00:00.000Z".into(),
+            start,
+        );
+        assert_eq!(
+            log.progress("t1", "running").unwrap().progress.as_deref(),
+            Some("running")
+        );
+        let done = log
+            .complete(
+                "t1",
+                true,
+                Some("Cargo.toml"),
+                None,
+                start + Duration::from_millis(250),
+            )
+            .unwrap();
+        assert_eq!(done.success, Some(true));
+        assert_eq!(done.result.as_deref(), Some("Cargo.toml"));
+        assert_eq!(done.duration_ms, Some(250));
+        assert_eq!(done.progress, None);
+
+        assert!(log.complete("unknown", true, None, None, start).is_none());
+        assert_eq!(log.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_tool_output_is_truncated() {
+        let long = "x".repeat(MAX_TOOL_OUTPUT_CHARS + 10);
+        let kept = truncate_output(&long);
+        assert!(kept.starts_with(&"x".repeat(MAX_TOOL_OUTPUT_CHARS)));
+        assert!(kept.ends_with("(output truncated)"));
+        assert_eq!(truncate_output("short"), "short");
+    }
+}
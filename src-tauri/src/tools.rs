@@ -0,0 +1,386 @@
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use ts_rs::TS;
+
+/// Largest file `read_file` returns, and its default.
+const MAX_READ_BYTES: u64 = 256 * 1024;
+const DEFAULT_READ_BYTES: u64 = 64 * 1024;
+const MAX_SEARCH_RESULTS: u32 = 20;
+/// Longest argument text kept in the audit log.
+const MAX_AUDIT_ARGUMENTS: usize = 4096;
+
+/// A tool implemented by the app and offered to the model.
+pub struct BuiltinTool {
+    pub name: &'static str,
+    pub description: &'static str,
+    schema: fn() -> serde_json::Value,
+}
+
+pub const BUILTIN_TOOLS: &[BuiltinTool] = &[
+    BuiltinTool {
+        name: "search_conversations",
+        description: "Search the user's past conversations in this app for messages containing the query text.",
+        schema: || {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "minLength": 2, "description": "Text to look for." },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": MAX_SEARCH_RESULTS, "description": "Most results to return (default 10)." }
+                },
+                "required": ["query"],
+                "additionalProperties": false
+            })
+        },
+    },
+    BuiltinTool {
+        name: "read_file",
+        description: "Read a text file from the conversation's working folder. Paths are relative to that folder.",
+        schema: || {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "File path inside the working folder." },
+                    "max_bytes": { "type": "integer", "minimum": 1, "maximum": MAX_READ_BYTES, "description": "Most bytes to read (default 65536)." }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            })
+        },
+    },
+    BuiltinTool {
+        name: "current_datetime",
+        description: "Get the current date and time, in UTC and in the user's local time zone.",
+        schema: || {
+            serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            })
+        },
+    },
+];
+
+/// A built-in tool and whether it is enabled in a conversation.
+#[derive(serde::Serialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct BuiltinToolInfo {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+}
+
+/// One logged call of a built-in tool.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct ToolAuditEntry {
+    pub id: String,
+    pub conversation_id: String,
+    pub session_id: String,
+    pub tool_name: String,
+    /// Arguments as JSON text, cut off after 4 KiB.
+    pub arguments: String,
+    pub success: bool,
+    /// Error message, or the size of the result for successful calls.
+    pub outcome: String,
+    pub duration_ms: u32,
+    pub created_at: String,
+}
+
+/// A message found by `search_conversations`.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub conversation_id: String,
+    pub conversation_title: String,
+    pub role: String,
+    /// Text around the first match.
+    pub snippet: String,
+    pub created_at: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchArgs {
+    query: String,
+    limit: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReadFileArgs {
+    path: String,
+    max_bytes: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DateTimeArgs {}
+
+fn parse<T: serde::de::DeserializeOwned>(arguments: &serde_json::Value) -> Result<T, String> {
+    let arguments = match arguments {
+        serde_json::Value::Null => serde_json::json!({}),
+        other => other.clone(),
+    };
+    serde_json::from_value(arguments).map_err(|e| format!("Invalid arguments: {}", e))
+}
+
+/// Resolves `path` inside `root`, refusing anything that escapes it
+/// (absolute paths elsewhere, `..`, symlinks pointing out).
+fn resolve_in_root(root: &Path, path: &str) -> Result<PathBuf, String> {
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("Working folder is not available: {}", e))?;
+    let candidate = root.join(path);
+    let resolved = candidate
+        .canonicalize()
+        .map_err(|_| format!("File not found: {}", path))?;
+    if !resolved.starts_with(&root) {
+        return Err(format!("{} is outside the working folder.", path));
+    }
+    Ok(resolved)
+}
+
+fn read_file(root: &Path, args: ReadFileArgs) -> Result<String, String> {
+    let limit = args.max_bytes.unwrap_or(DEFAULT_READ_BYTES);
+    if limit == 0 || limit > MAX_READ_BYTES {
+        return Err(format!(
+            "max_bytes must be between 1 and {}.",
+            MAX_READ_BYTES
+        ));
+    }
+    let path = resolve_in_root(root, &args.path)?;
+    if !path.is_file() {
+        return Err(format!("{} is not a file.", args.path));
+    }
+    let mut bytes = Vec::new();
+    use std::io::Read;
+    std::fs::File::open(&path)
+        .and_then(|f| f.take(limit + 1).read_to_end(&mut bytes))
+        .map_err(|e| format!("Failed to read {}: {}", args.path, e))?;
+    let truncated = bytes.len() as u64 > limit;
+    bytes.truncate(limit as usize);
+    if bytes.contains(&0) {
+        return Err(format!("{} is not a text file.", args.path));
+    }
+    let mut text = String::from_utf8_lossy(&bytes).into_owned();
+    if truncated {
+        text.push_str(&format!("\n… (truncated after {} bytes)", limit));
+    }
+    Ok(text)
+}
+
+fn search_conversations(conn: &rusqlite::Connection, args: SearchArgs) -> Result<String, String> {
+    let query = args.query.trim();
+    if query.chars().count() < 2 {
+        return Err("query must be at least 2 characters.".to_string());
+    }
+    let limit = args.limit.unwrap_or(10);
+    if limit == 0 || limit > MAX_SEARCH_RESULTS {
+        return Err(format!(
+            "limit must be between 1 and {}.",
+            MAX_SEARCH_RESULTS
+        ));
+    }
+    let hits = crate::db::search_messages(conn, query, limit).map_err(|e| {
+        tracing::error!("Conversation search failed: {}", e);
+        "Search failed.".to_string()
+    })?;
+    serde_json::to_string(&hits).map_err(|e| e.to_string())
+}
+
+fn current_datetime(_args: DateTimeArgs) -> Result<String, String> {
+    let utc = chrono::Utc::now();
+    let local = utc.with_timezone(&chrono::Local);
+    Ok(serde_json::json!({
+        "utc": utc.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        "local": local.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        "weekday": local.format("%A").to_string(),
+    })
+    .to_string())
+}
+
+/// Validates `arguments` and runs tool `name`. `root` is the folder
+/// `read_file` may read from.
+async fn run(
+    db: &Mutex<Option<Connection>>,
+    root: &Path,
+    name: &str,
+    arguments: &serde_json::Value,
+) -> Result<String, String> {
+    match name {
+        "search_conversations" => {
+            let args = parse(arguments)?;
+            let db_guard = db.lock().await;
+            let conn = db_guard.as_ref().ok_or("Database not initialized.")?;
+            search_conversations(conn, args)
+        }
+        "read_file" => read_file(root, parse(arguments)?),
+        "current_datetime" => current_datetime(parse(arguments)?),
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
+/// Built-in tools offered to new sessions of a conversation.
+pub fn definitions(disabled: &[String]) -> Vec<copilot_sdk::Tool> {
+    BUILTIN_TOOLS
+        .iter()
+        .filter(|t| !disabled.iter().any(|d| d == t.name))
+        .map(|t| {
+            copilot_sdk::Tool::new(t.name)
+                .description(t.description)
+                .schema((t.schema)())
+        })
+        .collect()
+}
+
+/// Registers the handlers of the built-in tools on `session`. A tool turned
+/// off after the session was created is refused when called.
+pub async fn register(
+    db: &Arc<Mutex<Option<Connection>>>,
+    session: &copilot_sdk::Session,
+    conversation_id: &str,
+    root: &Path,
+) {
+    let session_id = session.session_id().to_string();
+    for tool in BUILTIN_TOOLS {
+        let call = ToolCallContext {
+            db: Arc::clone(db),
+            root: root.to_path_buf(),
+            session_id: session_id.clone(),
+            conversation_id: conversation_id.to_string(),
+        };
+        session
+            .register_tool_handler(
+                tool.name,
+                move |invocation: &copilot_sdk::ToolInvocation| {
+                    let arguments = invocation.arguments.clone().unwrap_or_default();
+                    // The SDK calls handlers synchronously
+                    let outcome = tokio::task::block_in_place(|| {
+                        tokio::runtime::Handle::current()
+                            .block_on(call.invoke(tool.name, &arguments))
+                    });
+                    match outcome {
+                        Ok(text) => copilot_sdk::ToolResultObject::text(text),
+                        Err(e) => copilot_sdk::ToolResultObject::error(e),
+                    }
+                },
+            )
+            .await;
+    }
+}
+
+/// What a tool handler needs to run a call and log it.
+struct ToolCallContext {
+    db: Arc<Mutex<Option<Connection>>>,
+    root: PathBuf,
+    session_id: String,
+    conversation_id: String,
+}
+
+impl ToolCallContext {
+    /// Runs a tool call from the model and records it in the audit log.
+    async fn invoke(&self, name: &str, arguments: &serde_json::Value) -> Result<String, String> {
+        let started = std::time::Instant::now();
+        let enabled = {
+            let db_guard = self.db.lock().await;
+            match db_guard.as_ref() {
+                Some(conn) => crate::db::get_disabled_tools(conn, &self.conversation_id)
+                    .map(|disabled| !disabled.iter().any(|d| d == name))
+                    .unwrap_or(true),
+                None => true,
+            }
+        };
+        let outcome = if enabled {
+            run(&self.db, &self.root, name, arguments).await
+        } else {
+            Err(format!("{} is turned off in this conversation.", name))
+        };
+
+        let entry = ToolAuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            conversation_id: self.conversation_id.clone(),
+            session_id: self.session_id.clone(),
+            tool_name: name.to_string(),
+            arguments: truncate_arguments(arguments.to_string()),
+            success: outcome.is_ok(),
+            outcome: match &outcome {
+                Ok(text) => format!("{} bytes", text.len()),
+                Err(e) => e.clone(),
+            },
+            duration_ms: started.elapsed().as_millis().min(u32::MAX as u128) as u32,
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        };
+        tracing::info!(
+            "Tool {} called in conversation {} ({})",
+            name,
+            self.conversation_id,
+            if entry.success { "ok" } else { "failed" }
+        );
+        let db_guard = self.db.lock().await;
+        if let Some(conn) = db_guard.as_ref() {
+            if let Err(e) = crate::db::insert_tool_audit(conn, &entry) {
+                tracing::error!("Failed to record tool call: {}", e);
+            }
+        }
+        outcome
+    }
+}
+
+fn truncate_arguments(mut arguments: String) -> String {
+    if arguments.len() > MAX_AUDIT_ARGUMENTS {
+        let cut = (0..=MAX_AUDIT_ARGUMENTS)
+            .rev()
+            .find(|&i| arguments.is_char_boundary(i))
+            .unwrap_or(0);
+        arguments.truncate(cut);
+    }
+    arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemas_describe_objects() {
+        for tool in BUILTIN_TOOLS {
+            let schema = (tool.schema)();
+            assert_eq!(schema["type"], "object", "{}", tool.name);
+            assert_eq!(schema["additionalProperties"], false, "{}", tool.name);
+        }
+    }
+
+    #[test]
+    fn test_arguments_are_validated() {
+        assert!(parse::<SearchArgs>(&serde_json::json!({ "query": "rust" })).is_ok());
+        assert!(parse::<SearchArgs>(&serde_json::json!({})).is_err());
+        assert!(parse::<SearchArgs>(&serde_json::json!({ "query": "x", "extra": 1 })).is_err());
+        assert!(parse::<DateTimeArgs>(&serde_json::Value::Null).is_ok());
+    }
+
+    #[test]
+    fn test_read_file_stays_in_root() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("notes.txt"), "hello world").unwrap();
+        let outside = tempfile::NamedTempFile::new().unwrap();
+
+        let read = |path: &str, max_bytes: Option<u64>| {
+            read_file(
+                root.path(),
+                ReadFileArgs {
+                    path: path.to_string(),
+                    max_bytes,
+                },
+            )
+        };
+        assert_eq!(read("notes.txt", None).unwrap(), "hello world");
+        assert!(read("notes.txt", Some(5))
+            .unwrap()
+            .starts_with("hello\n… (truncated"));
+        assert!(read("../notes.txt", None).is_err());
+        assert!(read(outside.path().to_str().unwrap(), None).is_err());
+        assert!(read("missing.txt", None).is_err());
+        assert!(read("notes.txt", Some(MAX_READ_BYTES + 1)).is_err());
+    }
+}
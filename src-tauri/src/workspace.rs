@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+
+/// A folder the user allows conversations to work in, along with its
+/// subfolders.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, TS)]
+#[ts(export)]
+pub struct TrustedFolder {
+    /// Absolute path with symlinks resolved.
+    pub path: String,
+    pub created_at: String,
+}
+
+/// Resolves `path` to an absolute folder path with symlinks followed.
+pub fn normalize(path: &str) -> Result<PathBuf, String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("A folder path is required.".to_string());
+    }
+    let resolved = Path::new(path)
+        .canonicalize()
+        .map_err(|_| format!("Folder not found: {}", path))?;
+    if !resolved.is_dir() {
+        return Err(format!("{} is not a folder.", path));
+    }
+    Ok(resolved)
+}
+
+/// Whether `folder` (already normalized) is a trusted folder or inside one.
+pub fn is_trusted(folder: &Path, trusted: &[TrustedFolder]) -> bool {
+    trusted.iter().any(|t| folder.starts_with(&t.path))
+}
+
+/// The chosen workspace `chosen` of a conversation while it exists and is
+/// still trusted; `None` means the session works in its sandbox.
+pub fn session_directory(chosen: Option<&str>, trusted: &[TrustedFolder]) -> Option<PathBuf> {
+    let chosen = chosen?;
+    match normalize(chosen) {
+        Ok(folder) if is_trusted(&folder, trusted) => Some(folder),
+        Ok(_) => {
+            tracing::warn!(
+                "Workspace {} is no longer trusted, using the sandbox",
+                chosen
+            );
+            None
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Workspace {} is unavailable, using the sandbox: {}",
+                chosen,
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted(path: &Path) -> TrustedFolder {
+        TrustedFolder {
+            path: path.canonicalize().unwrap().to_string_lossy().into_owned(),
+            created_at: "2026-01-01T00:00:00.000Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_trusted_folders_include_subfolders() {
+        let root = tempfile::tempdir().unwrap();
+        let project = root.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let allow = vec![trusted(&project)];
+        let check = |path: &Path| is_trusted(&normalize(path.to_str().unwrap()).unwrap(), &allow);
+
+        assert!(check(&project));
+        assert!(check(&project.join("src").join("..").join("src")));
+        assert!(!check(root.path()));
+        // A sibling whose name starts with the trusted one is not inside it
+        let sibling = root.path().join("project-old");
+        std::fs::create_dir(&sibling).unwrap();
+        assert!(!check(&sibling));
+        assert!(!check(other.path()));
+    }
+
+    #[test]
+    fn test_session_directory_falls_back() {
+        let project = tempfile::tempdir().unwrap();
+        let chosen = project.path().to_str().unwrap();
+
+        assert_eq!(session_directory(None, &[]), None);
+        assert_eq!(session_directory(Some(chosen), &[]), None);
+        assert_eq!(
+            session_directory(Some(chosen), &[trusted(project.path())]),
+            Some(project.path().canonicalize().unwrap())
+        );
+        assert_eq!(session_directory(Some("/does/not/exist"), &[]), None);
+        assert!(normalize("").is_err());
+    }
+}
@@ -1,7 +1,7 @@
 import { describe, it, expect, vi, afterEach } from 'vitest';
 import { mockIPC, clearMocks } from '@tauri-apps/api/mocks';
 import { invoke } from '@tauri-apps/api/core';
-import type { ModelInfo, Settings, Conversation } from './tauri';
+import type { ModelInfo, Settings, Conversation, SessionStart, Message } from './tauri';
 
 interface CreateConversationArgs {
   title?: string;
@@ -13,6 +13,7 @@ interface DeleteConversationArgs {
 }
 
 interface SendMessageArgs {
+  conversationId: string;
   sessionId: string;
   content: string;
 }
@@ -146,21 +147,40 @@ describe('Tauri IPC Commands', () => {
           return null;
         }
       });
-      await invoke('send_message', { sessionId: 'sess-1', content: 'Hello world' });
+      await invoke('send_message', { conversationId: 'c1', sessionId: 'sess-1', content: 'Hello world' });
+      expect(sentArgs!.conversationId).toBe('c1');
       expect(sentArgs!.sessionId).toBe('sess-1');
       expect(sentArgs!.content).toBe('Hello world');
     });
   });
 
   describe('create_session', () => {
-    it('creates session with model', async () => {
+    it('creates session for a conversation with model', async () => {
       mockIPC((cmd) => {
         if (cmd === 'create_session') {
-          return 'session-abc-123';
+          return { session_id: 'session-abc-123', reused: false, carried_over: ['m1'], omitted: 0 };
+        }
+      });
+      const start = await invoke<SessionStart>('create_session', { conversationId: 'c1', model: 'gpt-4o' });
+      expect(start.session_id).toBe('session-abc-123');
+      expect(start.carried_over).toEqual(['m1']);
+    });
+  });
+
+  describe('switch_branch', () => {
+    it('sends the sibling to show and returns the new branch', async () => {
+      let receivedArgs: Record<string, unknown> | null = null;
+      mockIPC((cmd, args) => {
+        if (cmd === 'switch_branch') {
+          receivedArgs = args as Record<string, unknown>;
+          return [
+            { id: 'u1b', conversation_id: 'c1', role: 'user', content: 'Edited', created_at: '2026-01-01', parent_id: null, sibling_index: 1, sibling_count: 2 },
+          ];
         }
       });
-      const sessionId = await invoke('create_session', { model: 'gpt-4o' });
-      expect(sessionId).toBe('session-abc-123');
+      const branch = await invoke<Message[]>('switch_branch', { conversationId: 'c1', messageId: 'u1', siblingIndex: 1 });
+      expect(receivedArgs!.siblingIndex).toBe(1);
+      expect(branch[0].sibling_count).toBe(2);
     });
   });
 });
@@ -1,4 +1,4 @@
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, type Channel } from '@tauri-apps/api/core';
 import { logger } from '$lib/utils/logger';
 
 // NOTE: The interfaces below mirror Rust structs in src-tauri/src/commands.rs.
@@ -9,12 +9,79 @@ export interface Settings {
   theme: string;
   default_model: string | null;
   system_prompt: string | null;
+  context_budget_chars?: number | null;
+  session_idle_ttl_secs?: number | null;
+  max_sessions?: number | null;
+  send_retry_limit?: number | null;
+  response_deadlines?: DeadlineSettings | null;
+  sandbox_retention_hours?: number | null;
+}
+
+/** Response time limits in seconds; unset inherits, 0 disables. */
+export interface ResponseDeadlines {
+  idle_timeout_secs?: number | null;
+  first_token_timeout_secs?: number | null;
+  total_timeout_secs?: number | null;
+}
+
+/** Global response limits plus overrides keyed by model id */
+export interface DeadlineSettings {
+  default: ResponseDeadlines;
+  models: Record<string, ResponseDeadlines>;
+}
+
+export interface SessionStart {
+  session_id: string;
+  reused: boolean;
+  /** The conversation's session from an earlier run was resumed instead of replaying history */
+  resumed?: boolean;
+  carried_over: string[];
+  omitted: number;
+}
+
+export interface SendReceipt {
+  message: Message;
+  /** 1-based position when the prompt waits behind the current reply; null when sent right away */
+  queue_position: number | null;
+}
+
+/** One event of a streaming turn; `seq` numbers a turn's events from 0. */
+export type StreamEvent =
+  | { type: 'dispatched'; seq: number; message: Message }
+  | { type: 'thinking_delta'; seq: number; delta: string }
+  | { type: 'thinking_complete'; seq: number; content: string }
+  | { type: 'message_delta'; seq: number; delta: string }
+  | { type: 'message_complete'; seq: number; content: string }
+  | { type: 'usage'; seq: number; input_tokens: number | null; output_tokens: number | null }
+  | { type: 'tool_started'; seq: number; tool_call: ToolCall }
+  | { type: 'tool_progress'; seq: number; tool_call_id: string; message: string }
+  | { type: 'tool_completed'; seq: number; tool_call: ToolCall }
+  | { type: 'message_saved'; seq: number; message_id: string }
+  | { type: 'aborted'; seq: number; content: string; message_id: string | null }
+  | { type: 'error'; seq: number; message: string }
+  | { type: 'idle'; seq: number };
+
+export interface QueuedPrompt {
+  id: string;
+  content: string;
+  created_at: string;
+  position: number;
+}
+
+export interface ForkedConversation {
+  conversation: Conversation;
+  messages: Message[];
+  session: SessionStart | null;
 }
 
 export interface ModelInfo {
   id: string;
   name: string;
   provider: string | null;
+  /** Whether the model accepts image attachments */
+  supports_vision?: boolean;
+  /** Tokens the model can take in at once, when known */
+  context_window_tokens?: number | null;
 }
 
 export interface Conversation {
@@ -23,6 +90,12 @@ export interface Conversation {
   model: string | null;
   created_at: string;
   updated_at: string;
+  forked_from_conversation_id?: string | null;
+  forked_from_message_id?: string | null;
+  /** Folder the conversation's sessions work in; the sandbox when null */
+  workspace_folder?: string | null;
+  /** Persona the conversation was started with, if any */
+  persona_id?: string | null;
 }
 
 export interface Message {
@@ -32,6 +105,49 @@ export interface Message {
   content: string;
   thinking?: string;
   created_at: string;
+  interrupted?: boolean;
+  parent_id?: string | null;
+  sibling_index?: number;
+  sibling_count?: number;
+  tool_calls?: ToolCall[];
+  /** Files sent along with this prompt */
+  attachments?: Attachment[];
+}
+
+/** A file sent along with a user message */
+export interface Attachment {
+  id: string;
+  name: string;
+  mime_type: string;
+  /** Size in bytes */
+  size: number;
+  /** Hex SHA-256 of the contents */
+  sha256: string;
+  /** JPEG preview of an image as a data: URL; null for text */
+  thumbnail?: string | null;
+}
+
+/** A file to attach: one picked on disk, text pasted as a file, or a pasted image as base64 */
+export type AttachmentInput =
+  | { type: 'path'; path: string }
+  | { type: 'pasted'; name: string; content: string }
+  | { type: 'image'; name: string; data: string }
+  | { type: 'document'; name: string; data: string };
+
+/** A tool the agent ran while producing a reply */
+export interface ToolCall {
+  id: string;
+  tool_name: string;
+  /** Arguments as JSON text */
+  arguments: string | null;
+  /** Latest progress message while the tool runs */
+  progress?: string | null;
+  result: string | null;
+  error: string | null;
+  /** null while running, or when the reply ended before the tool did */
+  success: boolean | null;
+  started_at: string;
+  duration_ms: number | null;
 }
 
 // Client lifecycle
@@ -57,11 +173,11 @@ export async function refreshModelList(): Promise<void> {
 }
 
 // Sessions
-export async function createSession(model?: string, systemPrompt?: string): Promise<string> {
-  logger.debug('createSession', { model, systemPrompt });
-  const id = await invoke<string>('create_session', { model, systemPrompt });
-  logger.debug('createSession', 'Session created:', id);
-  return id;
+export async function createSession(conversationId: string, model?: string, systemPrompt?: string): Promise<SessionStart> {
+  logger.debug('createSession', { conversationId, model, systemPrompt });
+  const start = await invoke<SessionStart>('create_session', { conversationId, model, systemPrompt });
+  logger.debug('createSession', 'Session ready:', start);
+  return start;
 }
 
 export async function destroySession(sessionId: string): Promise<void> {
@@ -69,9 +185,52 @@ export async function destroySession(sessionId: string): Promise<void> {
   return invoke('destroy_session', { sessionId });
 }
 
-export async function sendMessage(sessionId: string, content: string): Promise<void> {
-  logger.debug('sendMessage', { sessionId, contentLength: content.length });
-  return invoke('send_message', { sessionId, content });
+/**
+ * Sends a prompt, or queues it while the session is still replying. Resolves with
+ * the user message (as stored, unless queued) and its queue position. The reply
+ * streams to `onEvent`; a queued prompt's stream starts with a `dispatched` event.
+ */
+export async function sendMessage(conversationId: string, sessionId: string, content: string, onEvent: Channel<StreamEvent>, attachments?: AttachmentInput[]): Promise<SendReceipt> {
+  logger.debug('sendMessage', { conversationId, sessionId, contentLength: content.length, attachments: attachments?.length ?? 0 });
+  return invoke('send_message', { conversationId, sessionId, content, attachments, onEvent });
+}
+
+export async function abortMessage(sessionId: string): Promise<void> {
+  logger.debug('abortMessage', sessionId);
+  return invoke('abort_message', { sessionId });
+}
+
+export async function listQueuedPrompts(sessionId: string): Promise<QueuedPrompt[]> {
+  return invoke('list_queued_prompts', { sessionId });
+}
+
+/** Moves a queued prompt to the 1-based `position`. */
+export async function reorderQueuedPrompt(sessionId: string, promptId: string, position: number): Promise<QueuedPrompt[]> {
+  logger.debug('reorderQueuedPrompt', { sessionId, promptId, position });
+  return invoke('reorder_queued_prompt', { sessionId, promptId, position });
+}
+
+export async function cancelQueuedPrompt(sessionId: string, promptId: string): Promise<QueuedPrompt[]> {
+  logger.debug('cancelQueuedPrompt', { sessionId, promptId });
+  return invoke('cancel_queued_prompt', { sessionId, promptId });
+}
+
+/** Generates a new reply to the last prompt; the previous reply is kept as a sibling. */
+export async function regenerateMessage(conversationId: string, onEvent: Channel<StreamEvent>, model?: string): Promise<SessionStart> {
+  logger.debug('regenerateMessage', { conversationId, model });
+  return invoke('regenerate_message', { conversationId, model, onEvent });
+}
+
+/** Sends an edited copy of `messageId` on a new branch and resolves with the stored copy. */
+export async function editMessage(conversationId: string, messageId: string, content: string, onEvent: Channel<StreamEvent>, model?: string): Promise<[SessionStart, Message]> {
+  logger.debug('editMessage', { conversationId, messageId, contentLength: content.length });
+  return invoke('edit_message', { conversationId, messageId, content, model, onEvent });
+}
+
+/** Shows another sibling of `messageId` and resolves with the new active branch. */
+export async function switchBranch(conversationId: string, messageId: string, siblingIndex: number): Promise<Message[]> {
+  logger.debug('switchBranch', { conversationId, messageId, siblingIndex });
+  return invoke('switch_branch', { conversationId, messageId, siblingIndex });
 }
 
 // Settings
@@ -92,8 +251,15 @@ export async function getConversation(conversationId: string, limit?: number, of
   return invoke('get_conversation', { conversationId, limit, offset });
 }
 
-export async function createConversation(title?: string, model?: string): Promise<Conversation> {
-  return invoke('create_conversation', { title, model });
+/** Creates a conversation, started with `personaId`'s model and tools when given. */
+export async function createConversation(title?: string, model?: string, personaId?: string): Promise<Conversation> {
+  return invoke('create_conversation', { title, model, personaId });
+}
+
+/** Copies the conversation up to `messageId` into a new conversation with its own session. */
+export async function forkConversation(conversationId: string, messageId: string): Promise<ForkedConversation> {
+  logger.debug('forkConversation', { conversationId, messageId });
+  return invoke('fork_conversation', { conversationId, messageId });
 }
 
 export async function deleteConversation(conversationId: string): Promise<void> {
@@ -103,3 +269,257 @@ export async function deleteConversation(conversationId: string): Promise<void>
 export async function saveMessage(message: Message): Promise<void> {
   return invoke('save_message', { message });
 }
+
+// Tool permissions
+export type PermissionDecision = 'allow' | 'deny';
+/** Where a remembered answer applies */
+export type RuleScope = 'conversation' | 'everywhere';
+
+export interface PermissionRule {
+  id: string;
+  /** Request kind (shell, write, read, url) or the MCP tool name */
+  tool: string;
+  /** Glob matched against the path, or the command line for shell requests; null matches everything */
+  path_pattern: string | null;
+  /** null applies the rule in every conversation */
+  conversation_id: string | null;
+  decision: PermissionDecision;
+  created_at: string;
+}
+
+/** Answers a permission request; with `remember` the answer is saved as a rule. */
+export async function answerPermission(requestId: string, decision: PermissionDecision, remember?: RuleScope, pathPattern?: string): Promise<void> {
+  logger.debug('answerPermission', { requestId, decision, remember });
+  return invoke('answer_permission', { requestId, decision, remember, pathPattern });
+}
+
+export async function listPermissionRules(conversationId?: string): Promise<PermissionRule[]> {
+  return invoke('list_permission_rules', { conversationId });
+}
+
+export async function revokePermissionRule(ruleId: string): Promise<void> {
+  return invoke('revoke_permission_rule', { ruleId });
+}
+
+// Built-in tools
+export interface BuiltinToolInfo {
+  name: string;
+  description: string;
+  enabled: boolean;
+}
+
+export interface ToolAuditEntry {
+  id: string;
+  conversation_id: string;
+  session_id: string;
+  tool_name: string;
+  /** Arguments as JSON text, cut off after 4 KiB */
+  arguments: string;
+  success: boolean;
+  /** Error message, or the size of the result for successful calls */
+  outcome: string;
+  duration_ms: number;
+  created_at: string;
+}
+
+/** Built-in tools and whether each is on in the conversation; all on without one. */
+export async function listBuiltinTools(conversationId?: string): Promise<BuiltinToolInfo[]> {
+  return invoke('list_builtin_tools', { conversationId });
+}
+
+export async function setConversationToolEnabled(conversationId: string, toolName: string, enabled: boolean): Promise<void> {
+  logger.debug('setConversationToolEnabled', { conversationId, toolName, enabled });
+  return invoke('set_conversation_tool_enabled', { conversationId, toolName, enabled });
+}
+
+export async function listToolAudit(conversationId?: string, limit?: number): Promise<ToolAuditEntry[]> {
+  return invoke('list_tool_audit', { conversationId, limit });
+}
+
+// MCP servers
+export type McpTransport =
+  | { type: 'local'; command: string; args: string[]; env: Record<string, string>; cwd: string | null }
+  | { type: 'remote'; url: string; headers: Record<string, string> };
+
+export interface McpServerInput {
+  /** Unique name; letters, digits, '-' and '_' */
+  name: string;
+  /** Disabled servers are kept but not given to new sessions */
+  enabled: boolean;
+  transport: McpTransport;
+}
+
+export interface McpServer extends McpServerInput {
+  id: string;
+  created_at: string;
+}
+
+export interface McpToolInfo {
+  name: string;
+  description: string | null;
+}
+
+export interface McpImport {
+  imported: McpServer[];
+  /** Names already taken or entries that could not be read */
+  skipped: string[];
+}
+
+export async function listMcpServers(): Promise<McpServer[]> {
+  return invoke('list_mcp_servers');
+}
+
+export async function addMcpServer(server: McpServerInput): Promise<McpServer> {
+  logger.debug('addMcpServer', { name: server.name, type: server.transport.type });
+  return invoke('add_mcp_server', { server });
+}
+
+export async function updateMcpServer(serverId: string, server: McpServerInput): Promise<McpServer> {
+  logger.debug('updateMcpServer', { serverId, name: server.name, enabled: server.enabled });
+  return invoke('update_mcp_server', { serverId, server });
+}
+
+export async function deleteMcpServer(serverId: string): Promise<void> {
+  return invoke('delete_mcp_server', { serverId });
+}
+
+/** Adds the servers from the Copilot CLI's own MCP config file. */
+export async function importCliMcpServers(): Promise<McpImport> {
+  return invoke('import_cli_mcp_servers');
+}
+
+/** Starts or connects to a server and lists the tools it reports. */
+export async function testMcpServer(transport: McpTransport): Promise<McpToolInfo[]> {
+  return invoke('test_mcp_server', { transport });
+}
+
+// Personas
+export interface PersonaInput {
+  /** Unique name shown when starting a conversation */
+  name: string;
+  /** Replaces the system prompt of sessions in the persona's conversations */
+  system_prompt: string | null;
+  /** Model new conversations start with; the selected one when null */
+  model: string | null;
+  /** 'low', 'medium' or 'high'; the model's default when null */
+  reasoning_effort: string | null;
+  /** Built-in tools turned on in new conversations; all when null */
+  tools: string[] | null;
+  /** Ids of the MCP servers given to sessions, enabled or not; the enabled ones when null */
+  mcp_servers: string[] | null;
+}
+
+export interface Persona extends PersonaInput {
+  id: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export async function listPersonas(): Promise<Persona[]> {
+  return invoke('list_personas');
+}
+
+export async function addPersona(persona: PersonaInput): Promise<Persona> {
+  logger.debug('addPersona', { name: persona.name, model: persona.model });
+  return invoke('add_persona', { persona });
+}
+
+export async function updatePersona(personaId: string, persona: PersonaInput): Promise<Persona> {
+  logger.debug('updatePersona', { personaId, name: persona.name, model: persona.model });
+  return invoke('update_persona', { personaId, persona });
+}
+
+export async function deletePersona(personaId: string): Promise<void> {
+  return invoke('delete_persona', { personaId });
+}
+
+// Prompt templates
+export interface TemplateVariable {
+  name: string;
+  /** Used when no value is given; null makes the variable required */
+  default: string | null;
+}
+
+export interface PromptTemplateInput {
+  /** Unique name, typed as /name in the message box */
+  name: string;
+  description: string | null;
+  /** Text with {{name}} or {{name|default}} placeholders */
+  body: string;
+}
+
+export interface PromptTemplate extends PromptTemplateInput {
+  id: string;
+  variables: TemplateVariable[];
+  created_at: string;
+  updated_at: string;
+}
+
+export interface RenderedTemplate {
+  content: string;
+  /** Set when the result was also sent to a conversation */
+  receipt: SendReceipt | null;
+}
+
+export async function listPromptTemplates(): Promise<PromptTemplate[]> {
+  return invoke('list_prompt_templates');
+}
+
+export async function addPromptTemplate(template: PromptTemplateInput): Promise<PromptTemplate> {
+  logger.debug('addPromptTemplate', { name: template.name });
+  return invoke('add_prompt_template', { template });
+}
+
+export async function updatePromptTemplate(templateId: string, template: PromptTemplateInput): Promise<PromptTemplate> {
+  logger.debug('updatePromptTemplate', { templateId, name: template.name });
+  return invoke('update_prompt_template', { templateId, template });
+}
+
+export async function deletePromptTemplate(templateId: string): Promise<void> {
+  return invoke('delete_prompt_template', { templateId });
+}
+
+/**
+ * Fills in a template, by id or slash-command name. Pass a conversation,
+ * session and channel to also send the result.
+ */
+export async function renderTemplate(
+  template: string,
+  values: Record<string, string>,
+  send?: { conversationId: string; sessionId: string; onEvent: Channel<StreamEvent> },
+): Promise<RenderedTemplate> {
+  logger.debug('renderTemplate', { template, send: !!send });
+  return invoke('render_template', { template, values, ...send });
+}
+
+/** The text a `/name ...` message expands to, or null if it is not a template. */
+export async function expandSlashCommand(input: string): Promise<string | null> {
+  return invoke('expand_slash_command', { input });
+}
+
+// Workspace folders
+export interface TrustedFolder {
+  /** Absolute path with symlinks resolved */
+  path: string;
+  created_at: string;
+}
+
+export async function listTrustedFolders(): Promise<TrustedFolder[]> {
+  return invoke('list_trusted_folders');
+}
+
+export async function addTrustedFolder(path: string): Promise<TrustedFolder> {
+  logger.debug('addTrustedFolder', path);
+  return invoke('add_trusted_folder', { path });
+}
+
+export async function removeTrustedFolder(path: string): Promise<void> {
+  logger.debug('removeTrustedFolder', path);
+  return invoke('remove_trusted_folder', { path });
+}
+
+/** Sets the folder a conversation works in; it must be inside a trusted folder. `null` goes back to the sandbox. */
+export async function setConversationWorkspace(conversationId: string, folder: string | null): Promise<Conversation> {
+  logger.debug('setConversationWorkspace', { conversationId, folder });
+  return invoke('set_conversation_workspace', { conversationId, folder });
+}
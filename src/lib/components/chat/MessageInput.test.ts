@@ -1,5 +1,5 @@
 import { describe, it, expect, vi } from 'vitest';
-import { render, fireEvent } from '@testing-library/svelte';
+import { render, fireEvent, waitFor } from '@testing-library/svelte';
 import MessageInput from './MessageInput.svelte';
 
 // Mock the chat store
@@ -61,6 +61,22 @@ describe('MessageInput', () => {
     expect(onSend).not.toHaveBeenCalled();
   });
 
+  it('sends attached files without text', async () => {
+    const onSend = vi.fn();
+    const { container, getByLabelText, getByText } = render(MessageInput, {
+      props: { onSend },
+    });
+
+    const fileInput = container.querySelector('input[type="file"]') as HTMLInputElement;
+    const file = new File(['error: oops'], 'build.log', { type: 'text/plain' });
+    Object.defineProperty(fileInput, 'files', { value: [file], configurable: true });
+    await fireEvent.change(fileInput);
+    await waitFor(() => expect(getByText('build.log')).toBeTruthy());
+
+    await fireEvent.click(getByLabelText('Send message'));
+    expect(onSend).toHaveBeenCalledWith('', [{ type: 'pasted', name: 'build.log', content: 'error: oops' }]);
+  });
+
   it('textarea is disabled when disabled prop is true', () => {
     const { getByPlaceholderText } = render(MessageInput, {
       props: { disabled: true },
@@ -3,13 +3,45 @@ import { writable } from 'svelte/store';
 export type AppStatus = 'initializing' | 'ready' | 'error' | 'disconnected';
 export const appStatus = writable<AppStatus>('initializing');
 export const appError = writable<string | null>(null);
+/** Restart attempt while the Copilot CLI is being brought back after a crash */
+export const restartAttempt = writable<number | null>(null);
+
+type ClientStatus = 'starting' | 'ready' | 'crashed' | 'restarting' | 'failed';
+
+let unlistenStatus: (() => void) | null = null;
+
+/** Follow the backend supervisor so the status bar reflects crashes and restarts. */
+async function listenClientStatus() {
+  if (unlistenStatus) return;
+  const { listen } = await import('@tauri-apps/api/event');
+  unlistenStatus = await listen<{ status: ClientStatus; attempt: number | null; message: string | null }>('copilot:client-status', (event) => {
+    const { status, attempt, message } = event.payload;
+    switch (status) {
+      case 'crashed':
+      case 'restarting':
+        appStatus.set('disconnected');
+        restartAttempt.set(attempt);
+        break;
+      case 'ready':
+        appStatus.update(s => s === 'disconnected' ? 'ready' : s);
+        restartAttempt.set(null);
+        break;
+      case 'failed':
+        appError.set(message);
+        appStatus.set('error');
+        restartAttempt.set(null);
+        break;
+    }
+  });
+}
 
 export async function initializeApp() {
   appStatus.set('initializing');
   appError.set(null);
 
   try {
-    // 1. Start Copilot client
+    // 1. Start Copilot client, following its lifecycle from here on
+    await listenClientStatus();
     const { startClient } = await import('$lib/api/tauri');
     await startClient();
 
@@ -49,6 +81,8 @@ export async function shutdownApp() {
   try {
     const { cleanupChatListeners } = await import('$lib/services/chat');
     cleanupChatListeners();
+    unlistenStatus?.();
+    unlistenStatus = null;
 
     const { stopClient } = await import('$lib/api/tauri');
     await stopClient();
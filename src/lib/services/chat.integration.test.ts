@@ -9,6 +9,7 @@ import {
   activeConversationId,
   conversations,
   clearAllCachedMessages,
+  permissionRequests,
 } from '$lib/stores/chat';
 import type { Message } from '$lib/stores/chat';
 
@@ -19,9 +20,20 @@ function setupMockIPC() {
   let sessionCounter = 0;
   mockIPC((cmd, args) => {
     ipcCalls.push({ cmd, args });
-    if (cmd === 'create_session') return `session-${++sessionCounter}`;
+    if (cmd === 'create_session') return { session_id: `session-${++sessionCounter}`, reused: false, carried_over: [], omitted: 0 };
     if (cmd === 'destroy_session') return null;
-    if (cmd === 'send_message') return null;
+    if (cmd === 'send_message') {
+      return {
+        message: {
+          id: `stored-${ipcCalls.length}`,
+          conversation_id: (args as any).conversationId,
+          role: 'user',
+          content: (args as any).content,
+          created_at: new Date().toISOString(),
+        },
+        queue_position: null,
+      };
+    }
     if (cmd === 'save_message') return null;
     if (cmd === 'create_conversation') {
       return {
@@ -120,6 +132,101 @@ describe('Chat Service Integration Tests', () => {
     });
   });
 
+  describe('stream channel', () => {
+    it('applies the events streamed to the channel passed to send_message', async () => {
+      setupMockIPC();
+
+      const { sendChatMessage, resetSession } = await import('$lib/services/chat');
+      await resetSession();
+      ipcCalls = [];
+
+      activeConversationId.set('conv-channel');
+      await sendChatMessage('Stream me');
+
+      const sendCall = ipcCalls.find((c) => c.cmd === 'send_message');
+      const onEvent = sendCall!.args.onEvent;
+      expect(onEvent).toBeDefined();
+
+      onEvent.onmessage({ type: 'message_delta', seq: 0, delta: 'Hello ' });
+      onEvent.onmessage({ type: 'message_delta', seq: 1, delta: 'world' });
+      expect(get(messages).find((m) => m.id === 'streaming')!.content).toBe('Hello world');
+
+      onEvent.onmessage({ type: 'message_saved', seq: 2, message_id: 'reply-1' });
+      onEvent.onmessage({ type: 'idle', seq: 3 });
+      const reply = get(messages).find((m) => m.role === 'assistant');
+      expect(reply!.id).toBe('reply-1');
+      expect(reply!.content).toBe('Hello world');
+      expect(get(isStreaming)).toBe(false);
+
+      await resetSession();
+    });
+
+    it('tracks tool calls on the streaming reply', async () => {
+      setupMockIPC();
+
+      const { sendChatMessage, resetSession } = await import('$lib/services/chat');
+      await resetSession();
+      ipcCalls = [];
+
+      activeConversationId.set('conv-tools');
+      await sendChatMessage('List the files');
+
+      const onEvent = ipcCalls.find((c) => c.cmd === 'send_message')!.args.onEvent;
+      const started = {
+        id: 't1',
+        tool_name: 'bash',
+        arguments: '{"command":"ls"}',
+        progress: null,
+        result: null,
+        error: null,
+        success: null,
+        started_at: new Date().toISOString(),
+        duration_ms: null,
+      };
+      onEvent.onmessage({ type: 'tool_started', seq: 0, tool_call: started });
+      onEvent.onmessage({ type: 'tool_progress', seq: 1, tool_call_id: 't1', message: 'Running' });
+      let calls = get(messages).find((m) => m.id === 'streaming')!.tool_calls!;
+      expect(calls).toHaveLength(1);
+      expect(calls[0].progress).toBe('Running');
+
+      onEvent.onmessage({
+        type: 'tool_completed',
+        seq: 2,
+        tool_call: { ...started, result: 'README.md', success: true, duration_ms: 40 },
+      });
+      onEvent.onmessage({ type: 'message_saved', seq: 3, message_id: 'reply-tools' });
+      onEvent.onmessage({ type: 'idle', seq: 4 });
+
+      calls = get(messages).find((m) => m.id === 'reply-tools')!.tool_calls!;
+      expect(calls[0]).toMatchObject({ success: true, result: 'README.md', duration_ms: 40 });
+
+      await resetSession();
+    });
+  });
+
+  describe('permission requests', () => {
+    it('answers a request and drops the prompt', async () => {
+      setupMockIPC();
+      ipcCalls = [];
+      const { answerPermissionRequest } = await import('$lib/services/chat');
+
+      permissionRequests.set([{
+        request_id: 'req-1',
+        session_id: 'session-1',
+        conversation_id: 'conv-1',
+        kind: 'shell',
+        tool: 'shell',
+        target: 'git status',
+        details: {},
+      }]);
+      await answerPermissionRequest('req-1', 'allow', 'conversation');
+
+      expect(get(permissionRequests)).toEqual([]);
+      const call = ipcCalls.find((c) => c.cmd === 'answer_permission');
+      expect(call!.args).toMatchObject({ requestId: 'req-1', decision: 'allow', remember: 'conversation' });
+    });
+  });
+
   // Test 3 — Streaming cleanup on error
   describe('streaming cleanup on error', () => {
     it('removes streaming placeholder and adds error message on session-error', async () => {
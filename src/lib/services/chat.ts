@@ -1,7 +1,11 @@
 import { get } from 'svelte/store';
-import { messages, streamingState, resetStreamingState, activeConversationId, conversations, cacheMessages, getCachedMessages, type Message } from '$lib/stores/chat';
+import { messages, queuedPrompts, permissionRequests, streamingState, resetStreamingState, activeConversationId, conversations, cacheMessages, getCachedMessages, clearCachedMessages, type Message, type QueuedPrompt } from '$lib/stores/chat';
 import { selectedModel } from '$lib/stores/models';
+import { selectedPersonaId } from '$lib/stores/personas';
 import { logger } from '$lib/utils/logger';
+import { showError } from '$lib/utils/toast';
+import type { AttachmentInput, PermissionDecision, RuleScope, StreamEvent, ToolCall } from '$lib/api/tauri';
+import type { PermissionRequest } from '$lib/stores/chat';
 
 const CONVERSATION_TITLE_MAX_LENGTH = 50;
 
@@ -14,23 +18,10 @@ let sessionConversationId: string | null = null;
 
 let unlistenFns: Array<() => void> = [];
 
-/**
- * Check if an incoming event belongs to the current streaming session.
- * Silently drops events from stale/old sessions.
- */
-function isActiveSession(eventSessionId: string): boolean {
-  const { sessionId } = get(streamingState);
-  if (sessionId && eventSessionId !== sessionId) {
-    logger.debug('DROPPED stale event', { event_session: eventSessionId, active_session: sessionId });
-    return false;
-  }
-  return true;
-}
-
 /**
  * Destroy the current SDK session so the next message creates a fresh one.
- * This MUST be called whenever the user switches conversations or starts a
- * new chat — otherwise the model responds in the context of the old session.
+ * Switching conversations does not need this — the backend keeps one session
+ * per conversation and hands it back from `createSession`; use `detachSession`.
  */
 export async function resetSession() {
   if (currentSessionId) {
@@ -45,6 +36,21 @@ export async function resetSession() {
     }
     currentSessionId = null;
     sessionConversationId = null;
+    queuedPrompts.set([]);
+  }
+}
+
+/**
+ * Forget the current session without destroying it. The backend keeps it bound
+ * to its conversation, so returning to that conversation picks it up again.
+ */
+export function detachSession() {
+  if (currentSessionId) {
+    logger.debug('detachSession', currentSessionId);
+    streamingState.update(s => ({ ...s, sessionId: null }));
+    currentSessionId = null;
+    sessionConversationId = null;
+    queuedPrompts.set([]);
   }
 }
 
@@ -70,108 +76,247 @@ function updateMessagesForStreamingConvo(updater: (msgs: Message[]) => Message[]
 export async function initChatListeners() {
   const { listen } = await import('@tauri-apps/api/event');
 
-  // --- Thinking / reasoning stream ---
-  const unlistenThinkingDelta = await listen<{ session_id: string; delta: string }>('copilot:thinking-delta', (event) => {
-    if (!isActiveSession(event.payload.session_id)) return;
-    logger.debug('thinking-delta', { session: event.payload.session_id, deltaLen: event.payload.delta.length });
-    streamingState.update(s => ({ ...s, thinking: s.thinking + event.payload.delta, isThinking: true }));
-    updateMessagesForStreamingConvo(msgs => {
-      const last = msgs[msgs.length - 1];
-      if (last && last.role === 'assistant' && last.id === 'streaming') {
-        return [...msgs.slice(0, -1), { ...last, thinking: get(streamingState).thinking }];
-      }
-      return msgs;
-    });
+  // Stream events arrive on the channel of the call that started the turn; only the queue is global
+  const unlistenQueue = await listen<{ session_id: string; conversation_id: string; queue: QueuedPrompt[] }>('copilot:queue-updated', (event) => {
+    if (event.payload.session_id !== currentSessionId) return;
+    logger.debug('queue-updated', { session: event.payload.session_id, length: event.payload.queue.length });
+    queuedPrompts.set(event.payload.queue);
   });
 
-  const unlistenThinkingComplete = await listen<{ session_id: string; content: string }>('copilot:thinking-complete', (event) => {
-    if (!isActiveSession(event.payload.session_id)) return;
-    logger.debug('thinking-complete', { session: event.payload.session_id });
-    streamingState.update(s => ({ ...s, isThinking: false }));
+  // A transient failure is being retried; show it until the reply starts
+  const unlistenRetrying = await listen<{ session_id: string; conversation_id: string; attempt: number; max_attempts: number; delay_ms: number; reason: string }>('copilot:retrying', (event) => {
+    const { conversation_id, attempt, max_attempts, delay_ms, reason } = event.payload;
+    logger.debug('retrying', { attempt, max_attempts, delay_ms, reason });
+    streamingState.update(s => s.conversationId === conversation_id
+      ? { ...s, retry: { attempt, maxAttempts: max_attempts } }
+      : s);
   });
 
-  // --- Message content stream ---
-  const unlisten1 = await listen<{ session_id: string; delta: string }>('copilot:message-delta', (event) => {
-    if (!isActiveSession(event.payload.session_id)) return;
-    logger.debug('message-delta', { session: event.payload.session_id, deltaLen: event.payload.delta.length });
-    // Once message deltas arrive, thinking phase is over
-    streamingState.update(s => ({ ...s, content: s.content + event.payload.delta, isThinking: false }));
-    updateMessagesForStreamingConvo(msgs => {
-      const last = msgs[msgs.length - 1];
-      if (last && last.role === 'assistant' && last.id === 'streaming') {
-        return [...msgs.slice(0, -1), { ...last, content: get(streamingState).content }];
-      }
-      return msgs;
-    });
+  // The backend destroyed an idle session; the next prompt opens a new one seeded from history
+  const unlistenEvicted = await listen<{ session_id: string; conversation_id: string; reason: 'idle' | 'limit' }>('copilot:session-evicted', (event) => {
+    logger.debug('session-evicted', event.payload);
+    if (event.payload.session_id === currentSessionId) detachSession();
   });
 
-  const unlisten2 = await listen<{ session_id: string; content: string }>('copilot:message-complete', (event) => {
-    if (!isActiveSession(event.payload.session_id)) return;
-    logger.debug('message-complete', { session: event.payload.session_id, contentLen: event.payload.content?.length });
-    updateMessagesForStreamingConvo(msgs => {
-      const last = msgs[msgs.length - 1];
-      if (last && last.id === 'streaming') {
-        // Keep id as 'streaming' — session-idle will assign the final UUID.
-        // This prevents a duplicate bubble (finalized message + dots) between
-        // the message-complete and session-idle events.
-        return [...msgs.slice(0, -1), {
-          ...last,
-          content: event.payload.content || get(streamingState).content,
-        }];
-      }
-      return msgs;
-    });
+  // A tool wants to run a command or touch a file; the turn waits for answerPermissionRequest
+  const unlistenPermission = await listen<PermissionRequest>('copilot:permission-request', (event) => {
+    logger.debug('permission-request', { tool: event.payload.tool, target: event.payload.target });
+    permissionRequests.update(reqs => [...reqs, event.payload]);
   });
 
-  const unlisten3 = await listen<{ session_id: string }>('copilot:session-idle', (event) => {
-    if (!isActiveSession(event.payload.session_id)) return;
-    logger.debug('session-idle', { session: event.payload.session_id });
-
-    // Finalize the streaming placeholder with a real UUID, filtering all
-    // occurrences so no orphaned placeholders survive.
-    updateMessagesForStreamingConvo(msgs => {
-      const streamingMsg = msgs.find(m => m.id === 'streaming');
-      const filtered = msgs.filter(m => m.id !== 'streaming');
-      if (streamingMsg) {
-        return [...filtered, { ...streamingMsg, id: crypto.randomUUID() }];
-      }
-      return filtered;
-    });
+  // Timed out or denied because the turn was stopped
+  const unlistenPermissionClosed = await listen<{ request_id: string }>('copilot:permission-closed', (event) => {
+    permissionRequests.update(reqs => reqs.filter(r => r.request_id !== event.payload.request_id));
+  });
 
-    // Clear streaming indicator *after* finalizing the message so the UI
-    // never renders the dots alongside a completed response.
-    if (get(streamingState).conversationId === get(activeConversationId)) {
-      streamingState.update(s => ({ ...s, isActive: false }));
-    }
+  unlistenFns = [unlistenQueue, unlistenRetrying, unlistenEvicted, unlistenPermission, unlistenPermissionClosed];
+}
 
-    streamingState.update(s => ({ ...s, content: '' }));
-    saveCurrentMessages();
-    resetStreamingState();
-  });
+/** Answers a pending permission request, optionally remembering the answer. */
+export async function answerPermissionRequest(requestId: string, decision: PermissionDecision, remember?: RuleScope) {
+  permissionRequests.update(reqs => reqs.filter(r => r.request_id !== requestId));
+  try {
+    const { answerPermission } = await import('$lib/api/tauri');
+    await answerPermission(requestId, decision, remember);
+  } catch (e) {
+    console.warn('Failed to answer permission request:', e);
+  }
+}
+
+/**
+ * Create the channel a turn in `convoId` streams its events to. A queued prompt's
+ * stream opens with `dispatched` once it is actually sent.
+ */
+async function openStream(convoId: string) {
+  const { Channel } = await import('@tauri-apps/api/core');
+  const channel = new Channel<StreamEvent>();
+  let nextSeq = 0;
+  channel.onmessage = (event) => {
+    if (event.seq !== nextSeq) {
+      logger.debug('stream event out of order', { convoId, expected: nextSeq, seq: event.seq });
+    }
+    nextSeq = event.seq + 1;
 
-  const unlisten4 = await listen<{ session_id: string; message: string }>('copilot:session-error', (event) => {
-    if (!isActiveSession(event.payload.session_id)) return;
-    // Only clear UI streaming indicator if the active conversation is the one that was streaming
-    if (get(streamingState).conversationId === get(activeConversationId)) {
-      streamingState.update(s => ({ ...s, isActive: false }));
+    if (event.type === 'dispatched') {
+      showDispatched(convoId, event.message);
+      return;
     }
+    if (get(streamingState).conversationId !== convoId) {
+      if (event.type === 'error') console.warn('Message failed:', event.message);
+      logger.debug('DROPPED stale event', { type: event.type, convoId });
+      return;
+    }
+    handleStreamEvent(event);
+  };
+  return channel;
+}
 
-    const convoId = get(streamingState).conversationId || get(activeConversationId) || '';
-    updateMessagesForStreamingConvo(msgs => {
-      const filtered = msgs.filter(m => m.id !== 'streaming');
-      return [...filtered, {
-        id: crypto.randomUUID(),
-        conversation_id: convoId,
-        role: 'assistant' as const,
-        content: `Error: ${event.payload.message}`,
-        created_at: new Date().toISOString(),
-      }];
-    });
+/** A queued prompt went out after the previous reply finished; stream its reply like a fresh send. */
+function showDispatched(convoId: string, message: Message) {
+  logger.debug('dispatched', { convoId, prompt: message.id });
+  if (sessionConversationId !== convoId || convoId !== get(activeConversationId)) {
+    // Not on screen; reload from the database next time the conversation is opened
+    clearCachedMessages(convoId);
+    return;
+  }
+  messages.update(msgs => [...msgs, message]);
+  beginStreaming(convoId, currentSessionId);
+}
 
-    resetStreamingState();
-  });
+/** Applies `update` to the tool calls of the reply being streamed. */
+function updateStreamingToolCalls(update: (calls: ToolCall[]) => ToolCall[]) {
+  updateMessagesForStreamingConvo(msgs => msgs.map(m =>
+    m.id === 'streaming' ? { ...m, tool_calls: update(m.tool_calls ?? []) } : m
+  ));
+}
+
+function handleStreamEvent(event: StreamEvent) {
+  switch (event.type) {
+    // --- Thinking / reasoning stream ---
+    case 'thinking_delta':
+      logger.debug('thinking-delta', { deltaLen: event.delta.length });
+      streamingState.update(s => ({ ...s, thinking: s.thinking + event.delta, isThinking: true, retry: null }));
+      updateMessagesForStreamingConvo(msgs => {
+        const last = msgs[msgs.length - 1];
+        if (last && last.role === 'assistant' && last.id === 'streaming') {
+          return [...msgs.slice(0, -1), { ...last, thinking: get(streamingState).thinking }];
+        }
+        return msgs;
+      });
+      break;
+
+    case 'thinking_complete':
+      logger.debug('thinking-complete');
+      streamingState.update(s => ({ ...s, isThinking: false }));
+      break;
+
+    // --- Message content stream ---
+    case 'message_delta':
+      logger.debug('message-delta', { deltaLen: event.delta.length });
+      // Once message deltas arrive, thinking phase is over
+      streamingState.update(s => ({ ...s, content: s.content + event.delta, isThinking: false, retry: null }));
+      updateMessagesForStreamingConvo(msgs => {
+        const last = msgs[msgs.length - 1];
+        if (last && last.role === 'assistant' && last.id === 'streaming') {
+          return [...msgs.slice(0, -1), { ...last, content: get(streamingState).content }];
+        }
+        return msgs;
+      });
+      break;
+
+    case 'message_complete':
+      logger.debug('message-complete', { contentLen: event.content?.length });
+      updateMessagesForStreamingConvo(msgs => {
+        const last = msgs[msgs.length - 1];
+        if (last && last.id === 'streaming') {
+          // Keep id as 'streaming' — the idle event will assign the final id.
+          // This prevents a duplicate bubble (finalized message + dots) between
+          // the message_complete and idle events.
+          return [...msgs.slice(0, -1), {
+            ...last,
+            content: event.content || get(streamingState).content,
+          }];
+        }
+        return msgs;
+      });
+      break;
+
+    // --- Tool execution ---
+    case 'tool_started':
+      logger.debug('tool-started', { tool: event.tool_call.tool_name });
+      streamingState.update(s => ({ ...s, isThinking: false, retry: null }));
+      updateStreamingToolCalls(calls => [...calls, event.tool_call]);
+      break;
+
+    case 'tool_progress':
+      updateStreamingToolCalls(calls =>
+        calls.map(c => c.id === event.tool_call_id ? { ...c, progress: event.message } : c)
+      );
+      break;
+
+    case 'tool_completed':
+      logger.debug('tool-completed', { tool: event.tool_call.tool_name, success: event.tool_call.success });
+      updateStreamingToolCalls(calls =>
+        calls.map(c => c.id === event.tool_call.id ? event.tool_call : c)
+      );
+      break;
+
+    // The backend stores the reply itself and tells us the id it was saved under
+    case 'message_saved':
+      logger.debug('message-saved', { id: event.message_id });
+      updateMessagesForStreamingConvo(msgs =>
+        msgs.map(m => m.id === 'streaming' ? { ...m, id: event.message_id } : m)
+      );
+      break;
+
+    case 'idle':
+      logger.debug('idle');
+      // Normally message_saved already swapped in the stored id; if the reply
+      // was empty or could not be saved, finalize the placeholder here,
+      // filtering all occurrences so no orphaned placeholders survive.
+      updateMessagesForStreamingConvo(msgs => {
+        const streamingMsg = msgs.find(m => m.id === 'streaming');
+        const filtered = msgs.filter(m => m.id !== 'streaming');
+        if (streamingMsg) {
+          return [...filtered, { ...streamingMsg, id: crypto.randomUUID() }];
+        }
+        return filtered;
+      });
+
+      // Clear streaming indicator *after* finalizing the message so the UI
+      // never renders the dots alongside a completed response.
+      if (get(streamingState).conversationId === get(activeConversationId)) {
+        streamingState.update(s => ({ ...s, isActive: false }));
+      }
+
+      streamingState.update(s => ({ ...s, content: '' }));
+      cacheStreamedMessages();
+      resetStreamingState();
+      break;
+
+    case 'aborted':
+      logger.debug('aborted', { contentLen: event.content.length });
+      // Keep whatever was generated, flagged as interrupted; drop the placeholder if nothing was
+      updateMessagesForStreamingConvo(msgs => msgs.flatMap(m => {
+        const isPending = m.id === 'streaming' || m.id === event.message_id;
+        if (!isPending) return [m];
+        if (!event.message_id) return [];
+        return [{ ...m, id: event.message_id, content: event.content, interrupted: true }];
+      }));
+
+      if (get(streamingState).conversationId === get(activeConversationId)) {
+        streamingState.update(s => ({ ...s, isActive: false }));
+      }
+      cacheStreamedMessages();
+      resetStreamingState();
+      break;
+
+    case 'error': {
+      // Only clear UI streaming indicator if the active conversation is the one that was streaming
+      if (get(streamingState).conversationId === get(activeConversationId)) {
+        streamingState.update(s => ({ ...s, isActive: false }));
+      }
+
+      const convoId = get(streamingState).conversationId || get(activeConversationId) || '';
+      updateMessagesForStreamingConvo(msgs => {
+        const filtered = msgs.filter(m => m.id !== 'streaming');
+        return [...filtered, {
+          id: crypto.randomUUID(),
+          conversation_id: convoId,
+          role: 'assistant' as const,
+          content: `Error: ${event.message}`,
+          created_at: new Date().toISOString(),
+        }];
+      });
 
-  unlistenFns = [unlistenThinkingDelta, unlistenThinkingComplete, unlisten1, unlisten2, unlisten3, unlisten4];
+      resetStreamingState();
+      break;
+    }
+
+    case 'usage':
+      logger.debug('usage', { input: event.input_tokens, output: event.output_tokens });
+      break;
+  }
 }
 
 export function cleanupChatListeners() {
@@ -179,24 +324,75 @@ export function cleanupChatListeners() {
   unlistenFns = [];
 }
 
-export async function sendChatMessage(content: string) {
+/** Stop the reply that is currently streaming; the session stays usable. */
+export async function stopGeneration() {
+  const { sessionId } = get(streamingState);
+  if (!sessionId) return;
+  try {
+    const { abortMessage } = await import('$lib/api/tauri');
+    await abortMessage(sessionId);
+  } catch (e) {
+    console.warn('Failed to stop generation:', e);
+  }
+}
+
+function attachmentName(attachment: AttachmentInput): string {
+  return attachment.type === 'path' ? attachment.path.split(/[\\/]/).pop() ?? attachment.path : attachment.name;
+}
+
+export async function sendChatMessage(content: string, attachments: AttachmentInput[] = []) {
   if (isSending) return;
   isSending = true;
 
+  if (content.startsWith('/')) {
+    // `/name text` runs a saved prompt template
+    try {
+      const { expandSlashCommand } = await import('$lib/api/tauri');
+      content = (await expandSlashCommand(content)) ?? content;
+    } catch (error) {
+      showError(String(error));
+      isSending = false;
+      return;
+    }
+  }
+
   const conversationId = get(activeConversationId);
   let convoId = conversationId;
 
   try {
     const { createSession, sendMessage, createConversation } = await import('$lib/api/tauri');
+    const files = attachments.length ? attachments : undefined;
 
     if (!convoId) {
       const model = get(selectedModel);
-      const convo = await createConversation(content.substring(0, CONVERSATION_TITLE_MAX_LENGTH), model ?? undefined);
+      const title = content || attachments.map(attachmentName).join(', ');
+      const convo = await createConversation(
+        title.substring(0, CONVERSATION_TITLE_MAX_LENGTH),
+        model ?? undefined,
+        get(selectedPersonaId) ?? undefined,
+      );
       convoId = convo.id;
       activeConversationId.set(convoId);
       conversations.add(convo);
     }
 
+    // While a reply is streaming the backend queues the prompt; the queue store shows it
+    const { isActive, conversationId: streamingConvoId } = get(streamingState);
+    if (isActive && streamingConvoId === convoId && currentSessionId && sessionConversationId === convoId) {
+      try {
+        const receipt = await sendMessage(convoId, currentSessionId, content, await openStream(convoId), files);
+        if (receipt.queue_position === null) {
+          // The previous reply finished in the meantime and this one went straight out
+          messages.update(msgs => [...msgs, receipt.message]);
+          beginStreaming(convoId, currentSessionId);
+        }
+      } catch (error) {
+        // Leave the reply that is streaming alone
+        console.error('Failed to queue message:', error);
+      }
+      return;
+    }
+
     const userMessage: Message = {
       id: crypto.randomUUID(),
       conversation_id: convoId,
@@ -207,79 +403,197 @@ export async function sendChatMessage(content: string) {
     messages.update(msgs => [...msgs, userMessage]);
 
     if (!currentSessionId || sessionConversationId !== convoId) {
-      // Destroy stale session from a different conversation
-      if (currentSessionId && sessionConversationId !== convoId) {
-        logger.debug('sendChatMessage', 'destroying stale session', currentSessionId, 'for new convo', convoId);
-        try {
-          const { destroySession } = await import('$lib/api/tauri');
-          await destroySession(currentSessionId);
-        } catch (error) { console.warn('Failed to destroy stale session:', currentSessionId, error); }
-        currentSessionId = null;
-      }
+      // The backend reuses the session already bound to this conversation, if any
       const model = get(selectedModel);
-      currentSessionId = await createSession(model ?? undefined);
+      const start = await createSession(convoId, model ?? undefined);
+      currentSessionId = start.session_id;
       sessionConversationId = convoId;
-      logger.debug('sendChatMessage', 'created new session', currentSessionId, 'for convo', convoId);
+      if (!start.reused && start.omitted > 0) {
+        // Flag stored turns the new session could not carry over so the UI can show them as out of context
+        const carried = new Set(start.carried_over);
+        messages.update(msgs => msgs.map(m =>
+          m.id === userMessage.id ? m : { ...m, outOfContext: !carried.has(m.id) }
+        ));
+      }
+      logger.debug('sendChatMessage', 'using session', currentSessionId, 'for convo', convoId,
+        start.reused ? '(reused)' : start.resumed ? '(resumed)' : `(replayed ${start.carried_over.length} messages)`);
+    }
+
+    beginStreaming(convoId, currentSessionId);
+
+    const receipt = await sendMessage(convoId, currentSessionId, content, await openStream(convoId), files);
+    if (receipt.queue_position !== null) {
+      // The session was still busy; the prompt waits in the queue instead
+      messages.update(msgs => msgs.filter(m => m.id !== userMessage.id && m.id !== 'streaming'));
+      resetStreamingState();
+      return;
     }
+    // Adopt the id the backend stored the prompt under
+    messages.update(msgs => msgs.map(m => m.id === userMessage.id ? receipt.message : m));
+  } catch (error) {
+    resetStreamingState();
+    console.error('Failed to send message:', error);
+    showSendError(convoId || '', error);
+  } finally {
+    isSending = false;
+  }
+}
 
-    streamingState.update(s => ({ ...s, content: '' }));
-    const assistantPlaceholder: Message = {
-      id: 'streaming',
+/**
+ * Add the assistant placeholder and start accepting stream events for `convoId`.
+ * `sessionId` may be null while the backend is still opening the session; events
+ * are accepted until it is known.
+ */
+function beginStreaming(convoId: string, sessionId: string | null, placeholder: Partial<Message> = {}) {
+  messages.update(msgs => [...msgs, {
+    id: 'streaming',
+    conversation_id: convoId,
+    role: 'assistant',
+    content: '',
+    created_at: new Date().toISOString(),
+    ...placeholder,
+  }]);
+  streamingState.set({ isActive: true, sessionId, conversationId: convoId, content: '', thinking: '', isThinking: false });
+  logger.debug('beginStreaming', 'streaming session set', { sessionId, convoId });
+}
+
+function adoptSession(convoId: string, sessionId: string) {
+  currentSessionId = sessionId;
+  sessionConversationId = convoId;
+  streamingState.update(s => s.conversationId === convoId ? { ...s, sessionId } : s);
+}
+
+function showSendError(convoId: string, error: unknown) {
+  messages.update(msgs => {
+    const filtered = msgs.filter(m => m.id !== 'streaming');
+    return [...filtered, {
+      id: crypto.randomUUID(),
       conversation_id: convoId,
-      role: 'assistant',
-      content: '',
+      role: 'assistant' as const,
+      content: `Error: ${error}`,
       created_at: new Date().toISOString(),
-    };
-    messages.update(msgs => [...msgs, assistantPlaceholder]);
+    }];
+  });
+}
 
-    streamingState.set({ isActive: true, sessionId: currentSessionId, conversationId: convoId, content: '', thinking: '', isThinking: false });
-    logger.debug('sendChatMessage', 'streaming session set', { sessionId: currentSessionId, convoId });
+/** Move a queued prompt to the 1-based `position`. */
+export async function moveQueuedPrompt(promptId: string, position: number) {
+  if (!currentSessionId) return;
+  try {
+    const { reorderQueuedPrompt } = await import('$lib/api/tauri');
+    queuedPrompts.set(await reorderQueuedPrompt(currentSessionId, promptId, position));
+  } catch (e) {
+    console.warn('Failed to reorder queued message:', e);
+  }
+}
 
-    await sendMessage(currentSessionId, content);
+/** Drop a queued prompt before it is sent. */
+export async function cancelQueuedPrompt(promptId: string) {
+  if (!currentSessionId) return;
+  try {
+    const { cancelQueuedPrompt: cancel } = await import('$lib/api/tauri');
+    queuedPrompts.set(await cancel(currentSessionId, promptId));
+  } catch (e) {
+    console.warn('Failed to cancel queued message:', e);
+  }
+}
+
+/** Ask for a new reply to the last prompt. The current reply stays reachable as a sibling. */
+export async function regenerateReply() {
+  const convoId = get(activeConversationId);
+  if (isSending || get(streamingState).isActive || !convoId) return;
+  isSending = true;
+
+  const msgs = get(messages);
+  let lastUser = msgs.length - 1;
+  while (lastUser >= 0 && msgs[lastUser].role !== 'user') lastUser--;
+  if (lastUser < 0) {
+    isSending = false;
+    return;
+  }
+  const previousReply = msgs[lastUser + 1];
+  const siblings = previousReply ? previousReply.sibling_count ?? 1 : 0;
+
+  try {
+    const { regenerateMessage } = await import('$lib/api/tauri');
+    messages.set(msgs.slice(0, lastUser + 1));
+    beginStreaming(convoId, null, { parent_id: msgs[lastUser].id, sibling_index: siblings, sibling_count: siblings + 1 });
+    const model = get(selectedModel);
+    const start = await regenerateMessage(convoId, await openStream(convoId), model ?? undefined);
+    adoptSession(convoId, start.session_id);
   } catch (error) {
     resetStreamingState();
-    console.error('Failed to send message:', error);
-    messages.update(msgs => {
-      const filtered = msgs.filter(m => m.id !== 'streaming');
-      return [...filtered, {
-        id: crypto.randomUUID(),
-        conversation_id: convoId || '',
-        role: 'assistant' as const,
-        content: `Error: ${error}`,
-        created_at: new Date().toISOString(),
-      }];
-    });
+    console.error('Failed to regenerate reply:', error);
+    showSendError(convoId, error);
   } finally {
     isSending = false;
   }
 }
 
-async function saveCurrentMessages() {
+/** Resend `messageId` with new content on a new branch; the original branch is kept. */
+export async function editAndResend(messageId: string, content: string) {
+  const convoId = get(activeConversationId);
+  if (isSending || get(streamingState).isActive || !convoId) return;
+  const msgs = get(messages);
+  const index = msgs.findIndex(m => m.id === messageId);
+  if (index < 0) return;
+  isSending = true;
+
   try {
-    const { saveMessage } = await import('$lib/api/tauri');
-
-    // Determine which messages to save: if the streaming conversation is still
-    // active we read from the store, otherwise from the cache.
-    const { conversationId: streamConvoId } = get(streamingState);
-    let currentMessages: Message[];
-    if (streamConvoId && streamConvoId !== get(activeConversationId)) {
-      currentMessages = getCachedMessages(streamConvoId) ?? [];
-    } else {
-      currentMessages = get(messages);
-    }
+    const { editMessage } = await import('$lib/api/tauri');
+    const original = msgs[index];
+    const siblings = original.sibling_count ?? 1;
+    messages.set([...msgs.slice(0, index), {
+      ...original,
+      id: crypto.randomUUID(),
+      content,
+      created_at: new Date().toISOString(),
+      interrupted: false,
+      sibling_index: siblings,
+      sibling_count: siblings + 1,
+    }]);
+    const localId = get(messages)[index].id;
+    beginStreaming(convoId, null);
+    const model = get(selectedModel);
+    const [start, stored] = await editMessage(convoId, messageId, content, await openStream(convoId), model ?? undefined);
+    adoptSession(convoId, start.session_id);
+    messages.update(list => list.map(m => m.id === localId ? stored : m));
+  } catch (error) {
+    resetStreamingState();
+    console.error('Failed to edit message:', error);
+    showSendError(convoId, error);
+  } finally {
+    isSending = false;
+  }
+}
 
-    const toSave = currentMessages.slice(-2);
-    for (const msg of toSave) {
-      if (msg.id !== 'streaming') {
-        await saveMessage(msg);
-      }
-    }
+/** Show sibling `siblingIndex` of `messageId` along with the branch below it. */
+export async function showSibling(messageId: string, siblingIndex: number) {
+  const convoId = get(activeConversationId);
+  if (isSending || get(streamingState).isActive || !convoId) return;
 
-    // Also update the cache so a subsequent switch back sees the saved state
-    if (streamConvoId) {
-      cacheMessages(streamConvoId, currentMessages.filter(m => m.id !== 'streaming'));
-    }
+  try {
+    const { switchBranch } = await import('$lib/api/tauri');
+    const branch = await switchBranch(convoId, messageId, siblingIndex);
+    // The backend released the session so the next prompt sees this branch
+    detachSession();
+    messages.set(branch);
+    cacheMessages(convoId, branch);
   } catch (e) {
-    console.warn('Failed to save messages:', e);
+    console.warn('Failed to switch branch:', e);
   }
 }
+
+/**
+ * Refresh the per-conversation cache after a turn finishes so a later switch
+ * back sees the finalized messages. Persistence itself happens in the backend.
+ */
+function cacheStreamedMessages() {
+  const { conversationId: streamConvoId } = get(streamingState);
+  if (!streamConvoId) return;
+
+  const currentMessages = streamConvoId !== get(activeConversationId)
+    ? getCachedMessages(streamConvoId) ?? []
+    : get(messages);
+  cacheMessages(streamConvoId, currentMessages.filter(m => m.id !== 'streaming'));
+}
@@ -1,6 +1,6 @@
 import { get } from 'svelte/store';
 import { conversations, activeConversationId, messages, streamingState, cacheMessages, getCachedMessages, type Conversation, type Message } from '$lib/stores/chat';
-import { resetSession } from '$lib/services/chat';
+import { detachSession } from '$lib/services/chat';
 
 /**
  * Save the current conversation's messages to the in-memory cache
@@ -33,8 +33,8 @@ export async function switchConversation(conversationId: string) {
     // Persist current conversation's messages in cache before switching
     saveCurrentToCache();
 
-    // Destroy the SDK session so the new conversation gets a fresh context
-    await resetSession();
+    // Let go of the current session; the backend keeps it bound to its conversation
+    detachSession();
 
     // If streaming was in progress for the old conversation, stop the UI indicator
     // (the background request continues and results are captured by the session-aware handlers)
@@ -58,6 +58,35 @@ export async function switchConversation(conversationId: string) {
   }
 }
 
+/** Branch the active conversation off into a new one that ends at `messageId`. */
+export async function forkFromMessage(messageId: string) {
+  const sourceId = get(activeConversationId);
+  if (!sourceId) return;
+
+  try {
+    const { forkConversation } = await import('$lib/api/tauri');
+    const fork = await forkConversation(sourceId, messageId);
+    saveCurrentToCache();
+    // The backend already bound a seeded session to the fork; the next prompt picks it up
+    detachSession();
+    streamingState.update(s => ({ ...s, isActive: false }));
+    conversations.add(fork.conversation);
+    activeConversationId.set(fork.conversation.id);
+    messages.set(fork.messages);
+  } catch (e) {
+    console.warn('Failed to fork conversation:', e);
+  }
+}
+
+/** Point a conversation at a trusted folder, or back at the sandbox with `null`. */
+export async function setWorkspace(conversationId: string, folder: string | null) {
+  const { setConversationWorkspace } = await import('$lib/api/tauri');
+  const convo = await setConversationWorkspace(conversationId, folder);
+  conversations.replace(convo);
+  // The backend dropped the old session; the next prompt opens one in the new folder
+  if (get(activeConversationId) === conversationId) detachSession();
+}
+
 export async function removeConversation(conversationId: string) {
   try {
     const { deleteConversation } = await import('$lib/api/tauri');
@@ -65,6 +94,8 @@ export async function removeConversation(conversationId: string) {
     conversations.remove(conversationId);
 
     if (get(activeConversationId) === conversationId) {
+      // The backend cancels the session bound to a deleted conversation
+      detachSession();
       activeConversationId.set(null);
       messages.set([]);
     }
@@ -78,7 +109,7 @@ export async function removeConversation(conversationId: string) {
 export async function startNewChat() {
   // Persist current conversation's messages before clearing
   saveCurrentToCache();
-  await resetSession();
+  detachSession();
   streamingState.update(s => ({ ...s, isActive: false }));
   activeConversationId.set(null);
   messages.set([]);
@@ -53,6 +53,21 @@ describe('chat store', () => {
       expect(get(conversations)[0].title).toBe('Updated');
     });
 
+    it('replaces a conversation in place', () => {
+      const convo = {
+        id: 'test-1',
+        title: 'Chat',
+        model: null,
+        created_at: '2026-01-01',
+        updated_at: '2026-01-01',
+      };
+      conversations.add(convo);
+      conversations.add({ ...convo, id: 'test-2' });
+      conversations.replace({ ...convo, workspace_folder: '/home/me/project' });
+      expect(get(conversations).map(c => c.id)).toEqual(['test-2', 'test-1']);
+      expect(get(conversations)[1].workspace_folder).toBe('/home/me/project');
+    });
+
     it('prepends new conversations', () => {
       conversations.add({
         id: '1',
@@ -1,4 +1,5 @@
 import { writable, derived } from 'svelte/store';
+import type { Attachment, ToolCall } from '$lib/api/tauri';
 
 export interface Conversation {
   id: string;
@@ -6,6 +7,12 @@ export interface Conversation {
   model: string | null;
   created_at: string;
   updated_at: string;
+  forked_from_conversation_id?: string | null;
+  forked_from_message_id?: string | null;
+  /** Folder the conversation's sessions work in; the sandbox when null */
+  workspace_folder?: string | null;
+  /** Persona the conversation was started with, if any */
+  persona_id?: string | null;
 }
 
 export interface Message {
@@ -14,7 +21,27 @@ export interface Message {
   role: 'user' | 'assistant' | 'system';
   content: string;
   thinking?: string;
+  /** Set when a stored message was left out of a rehydrated session's context */
+  outOfContext?: boolean;
   created_at: string;
+  /** Set when the reply was stopped before it finished */
+  interrupted?: boolean;
+  parent_id?: string | null;
+  /** Position among regenerated replies or edited prompts sharing the same parent */
+  sibling_index?: number;
+  sibling_count?: number;
+  /** Tools the agent ran for this reply, in the order they started */
+  tool_calls?: ToolCall[];
+  /** Files sent along with this prompt */
+  attachments?: Attachment[];
+}
+
+/** A prompt waiting for the current reply to finish */
+export interface QueuedPrompt {
+  id: string;
+  content: string;
+  created_at: string;
+  position: number;
 }
 
 function createConversationStore() {
@@ -32,12 +59,31 @@ function createConversationStore() {
     updateTitle: (id: string, title: string) => {
       update(convos => convos.map(c => c.id === id ? { ...c, title } : c));
     },
+    replace: (conversation: Conversation) => {
+      update(convos => convos.map(c => c.id === conversation.id ? conversation : c));
+    },
   };
 }
 
 export const conversations = createConversationStore();
 export const activeConversationId = writable<string | null>(null);
 export const messages = writable<Message[]>([]);
+/** A tool asking for permission, waiting for the user's answer */
+export interface PermissionRequest {
+  request_id: string;
+  session_id: string;
+  conversation_id: string;
+  /** shell, write, read, url or mcp */
+  kind: string;
+  tool: string;
+  /** File path or command line, when the request names one */
+  target: string | null;
+  details: Record<string, unknown>;
+}
+
+export const permissionRequests = writable<PermissionRequest[]>([]);
+/** Prompts queued behind the reply streaming in the current session */
+export const queuedPrompts = writable<QueuedPrompt[]>([]);
 export interface StreamingState {
   isActive: boolean;
   sessionId: string | null;
@@ -45,6 +91,8 @@ export interface StreamingState {
   content: string;
   thinking: string;
   isThinking: boolean;
+  /** Set while the backend waits to retry a failed send */
+  retry?: { attempt: number; maxAttempts: number } | null;
 }
 
 export const streamingState = writable<StreamingState>({
@@ -5,6 +5,10 @@ export interface ModelInfo {
   id: string;
   name: string;
   provider: string | null;
+  /** Whether the model accepts image attachments */
+  supports_vision?: boolean;
+  /** Tokens the model can take in at once, when known */
+  context_window_tokens?: number | null;
 }
 
 export const models = writable<ModelInfo[]>([]);
@@ -0,0 +1,31 @@
+import { writable, get } from 'svelte/store';
+import type { Persona } from '$lib/api/tauri';
+import { selectedModel } from '$lib/stores/models';
+
+export const personas = writable<Persona[]>([]);
+/** Persona the next new conversation starts with; null for the global settings. */
+export const selectedPersonaId = writable<string | null>(null);
+
+export async function loadPersonas() {
+  try {
+    const { listPersonas } = await import('$lib/api/tauri');
+    const list = await listPersonas();
+    personas.set(list);
+    // Forget a selection whose persona was deleted
+    const selected = get(selectedPersonaId);
+    if (selected && !list.some(p => p.id === selected)) {
+      selectedPersonaId.set(null);
+    }
+  } catch (error) {
+    console.warn('Failed to load personas:', error);
+  }
+}
+
+/** Selects a persona for new conversations and switches to its model, if it has one. */
+export function selectPersona(personaId: string | null) {
+  selectedPersonaId.set(personaId);
+  const persona = get(personas).find(p => p.id === personaId);
+  if (persona?.model) {
+    selectedModel.set(persona.model);
+  }
+}
@@ -1,9 +1,16 @@
 import { writable, get } from 'svelte/store';
+import type { DeadlineSettings } from '$lib/api/tauri';
 
 export interface AppSettings {
   theme: string;
   default_model: string | null;
   system_prompt: string | null;
+  context_budget_chars?: number | null;
+  session_idle_ttl_secs?: number | null;
+  max_sessions?: number | null;
+  send_retry_limit?: number | null;
+  response_deadlines?: DeadlineSettings | null;
+  sandbox_retention_hours?: number | null;
 }
 
 export const settings = writable<AppSettings>({